@@ -0,0 +1,513 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+const DEFAULT_MAX_BYTES: usize = 256 * 1024 * 1024; // 256MB
+const DEFAULT_STALE_WINDOW: Duration = Duration::from_secs(600);
+
+/// Counters describing how well the cache is doing.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Hits served from an expired entry while it was being refreshed.
+    pub stale_hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+    /// Callers that waited on an identical upstream request instead of sending their own.
+    pub coalesced: u64,
+    /// Background refreshes started for stale or warmed-up entries.
+    pub refreshes: u64,
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
+/// Raw description of one stored response, as reported by a backend.
+#[derive(Debug, Clone)]
+pub struct CacheEntryInfo {
+    pub key: String,
+    pub age: Duration,
+    pub ttl: Duration,
+    pub stale_window: Duration,
+    pub size: usize,
+}
+
+/// Result of a cache lookup.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheLookup {
+    Fresh(Value),
+    /// Past its TTL but still inside its stale window: usable while a refresh runs.
+    Stale(Value),
+}
+
+/// Storage for proxied Wazuh responses.
+///
+/// Implementations are expected to be internally synchronised; `WazuhClient`
+/// shares one backend between all requests behind an `Arc`.
+pub trait CacheBackend: Send + Sync {
+    /// Returns the value stored under `key` unless it has outlived both its TTL
+    /// and its stale window.
+    fn get(&self, key: &str) -> Option<CacheLookup>;
+    /// Stores `data` under `key`. `size` is the encoded body length in bytes and
+    /// is what counts against the byte budget. After `ttl` the entry is served as
+    /// stale for another `stale_window`, then dropped.
+    fn insert(&self, key: &str, data: Value, size: usize, ttl: Duration, stale_window: Duration);
+    /// Lists every stored entry, including ones that have expired but were not yet dropped.
+    fn entries(&self) -> Vec<CacheEntryInfo>;
+    /// Drops every entry whose key matches and returns how many were removed.
+    fn remove_matching(&self, matches: &dyn Fn(&str) -> bool) -> usize;
+    /// Drops everything and returns how many entries were removed.
+    fn clear(&self) -> usize;
+    fn stats(&self) -> CacheStats;
+}
+
+struct Slot {
+    data: Value,
+    size: usize,
+    stored_at: Instant,
+    ttl: Duration,
+    stale_window: Duration,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct LruState {
+    slots: HashMap<String, Slot>,
+    // Access order: lowest tick is the least recently used key.
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    bytes: usize,
+}
+
+impl LruState {
+    fn touch(&mut self, key: &str) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(slot) = self.slots.get_mut(key) {
+            self.order.remove(&slot.tick);
+            slot.tick = tick;
+            self.order.insert(tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Slot> {
+        let slot = self.slots.remove(key)?;
+        self.order.remove(&slot.tick);
+        self.bytes -= slot.size;
+        Some(slot)
+    }
+
+    fn pop_oldest(&mut self) -> Option<Slot> {
+        let (_, key) = self.order.pop_first()?;
+        let slot = self.slots.remove(&key)?;
+        self.bytes -= slot.size;
+        Some(slot)
+    }
+}
+
+/// In-memory LRU bounded both by entry count and by total body size.
+pub struct LruCache {
+    state: Mutex<LruState>,
+    max_entries: usize,
+    max_bytes: usize,
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+impl LruCache {
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            state: Mutex::new(LruState::default()),
+            max_entries,
+            max_bytes,
+            hits: AtomicU64::new(0),
+            stale_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            expirations: AtomicU64::new(0),
+        }
+    }
+}
+
+impl CacheBackend for LruCache {
+    fn get(&self, key: &str) -> Option<CacheLookup> {
+        let mut state = self.state.lock().unwrap();
+
+        let (age, ttl, stale_window) = match state.slots.get(key) {
+            Some(slot) => (slot.stored_at.elapsed(), slot.ttl, slot.stale_window),
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        };
+
+        if age >= ttl + stale_window {
+            state.remove(key);
+            self.expirations.fetch_add(1, Ordering::Relaxed);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        state.touch(key);
+        let data = state.slots.get(key).map(|slot| slot.data.clone())?;
+        if age < ttl {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            Some(CacheLookup::Fresh(data))
+        } else {
+            self.stale_hits.fetch_add(1, Ordering::Relaxed);
+            Some(CacheLookup::Stale(data))
+        }
+    }
+
+    fn insert(&self, key: &str, data: Value, size: usize, ttl: Duration, stale_window: Duration) {
+        // A single body larger than the whole budget would just flush everything else.
+        if size > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.remove(key);
+
+        let tick = state.next_tick;
+        state.next_tick += 1;
+        state.order.insert(tick, key.to_string());
+        state.bytes += size;
+        state.slots.insert(key.to_string(), Slot {
+            data,
+            size,
+            stored_at: Instant::now(),
+            ttl,
+            stale_window,
+            tick,
+        });
+
+        while state.slots.len() > self.max_entries || state.bytes > self.max_bytes {
+            if state.pop_oldest().is_none() {
+                break;
+            }
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn entries(&self) -> Vec<CacheEntryInfo> {
+        let state = self.state.lock().unwrap();
+        state.slots
+            .iter()
+            .map(|(key, slot)| CacheEntryInfo {
+                key: key.clone(),
+                age: slot.stored_at.elapsed(),
+                ttl: slot.ttl,
+                stale_window: slot.stale_window,
+                size: slot.size,
+            })
+            .collect()
+    }
+
+    fn remove_matching(&self, matches: &dyn Fn(&str) -> bool) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let keys: Vec<String> = state.slots.keys().filter(|k| matches(k)).cloned().collect();
+        for key in &keys {
+            state.remove(key);
+        }
+        keys.len()
+    }
+
+    fn clear(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.slots.len();
+        *state = LruState::default();
+        removed
+    }
+
+    fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            stale_hits: self.stale_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+            coalesced: 0,
+            refreshes: 0,
+            entries: state.slots.len(),
+            bytes: state.bytes,
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+/// Cache lifetimes keyed by Wazuh path prefix. The longest matching prefix wins;
+/// a zero TTL means responses for that prefix are never cached.
+#[derive(Debug, Clone)]
+pub struct TtlPolicy {
+    rules: Vec<(String, Duration)>,
+    default_ttl: Duration,
+}
+
+impl Default for TtlPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+            .with_rule("mitre/*", Duration::from_secs(6 * 60 * 60))
+            .with_rule("agents/summary/status", Duration::from_secs(10))
+            .with_rule("manager/logs", Duration::ZERO)
+            .with_rule("tasks/status", Duration::ZERO)
+    }
+}
+
+impl TtlPolicy {
+    pub fn new(default_ttl: Duration) -> Self {
+        Self {
+            rules: Vec::new(),
+            default_ttl,
+        }
+    }
+
+    /// Adds or replaces the TTL for `prefix`. A trailing `*` is accepted and ignored,
+    /// so `mitre/*` and `mitre/` are the same rule.
+    pub fn with_rule(mut self, prefix: &str, ttl: Duration) -> Self {
+        let prefix = Self::normalize(prefix);
+        self.rules.retain(|(p, _)| *p != prefix);
+        self.rules.push((prefix, ttl));
+        // Longest prefix first so the first match is the most specific one.
+        self.rules.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    pub fn ttl_for(&self, path: &str) -> Duration {
+        let path = path.trim_start_matches('/');
+        self.rules
+            .iter()
+            .find(|(prefix, _)| Self::covers(prefix, path))
+            .map(|(_, ttl)| *ttl)
+            .unwrap_or(self.default_ttl)
+    }
+
+    /// `prefix` covers `path` only on a segment boundary, so `agents` does not
+    /// match `agentsX/...`.
+    fn covers(prefix: &str, path: &str) -> bool {
+        match path.strip_prefix(prefix) {
+            Some(rest) => prefix.is_empty() || prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?']),
+            None => false,
+        }
+    }
+
+    /// Parses `prefix=seconds` pairs separated by commas, e.g.
+    /// `mitre/*=21600,agents/summary/status=10,manager/logs=0`.
+    pub fn parse_rules(mut self, spec: &str) -> Result<Self, String> {
+        for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let (prefix, secs) = rule
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid cache TTL rule '{}': expected prefix=seconds", rule))?;
+            let secs: u64 = secs
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid cache TTL rule '{}': seconds must be a number", rule))?;
+            self = self.with_rule(prefix.trim(), Duration::from_secs(secs));
+        }
+        Ok(self)
+    }
+
+    fn normalize(prefix: &str) -> String {
+        prefix.trim_start_matches('/').trim_end_matches('*').to_string()
+    }
+}
+
+/// Sizing and lifetime settings for the response cache.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub ttl: TtlPolicy,
+    /// How long past its TTL an entry may still be served while it is refreshed
+    /// in the background. Zero disables stale-while-revalidate.
+    pub stale_window: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            ttl: TtlPolicy::default(),
+            stale_window: DEFAULT_STALE_WINDOW,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Reads `NEXUS_CACHE_MAX_ENTRIES`, `NEXUS_CACHE_MAX_BYTES`,
+    /// `NEXUS_CACHE_DEFAULT_TTL` (seconds), `NEXUS_CACHE_STALE_WINDOW` (seconds)
+    /// and `NEXUS_CACHE_TTL` (rules, see [`TtlPolicy::parse_rules`]). Unset
+    /// variables keep their defaults.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+
+        if let Ok(value) = env::var("NEXUS_CACHE_MAX_ENTRIES") {
+            config.max_entries = value
+                .parse()
+                .map_err(|_| format!("NEXUS_CACHE_MAX_ENTRIES must be a number, got '{}'", value))?;
+        }
+        if let Ok(value) = env::var("NEXUS_CACHE_MAX_BYTES") {
+            config.max_bytes = value
+                .parse()
+                .map_err(|_| format!("NEXUS_CACHE_MAX_BYTES must be a number, got '{}'", value))?;
+        }
+        if let Ok(value) = env::var("NEXUS_CACHE_DEFAULT_TTL") {
+            let secs: u64 = value
+                .parse()
+                .map_err(|_| format!("NEXUS_CACHE_DEFAULT_TTL must be a number, got '{}'", value))?;
+            config.ttl.default_ttl = Duration::from_secs(secs);
+        }
+        if let Ok(value) = env::var("NEXUS_CACHE_STALE_WINDOW") {
+            let secs: u64 = value
+                .parse()
+                .map_err(|_| format!("NEXUS_CACHE_STALE_WINDOW must be a number, got '{}'", value))?;
+            config.stale_window = Duration::from_secs(secs);
+        }
+        if let Ok(value) = env::var("NEXUS_CACHE_TTL") {
+            config.ttl = config.ttl.parse_rules(&value)?;
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    const NONE: Duration = Duration::ZERO;
+
+    fn fresh(value: Value) -> Option<CacheLookup> {
+        Some(CacheLookup::Fresh(value))
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used_entry() {
+        let cache = LruCache::new(2, 1024);
+        cache.insert("a", json!(1), 1, HOUR, NONE);
+        cache.insert("b", json!(2), 1, HOUR, NONE);
+
+        // Touch "a" so "b" becomes the oldest.
+        assert_eq!(cache.get("a"), fresh(json!(1)));
+        cache.insert("c", json!(3), 1, HOUR, NONE);
+
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), fresh(json!(1)));
+        assert_eq!(cache.get("c"), fresh(json!(3)));
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_lru_respects_byte_budget() {
+        let cache = LruCache::new(100, 10);
+        cache.insert("a", json!("a"), 4, HOUR, NONE);
+        cache.insert("b", json!("b"), 4, HOUR, NONE);
+        cache.insert("c", json!("c"), 4, HOUR, NONE);
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.bytes, 8);
+        assert_eq!(cache.get("a"), None);
+
+        // Larger than the whole budget: never stored.
+        cache.insert("huge", json!("huge"), 11, HOUR, NONE);
+        assert_eq!(cache.get("huge"), None);
+    }
+
+    #[test]
+    fn test_lru_expires_entries_and_counts() {
+        let cache = LruCache::new(10, 1024);
+        cache.insert("a", json!(1), 1, Duration::ZERO, NONE);
+
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("missing"), None);
+
+        let stats = cache.stats();
+        assert_eq!(stats.expirations, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.bytes, 0);
+    }
+
+    #[test]
+    fn test_lru_serves_stale_inside_window() {
+        let cache = LruCache::new(10, 1024);
+        cache.insert("a", json!(1), 1, Duration::ZERO, HOUR);
+
+        assert_eq!(cache.get("a"), Some(CacheLookup::Stale(json!(1))));
+        let stats = cache.stats();
+        assert_eq!(stats.stale_hits, 1);
+        assert_eq!(stats.hits, 0);
+        assert_eq!(stats.entries, 1);
+    }
+
+    #[test]
+    fn test_lru_remove_matching_and_clear() {
+        let cache = LruCache::new(10, 1024);
+        cache.insert("http://w/syscollector/001/os|t", json!(1), 3, HOUR, NONE);
+        cache.insert("http://w/syscollector/002/os|t", json!(2), 3, HOUR, NONE);
+        cache.insert("http://w/rules|t", json!(3), 3, HOUR, NONE);
+
+        let removed = cache.remove_matching(&|key| key.contains("/syscollector/001/"));
+        assert_eq!(removed, 1);
+        assert_eq!(cache.entries().len(), 2);
+        assert_eq!(cache.stats().bytes, 6);
+
+        assert_eq!(cache.clear(), 2);
+        assert_eq!(cache.stats().bytes, 0);
+        assert!(cache.entries().is_empty());
+    }
+
+    #[test]
+    fn test_ttl_policy_longest_prefix_wins() {
+        let policy = TtlPolicy::new(Duration::from_secs(300))
+            .with_rule("agents/*", Duration::from_secs(60))
+            .with_rule("agents/summary/status", Duration::from_secs(5))
+            .with_rule("manager/logs", Duration::ZERO);
+
+        assert_eq!(policy.ttl_for("agents/summary/status"), Duration::from_secs(5));
+        assert_eq!(policy.ttl_for("/agents/summary/os"), Duration::from_secs(60));
+        assert_eq!(policy.ttl_for("manager/logs/summary"), Duration::ZERO);
+        assert_eq!(policy.ttl_for("rules"), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_ttl_policy_matches_whole_segments() {
+        let policy = TtlPolicy::new(Duration::from_secs(300))
+            .with_rule("agents", Duration::from_secs(60))
+            .with_rule("mitre/*", Duration::ZERO);
+
+        assert_eq!(policy.ttl_for("agents"), Duration::from_secs(60));
+        assert_eq!(policy.ttl_for("agents/001"), Duration::from_secs(60));
+        assert_eq!(policy.ttl_for("agents?limit=10"), Duration::from_secs(60));
+        assert_eq!(policy.ttl_for("agentsX/001"), Duration::from_secs(300));
+        assert_eq!(policy.ttl_for("mitreX"), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_ttl_policy_parse_rules() {
+        let policy = TtlPolicy::new(Duration::from_secs(300))
+            .parse_rules("mitre/*=21600, groups=0")
+            .unwrap();
+
+        assert_eq!(policy.ttl_for("mitre/techniques"), Duration::from_secs(21600));
+        assert_eq!(policy.ttl_for("groups/default/agents"), Duration::ZERO);
+        assert!(TtlPolicy::default().parse_rules("mitre").is_err());
+        assert!(TtlPolicy::default().parse_rules("mitre=soon").is_err());
+    }
+}
@@ -1,97 +1,445 @@
-use reqwest::{Client, Response};
+pub mod cache;
+pub mod resilience;
+pub mod tls;
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::Serialize;
 use serde_json::Value;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::env;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use dotenv::dotenv;
+use crate::shared::error::NexusError;
+
+pub use cache::{CacheBackend, CacheConfig, CacheEntryInfo, CacheLookup, CacheStats, LruCache, TtlPolicy};
+pub use resilience::{BreakerConfig, BreakerSnapshot, CircuitBreaker, RetryConfig};
+pub use tls::TlsConfig;
 
-struct CacheEntry {
-    data: Value,
-    timestamp: Instant,
+/// A cache entry as shown to operators: the token is replaced by a short
+/// fingerprint so entries can be grouped per user without exposing the JWT.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntrySummary {
+    pub url: String,
+    pub token_fingerprint: Option<String>,
+    pub age_secs: u64,
+    pub ttl_secs: u64,
+    pub stale_window_secs: u64,
+    /// Past its TTL; served stale until the stale window runs out.
+    pub expired: bool,
+    pub size_bytes: usize,
+}
+
+type Flight = Shared<BoxFuture<'static, Result<Value, NexusError>>>;
+
+/// HTTP client and circuit breaker of one configured manager.
+struct UpstreamHttp {
+    name: String,
+    base_url: String,
+    client: Client,
+    breaker: CircuitBreaker,
 }
 
 #[derive(Clone)]
 pub struct WazuhClient {
     client: Client,
-    cache: Arc<RwLock<HashMap<String, CacheEntry>>>,
-    cache_duration: Duration,
+    // One HTTP client and breaker per configured manager, so each gets its own
+    // TLS settings and fails fast on its own. URLs outside them use `client`.
+    upstreams: Arc<Vec<Arc<UpstreamHttp>>>,
+    retry: RetryConfig,
+    breaker: BreakerConfig,
+    cache: Arc<dyn CacheBackend>,
+    ttl: Arc<TtlPolicy>,
+    stale_window: Duration,
+    // Upstream fetches currently running, keyed like the cache, so concurrent
+    // misses for the same key share one request.
+    inflight: Arc<Mutex<HashMap<String, Flight>>>,
+    coalesced: Arc<AtomicU64>,
+    refreshes: Arc<AtomicU64>,
+}
+
+impl Default for WazuhClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WazuhClient {
     pub fn new() -> Self {
+        Self::with_cache_config(&CacheConfig::default())
+    }
+
+    pub fn with_cache_config(config: &CacheConfig) -> Self {
+        let backend = LruCache::new(config.max_entries, config.max_bytes);
+        Self::with_backend(Arc::new(backend), config.ttl.clone(), config.stale_window)
+    }
+
+    /// Builds a client on top of any cache implementation.
+    pub fn with_backend(cache: Arc<dyn CacheBackend>, ttl: TtlPolicy, stale_window: Duration) -> Self {
         dotenv().ok(); // Load environment variables from .env file
         
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-        
         Self { 
-            client,
-            cache: Arc::new(RwLock::new(HashMap::new())),
-            cache_duration: Duration::from_secs(300), // 5 minutes default cache
+            client: Client::new(),
+            upstreams: Arc::new(Vec::new()),
+            retry: RetryConfig::default(),
+            breaker: BreakerConfig::default(),
+            cache,
+            ttl: Arc::new(ttl),
+            stale_window,
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+            coalesced: Arc::new(AtomicU64::new(0)),
+            refreshes: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    pub async fn get_cached(&self, url: &str, token: Option<&str>) -> Result<Value, String> {
-        let cache_key = match token {
-            Some(t) => format!("{}:{}", url, t),
-            None => url.to_string(),
+    /// Sets how requests are retried and when upstream breakers open. Call it
+    /// before `with_upstream`, which creates the breakers.
+    pub fn with_resilience(mut self, retry: RetryConfig, breaker: BreakerConfig) -> Self {
+        self.retry = retry;
+        self.breaker = breaker;
+        self
+    }
+
+    /// Sends requests for URLs under `base_url` through a client built from `tls`,
+    /// guarded by a circuit breaker of its own.
+    pub fn with_upstream(mut self, name: &str, base_url: &str, tls: &TlsConfig) -> Result<Self, String> {
+        let upstream = UpstreamHttp {
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: tls.build_client(name)?,
+            breaker: CircuitBreaker::new(self.breaker.clone()),
         };
+        Arc::make_mut(&mut self.upstreams).push(Arc::new(upstream));
+        Ok(self)
+    }
 
-        // Try to get from cache first
-        if let Some(cached_data) = self.get_from_cache(&cache_key).await {
-            return Ok(cached_data);
-        }
+    /// The configured manager serving `url`: the longest matching base URL.
+    fn upstream_for(&self, url: &str) -> Option<&UpstreamHttp> {
+        self.upstreams
+            .iter()
+            .filter(|u| {
+                url.strip_prefix(u.base_url.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || rest.starts_with('?'))
+            })
+            .max_by_key(|u| u.base_url.len())
+            .map(|u| u.as_ref())
+    }
 
-        // If not in cache, make the request
-        let response = self.get(url, token).await
-            .map_err(|e| format!("Request failed: {}", e))?;
+    /// Breaker state of the manager registered as `name`.
+    pub fn breaker_state(&self, name: &str) -> Option<BreakerSnapshot> {
+        self.upstreams.iter().find(|u| u.name == name).map(|u| u.breaker.snapshot())
+    }
 
-        let data = Self::handle_json_response(response).await?;
-        
-        // Store in cache
-        self.store_in_cache(&cache_key, data.clone()).await;
-        
-        Ok(data)
+    /// Sends the request built by `build`, retrying transport errors, 502/503/504 and
+    /// 429 (honouring `Retry-After`) with jittered exponential backoff. Requests
+    /// to a manager whose breaker is open fail fast with `CircuitOpen`.
+    async fn send(&self, url: &str, build: impl Fn(&Client) -> RequestBuilder) -> Result<Response, NexusError> {
+        self.dispatch(url, true, build).await
+    }
+
+    /// Like [`send`](Self::send), but for calls that change state on the manager.
+    /// Those are only retried when Wazuh cannot have acted on them: the
+    /// connection was never established, or the manager answered 429.
+    async fn send_once(&self, url: &str, build: impl Fn(&Client) -> RequestBuilder) -> Result<Response, NexusError> {
+        self.dispatch(url, false, build).await
+    }
+
+    async fn dispatch(&self, url: &str, idempotent: bool, build: impl Fn(&Client) -> RequestBuilder) -> Result<Response, NexusError> {
+        let upstream = self.upstream_for(url);
+        let client = upstream.map(|u| &u.client).unwrap_or(&self.client);
+        let mut retries = 0;
+
+        loop {
+            let attempt = match upstream {
+                Some(upstream) => Some(upstream.breaker.try_acquire().map_err(|retry_in| NexusError::CircuitOpen {
+                    upstream: upstream.name.clone(),
+                    retry_in_secs: retry_in.as_secs(),
+                })?),
+                None => None,
+            };
+
+            let result = build(client).send().await;
+
+            if let Some(attempt) = attempt {
+                match &result {
+                    Ok(response) if !response.status().is_server_error() => attempt.succeeded(),
+                    _ => attempt.failed(),
+                }
+            }
+
+            let delay = if retries >= self.retry.max_retries {
+                None
+            } else {
+                match &result {
+                    Err(e) if !idempotent && !e.is_connect() => None,
+                    Err(e) if !e.is_builder() => Some(self.retry.backoff(retries + 1)),
+                    Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                        match resilience::retry_after(response.headers()) {
+                            Some(wait) if wait > self.retry.max_retry_after => None,
+                            Some(wait) => Some(wait),
+                            None => Some(self.retry.backoff(retries + 1)),
+                        }
+                    }
+                    Ok(response) if idempotent && resilience::is_retryable(response.status()) => Some(self.retry.backoff(retries + 1)),
+                    _ => None,
+                }
+            };
+
+            let Some(delay) = delay else {
+                return result.map_err(NexusError::transport);
+            };
+
+            retries += 1;
+            match &result {
+                Ok(response) => println!("Wazuh answered {} for {}, retrying ({}/{}) in {:?}", response.status(), url, retries, self.retry.max_retries, delay),
+                Err(e) => println!("Request to {} failed, retrying ({}/{}) in {:?}: {}", url, retries, self.retry.max_retries, delay, e),
+            }
+            tokio::time::sleep(delay).await;
+        }
     }
 
-    async fn get_from_cache(&self, key: &str) -> Option<Value> {
-        let cache = self.cache.read().await;
-        if let Some(entry) = cache.get(key) {
-            if entry.timestamp.elapsed() < self.cache_duration {
-                return Some(entry.data.clone());
+    pub async fn get_cached(&self, url: &str, token: Option<&str>) -> Result<Value, NexusError> {
+        let ttl = self.ttl.ttl_for(&Self::wazuh_path(url));
+
+        // Uncacheable paths always go straight to Wazuh
+        if ttl.is_zero() {
+            return self.fetch_json(url, token).await.map(|(data, _)| data);
+        }
+
+        let cache_key = Self::cache_key(url, token);
+
+        // Try to get from cache first; a stale entry is served as-is and refreshed behind the caller
+        match self.cache.get(&cache_key) {
+            Some(CacheLookup::Fresh(cached_data)) => return Ok(cached_data),
+            Some(CacheLookup::Stale(cached_data)) => {
+                self.refresh_in_background(url, token, cache_key, ttl);
+                return Ok(cached_data);
             }
+            None => {}
+        }
+
+        // If not in cache, join the request already in flight or start one
+        let (flight, joined) = self.flight_for(url, token, cache_key, ttl);
+        if joined {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
         }
-        None
+        flight.await
+    }
+
+    /// Fetches `url` into the cache regardless of what is cached now, e.g. to
+    /// warm up slow endpoints ahead of the first caller.
+    pub async fn prefetch(&self, url: &str, token: Option<&str>) -> Result<(), NexusError> {
+        let ttl = self.ttl.ttl_for(&Self::wazuh_path(url));
+        if ttl.is_zero() {
+            return Ok(());
+        }
+
+        self.refreshes.fetch_add(1, Ordering::Relaxed);
+        let (flight, _) = self.flight_for(url, token, Self::cache_key(url, token), ttl);
+        flight.await.map(|_| ())
     }
 
-    async fn store_in_cache(&self, key: &str, data: Value) {
-        let mut cache = self.cache.write().await;
-        cache.insert(key.to_string(), CacheEntry {
-            data,
-            timestamp: Instant::now(),
+    fn refresh_in_background(&self, url: &str, token: Option<&str>, cache_key: String, ttl: Duration) {
+        let (flight, joined) = self.flight_for(url, token, cache_key, ttl);
+        if joined {
+            return; // Somebody is already refreshing this key
+        }
+
+        self.refreshes.fetch_add(1, Ordering::Relaxed);
+        let url = url.to_string();
+        tokio::spawn(async move {
+            // On failure the stale entry stays until its window runs out.
+            if let Err(e) = flight.await {
+                println!("Background refresh failed for {}: {}", url, e);
+            }
         });
     }
 
-    pub async fn get(&self, url: &str, token: Option<&str>) -> Result<Response, reqwest::Error> {
-        let mut request = self.client.get(url);
-        
-        if let Some(token) = token {
-            request = request.header("Authorization", format!("Bearer {}", token));
+    /// Returns the flight for `cache_key`, starting one if none is running.
+    /// The flag is `true` when an existing flight was joined.
+    fn flight_for(&self, url: &str, token: Option<&str>, cache_key: String, ttl: Duration) -> (Flight, bool) {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(flight) = inflight.get(&cache_key) {
+            return (flight.clone(), true);
         }
-        
-        request.send().await
+
+        let this = self.clone();
+        let url = url.to_string();
+        let token = token.map(String::from);
+        let key = cache_key.clone();
+        let flight = async move {
+            let result = this.fetch_json(&url, token.as_deref()).await;
+            if let Ok((data, size)) = &result {
+                this.cache.insert(&key, data.clone(), *size, ttl, this.stale_window);
+            }
+            // Only forget the flight once the result is in the cache, so later
+            // callers either join this flight or hit the cache.
+            this.inflight.lock().unwrap().remove(&key);
+            result.map(|(data, _)| data)
+        }
+        .boxed()
+        .shared();
+
+        inflight.insert(cache_key, flight.clone());
+        (flight, false)
     }
 
-    pub async fn get_with_auth(&self, url: &str, username: &str, password: &str) -> Result<Response, reqwest::Error> {
-        self.client
-            .get(url)
-            .basic_auth(username, Some(password))
-            .send()
-            .await
+    /// Fetches `url` as JSON without reading or filling the cache.
+    pub async fn get_json(&self, url: &str, token: Option<&str>) -> Result<Value, NexusError> {
+        self.fetch_json(url, token).await.map(|(data, _)| data)
+    }
+
+    /// Fetches `url` and parses it as JSON, returning the value and the body size.
+    /// Non-2xx answers become `NexusError::Upstream` and are never cached.
+    async fn fetch_json(&self, url: &str, token: Option<&str>) -> Result<(Value, usize), NexusError> {
+        let response = self.get(url, token).await?;
+        Self::read_json(response).await
+    }
+
+    /// Parses a Wazuh answer as JSON; non-2xx answers become `NexusError::Upstream`.
+    pub async fn json_result(response: Response) -> Result<Value, NexusError> {
+        Self::read_json(response).await.map(|(data, _)| data)
+    }
+
+    async fn read_json(response: Response) -> Result<(Value, usize), NexusError> {
+        let status = response.status();
+        let body = response.bytes().await
+            .map_err(NexusError::transport)?;
+
+        if !status.is_success() {
+            let body = serde_json::from_slice(&body)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+            return Err(NexusError::Upstream { status: status.as_u16(), body });
+        }
+
+        let data: Value = serde_json::from_slice(&body)
+            .map_err(|e| NexusError::Parse(e.to_string()))?;
+
+        Ok((data, body.len()))
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            refreshes: self.refreshes.load(Ordering::Relaxed),
+            ..self.cache.stats()
+        }
+    }
+
+    /// Lists cached entries, oldest first, with tokens redacted.
+    pub fn cache_entries(&self) -> Vec<CacheEntrySummary> {
+        let mut entries: Vec<CacheEntrySummary> = self.cache
+            .entries()
+            .into_iter()
+            .map(|entry| {
+                let (url, token) = Self::split_cache_key(&entry.key);
+                CacheEntrySummary {
+                    url: url.to_string(),
+                    token_fingerprint: token.map(Self::token_fingerprint),
+                    age_secs: entry.age.as_secs(),
+                    ttl_secs: entry.ttl.as_secs(),
+                    stale_window_secs: entry.stale_window.as_secs(),
+                    expired: entry.age >= entry.ttl,
+                    size_bytes: entry.size,
+                }
+            })
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.age_secs));
+        entries
+    }
+
+    /// Drops every entry whose full URL starts with `prefix`.
+    pub fn invalidate_url_prefix(&self, prefix: &str) -> usize {
+        self.cache.remove_matching(&|key| Self::split_cache_key(key).0.starts_with(prefix))
+    }
+
+    /// Drops every entry whose Wazuh path starts with `path`, on any manager,
+    /// e.g. `syscollector/001` or `mitre`.
+    pub fn invalidate_path_prefix(&self, path: &str) -> usize {
+        let path = path.trim_matches('/');
+        self.cache.remove_matching(&|key| {
+            let wazuh_path = Self::wazuh_path(Self::split_cache_key(key).0);
+            wazuh_path == path || wazuh_path.starts_with(&format!("{}/", path))
+        })
+    }
+
+    /// Drops every entry fetched with `token`, e.g. after it was revoked.
+    pub fn invalidate_token(&self, token: &str) -> usize {
+        self.cache.remove_matching(&|key| Self::split_cache_key(key).1 == Some(token))
+    }
+
+    pub fn flush_cache(&self) -> usize {
+        self.cache.clear()
+    }
+
+    /// Cache keys are `{url}|{token}`; JWTs never contain `|`, so the token part
+    /// can always be split off again with `rsplit_once`.
+    fn cache_key(url: &str, token: Option<&str>) -> String {
+        match token {
+            Some(t) => format!("{}|{}", url, t),
+            None => url.to_string(),
+        }
+    }
+
+    fn split_cache_key(key: &str) -> (&str, Option<&str>) {
+        match key.rsplit_once('|') {
+            Some((url, token)) => (url, Some(token)),
+            None => (key, None),
+        }
+    }
+
+    /// Short, stable identifier of a token that does not reveal it.
+    pub fn token_fingerprint(token: &str) -> String {
+        let digest = Sha256::digest(token.as_bytes());
+        digest[..4].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The Wazuh API path of `url`, used to look up its TTL.
+    fn wazuh_path(url: &str) -> String {
+        Url::parse(url)
+            .map(|u| u.path().trim_start_matches('/').to_string())
+            .unwrap_or_default()
+    }
+
+    pub async fn get(&self, url: &str, token: Option<&str>) -> Result<Response, NexusError> {
+        self.send(url, |client| {
+            let request = client.get(url);
+            match token {
+                Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+                None => request,
+            }
+        })
+        .await
+    }
+
+    pub async fn get_with_auth(&self, url: &str, username: &str, password: &str) -> Result<Response, NexusError> {
+        self.send(url, |client| client.get(url).basic_auth(username, Some(password))).await
+    }
+
+    /// `POST` with basic auth and a JSON body, e.g. `security/user/authenticate/run_as`.
+    /// Not retried once it may have reached Wazuh, like [`send_write`](Self::send_write).
+    pub async fn post_with_auth(&self, url: &str, username: &str, password: &str, body: &Value) -> Result<Response, NexusError> {
+        self.send_once(url, |client| client.post(url).basic_auth(username, Some(password)).json(body)).await
+    }
+
+    pub async fn delete(&self, url: &str, token: &str) -> Result<Response, NexusError> {
+        self.send_once(url, |client| client.delete(url).bearer_auth(token)).await
+    }
+
+    /// A state-changing call (`PUT`, `POST`, `DELETE` on agents, ...) with the
+    /// caller's token and an optional JSON body. Never cached and never retried
+    /// once the request may have reached Wazuh.
+    pub async fn send_write(&self, method: Method, url: &str, token: &str, body: Option<&Value>) -> Result<Response, NexusError> {
+        self.send_once(url, |client| {
+            let request = client.request(method.clone(), url).bearer_auth(token);
+            match body {
+                Some(body) => request.json(body),
+                None => request,
+            }
+        })
+        .await
     }
 
     pub async fn handle_json_response(response: Response) -> Result<Value, String> {
@@ -104,6 +452,8 @@ impl WazuhClient {
     // This method is only used in tests
     #[cfg(test)]
     pub async fn get_auth_token(&self) -> Result<String, String> {
+        use std::env;
+
         dotenv().ok(); // Ensure environment variables are loaded in tests
         
         let wazuh_url = env::var("WAZUH_URL").expect("WAZUH_URL must be set");
@@ -131,6 +481,7 @@ impl WazuhClient {
 mod tests {
     use super::*;
     use dotenv::dotenv;
+    use std::env;
 
     #[tokio::test]
     async fn test_cache_with_auth() {
@@ -0,0 +1,332 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How GETs to Wazuh are retried.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Extra attempts after the first one; `0` disables retries.
+    pub max_retries: u32,
+    /// Backoff before the first retry; doubled for every further retry.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Longest `Retry-After` on a 429 that is still waited for; longer ones
+    /// are returned to the caller as-is.
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            max_retry_after: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Reads `NEXUS_RETRY_MAX`, `NEXUS_RETRY_BASE_MS`, `NEXUS_RETRY_MAX_DELAY_MS`
+    /// and `NEXUS_RETRY_AFTER_MAX` (seconds).
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Some(value) = env_u64("NEXUS_RETRY_MAX")? {
+            config.max_retries = value as u32;
+        }
+        if let Some(value) = env_u64("NEXUS_RETRY_BASE_MS")? {
+            config.base_delay = Duration::from_millis(value);
+        }
+        if let Some(value) = env_u64("NEXUS_RETRY_MAX_DELAY_MS")? {
+            config.max_delay = Duration::from_millis(value);
+        }
+        if let Some(value) = env_u64("NEXUS_RETRY_AFTER_MAX")? {
+            config.max_retry_after = Duration::from_secs(value);
+        }
+        Ok(config)
+    }
+
+    /// Full-jitter exponential backoff before retry number `retry` (1-based).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(1u32 << retry.saturating_sub(1).min(16));
+        let ceiling = exponential.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(rand::random::<u64>() % (ceiling + 1))
+    }
+}
+
+/// Whether a response status is worth another attempt.
+pub fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Parses `Retry-After` as delta seconds or an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(Duration::from_secs(secs as u64))
+}
+
+/// When an upstream's circuit breaker opens and for how long.
+#[derive(Debug, Clone)]
+pub struct BreakerConfig {
+    /// Consecutive failures (transport errors or 5xx) that open the breaker;
+    /// `0` disables it.
+    pub failure_threshold: u32,
+    /// How long an open breaker fails fast before letting one probe through.
+    pub open_for: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+        }
+    }
+}
+
+impl BreakerConfig {
+    /// Reads `NEXUS_BREAKER_THRESHOLD` and `NEXUS_BREAKER_OPEN_SECS`.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Some(value) = env_u64("NEXUS_BREAKER_THRESHOLD")? {
+            config.failure_threshold = value as u32;
+        }
+        if let Some(value) = env_u64("NEXUS_BREAKER_OPEN_SECS")? {
+            config.open_for = Duration::from_secs(value);
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// One probe request is in flight; everybody else keeps failing fast until
+    /// it reports back through its [`Attempt`].
+    HalfOpen,
+}
+
+/// Circuit breaker guarding one upstream manager.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: Mutex<State>,
+}
+
+/// Breaker state as reported by `/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerSnapshot {
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    /// Seconds until an open breaker lets a probe through.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Asks to send a request. `Err` carries how long the breaker stays open.
+    pub fn try_acquire(&self) -> Result<Attempt<'_>, Duration> {
+        let attempt = |probe| Attempt { breaker: self, probe, settled: false };
+        if self.config.failure_threshold == 0 {
+            return Ok(attempt(false));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(attempt(false)),
+            State::Open { until } => {
+                let now = Instant::now();
+                if now >= until {
+                    *state = State::HalfOpen;
+                    Ok(attempt(true))
+                } else {
+                    Err(until - now)
+                }
+            }
+            State::HalfOpen => Err(Duration::ZERO),
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let open = State::Open { until: Instant::now() + self.config.open_for };
+        *state = match *state {
+            State::Closed { failures } if failures + 1 >= self.config.failure_threshold => open,
+            State::Closed { failures } => State::Closed { failures: failures + 1 },
+            // A failed probe, or a late failure from before the breaker opened
+            State::HalfOpen | State::Open { .. } => open,
+        };
+    }
+
+    pub fn snapshot(&self) -> BreakerSnapshot {
+        match *self.state.lock().unwrap() {
+            State::Closed { failures } => BreakerSnapshot {
+                state: "closed",
+                consecutive_failures: failures,
+                retry_in_secs: None,
+            },
+            State::Open { until } => BreakerSnapshot {
+                state: "open",
+                consecutive_failures: self.config.failure_threshold,
+                retry_in_secs: Some(until.saturating_duration_since(Instant::now()).as_secs()),
+            },
+            State::HalfOpen => BreakerSnapshot {
+                state: "half_open",
+                consecutive_failures: self.config.failure_threshold,
+                retry_in_secs: None,
+            },
+        }
+    }
+}
+
+/// A request let through by [`CircuitBreaker::try_acquire`]; report its outcome
+/// with `succeeded` or `failed`. A half-open probe dropped without an outcome
+/// (its caller was cancelled or timed out) counts as failed, so the breaker
+/// opens again instead of blocking the upstream until restart.
+#[must_use]
+pub struct Attempt<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    settled: bool,
+}
+
+impl Attempt<'_> {
+    pub fn succeeded(mut self) {
+        self.settled = true;
+        self.breaker.record_success();
+    }
+
+    pub fn failed(mut self) {
+        self.settled = true;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.settled {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+fn env_u64(name: &str) -> Result<Option<u64>, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} must be a number, got '{}'", name, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(threshold: u32, open_for: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig { failure_threshold: threshold, open_for })
+    }
+
+    #[test]
+    fn test_breaker_opens_after_threshold() {
+        let breaker = breaker(3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_ok());
+
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_err());
+        assert_eq!(breaker.snapshot().state, "open");
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let breaker = breaker(2, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_half_open_lets_one_probe_through() {
+        let breaker = breaker(1, Duration::ZERO);
+        breaker.record_failure();
+
+        let probe = breaker.try_acquire().expect("Open period is over, probe goes through");
+        assert!(breaker.try_acquire().is_err(), "Only one probe at a time");
+        assert_eq!(breaker.snapshot().state, "half_open");
+
+        probe.succeeded();
+        assert_eq!(breaker.snapshot().state, "closed");
+    }
+
+    #[test]
+    fn test_abandoned_probe_reopens_breaker() {
+        let tripped = breaker(1, Duration::ZERO);
+        tripped.record_failure();
+
+        drop(tripped.try_acquire().unwrap());
+        assert_eq!(tripped.snapshot().state, "open", "A cancelled probe must not leave the breaker half-open");
+        assert!(tripped.try_acquire().is_ok(), "The next caller may probe again");
+
+        // Requests through a closed breaker are not counted when dropped
+        let closed = breaker(1, Duration::ZERO);
+        drop(closed.try_acquire().unwrap());
+        assert_eq!(closed.snapshot().state, "closed");
+    }
+
+    #[test]
+    fn test_backoff_stays_within_bounds() {
+        let config = RetryConfig {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(250),
+            ..Default::default()
+        };
+        for retry in 1..10 {
+            assert!(config.backoff(retry) <= Duration::from_millis(250));
+        }
+        assert!(config.backoff(1) <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_retry_after_parsing() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+}
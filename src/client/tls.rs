@@ -0,0 +1,247 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::Client;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// TLS settings for the connection to one Wazuh manager.
+///
+/// Certificates are checked against the system roots unless `ca_file` is set.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    /// Skip certificate chain and hostname verification. Only for labs with
+    /// self-signed certificates; a warning is logged whenever such a client is
+    /// built. `spki_pins` are still enforced.
+    pub insecure: bool,
+    /// PEM bundle of CA certificates trusted instead of the system roots.
+    pub ca_file: Option<PathBuf>,
+    /// PEM client certificate chain and key for mutual TLS.
+    pub client_cert_file: Option<PathBuf>,
+    pub client_key_file: Option<PathBuf>,
+    /// Base64 SHA-256 digests of the accepted server SubjectPublicKeyInfo.
+    /// When set, the server key must match one of them.
+    pub spki_pins: Vec<String>,
+}
+
+impl TlsConfig {
+    /// Reads `{prefix}_TLS_INSECURE`, `_TLS_CA_FILE`, `_TLS_CLIENT_CERT`,
+    /// `_TLS_CLIENT_KEY` and `_TLS_SPKI_PINS` (comma separated), e.g.
+    /// `NEXUS_UPSTREAM_PROD_TLS_CA_FILE=/etc/nexus/wazuh-ca.pem`.
+    pub fn from_env(prefix: &str) -> Result<Self, String> {
+        let var = |suffix: &str| {
+            let name = format!("{}_TLS_{}", prefix, suffix);
+            env::var(&name).ok().filter(|v| !v.is_empty()).map(|v| (name, v))
+        };
+
+        let mut config = Self::default();
+        if let Some((name, value)) = var("INSECURE") {
+            config.insecure = parse_bool(&value)
+                .ok_or_else(|| format!("{} must be true or false, got '{}'", name, value))?;
+        }
+        config.ca_file = var("CA_FILE").map(|(_, v)| PathBuf::from(v));
+        config.client_cert_file = var("CLIENT_CERT").map(|(_, v)| PathBuf::from(v));
+        config.client_key_file = var("CLIENT_KEY").map(|(_, v)| PathBuf::from(v));
+        config.spki_pins = var("SPKI_PINS")
+            .map(|(_, v)| v.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+            .unwrap_or_default();
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.client_cert_file.is_some() != self.client_key_file.is_some() {
+            return Err("A TLS client certificate and key must be configured together".to_string());
+        }
+        if self.insecure && self.ca_file.is_some() {
+            return Err("TLS_INSECURE cannot be combined with TLS_CA_FILE".to_string());
+        }
+        for pin in &self.spki_pins {
+            decode_pin(pin)?;
+        }
+        Ok(())
+    }
+
+    /// Builds an HTTP client that applies these settings; `name` is only used in
+    /// logs and errors.
+    pub fn build_client(&self, name: &str) -> Result<Client, String> {
+        self.validate().map_err(|e| format!("Upstream '{}': {}", name, e))?;
+
+        let mut verifier: Arc<dyn ServerCertVerifier> = if self.insecure {
+            println!("WARNING: TLS certificate verification is disabled for upstream '{}'", name);
+            Arc::new(NoVerification)
+        } else {
+            let roots = match &self.ca_file {
+                Some(path) => load_ca_file(path)?,
+                None => load_system_roots()?,
+            };
+            Arc::new(WebPkiVerifier::new(roots, None))
+        };
+
+        if !self.spki_pins.is_empty() {
+            let pins = self.spki_pins.iter().map(|p| decode_pin(p)).collect::<Result<_, _>>()?;
+            verifier = Arc::new(PinnedVerifier { inner: verifier, pins });
+        }
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier);
+
+        let tls = match (&self.client_cert_file, &self.client_key_file) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+                .map_err(|e| format!("Invalid TLS client certificate for upstream '{}': {}", name, e))?,
+            _ => builder.with_no_client_auth(),
+        };
+
+        Client::builder()
+            .use_preconfigured_tls(tls)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client for upstream '{}': {}", name, e))
+    }
+}
+
+/// Accepts any certificate chain; signatures on the handshake are still checked.
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Runs `inner` and then requires the server key to match one of `pins`.
+struct PinnedVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+
+        let digest = spki_sha256(&end_entity.0)
+            .ok_or_else(|| rustls::Error::General("Server certificate could not be parsed".to_string()))?;
+        if self.pins.contains(&digest) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::General("Server key does not match any pinned SPKI hash".to_string()))
+        }
+    }
+}
+
+/// SHA-256 of the SubjectPublicKeyInfo of a DER certificate.
+fn spki_sha256(der: &[u8]) -> Option<Vec<u8>> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    Some(Sha256::digest(cert.public_key().raw).to_vec())
+}
+
+/// Accepts `base64` or `sha256/base64` (the HPKP notation).
+fn decode_pin(pin: &str) -> Result<Vec<u8>, String> {
+    let encoded = pin.strip_prefix("sha256/").unwrap_or(pin);
+    match STANDARD.decode(encoded) {
+        Ok(digest) if digest.len() == 32 => Ok(digest),
+        _ => Err(format!("'{}' is not a base64 SHA-256 SPKI pin", pin)),
+    }
+}
+
+fn load_system_roots() -> Result<RootCertStore, String> {
+    let certs = rustls_native_certs::load_native_certs()
+        .map_err(|e| format!("Failed to load system CA certificates: {}", e))?;
+    let mut roots = RootCertStore::empty();
+    let ders: Vec<Vec<u8>> = certs.into_iter().map(|c| c.0).collect();
+    roots.add_parsable_certificates(&ders);
+    Ok(roots)
+}
+
+fn load_ca_file(path: &Path) -> Result<RootCertStore, String> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(&cert)
+            .map_err(|e| format!("Invalid CA certificate in {}: {}", path.display(), e))?;
+    }
+    Ok(roots)
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>, String> {
+    let pem = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path.display()));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &Path) -> Result<PrivateKey, String> {
+    let pem = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let items = rustls_pemfile::read_all(&mut pem.as_slice())
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    items.into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("No private key found in {}", path.display()))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_pin_formats() {
+        let pin = "gGdD/0Em+JhC8i8Yi//+pdx2Y9up3IMAl7KOJiMFo84=";
+        assert_eq!(decode_pin(pin).unwrap().len(), 32);
+        assert_eq!(decode_pin(&format!("sha256/{}", pin)).unwrap(), decode_pin(pin).unwrap());
+        assert!(decode_pin("not base64!").is_err());
+        assert!(decode_pin("YWJj").is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_inconsistent_settings() {
+        let cert_only = TlsConfig { client_cert_file: Some("client.pem".into()), ..Default::default() };
+        assert!(cert_only.validate().is_err());
+
+        let insecure_with_ca = TlsConfig { insecure: true, ca_file: Some("ca.pem".into()), ..Default::default() };
+        assert!(insecure_with_ca.validate().is_err());
+
+        let insecure_with_pin = TlsConfig {
+            insecure: true,
+            spki_pins: vec!["gGdD/0Em+JhC8i8Yi//+pdx2Y9up3IMAl7KOJiMFo84=".to_string()],
+            ..Default::default()
+        };
+        assert!(insecure_with_pin.validate().is_ok());
+    }
+}
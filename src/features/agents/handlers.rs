@@ -1,46 +1,248 @@
+use axum::extract::State;
+use axum::response::IntoResponse;
 use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use crate::shared::audit::AuditRecord;
+use crate::shared::common::{ProxyResult, WazuhRequest, collect_wazuh_items, handle_wazuh_request, send_wazuh_write};
+use crate::shared::error::NexusError;
+use crate::shared::path_params::WazuhCall;
+use crate::shared::query::is_allowed;
+use crate::shared::state::AppState;
+use crate::shared::tenants;
+use super::models::Enrollment;
 
 // Base agents endpoint
-pub async fn get_agents(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents", |url| url).await
+pub async fn get_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents", |url| url).await
 }
 
 // Agent configuration and stats
-pub async fn get_agent_config_by_id(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/{agent_id}/config/{component}/{configuration}", |url| url).await
+pub async fn get_agent_config_by_id(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/{agent_id}/config/{component}/{configuration}", |url| url).await
 }
 
-pub async fn get_agent_group_sync_status(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/{agent_id}/group/is_sync", |url| url).await
+pub async fn get_agent_group_sync_status(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/{agent_id}/group/is_sync", |url| url).await
 }
 
-pub async fn get_daemon_stats(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/{agent_id}/daemons/stats", |url| url).await
+pub async fn get_daemon_stats(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/{agent_id}/daemons/stats", |url| url).await
 }
 
-pub async fn get_agent_stats_component(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/{agent_id}/stats/{component}", |url| url).await
+pub async fn get_agent_stats_component(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/{agent_id}/stats/{component}", |url| url).await
 }
 
 // Group related endpoints
-pub async fn get_agents_without_group(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/no_group", |url| url).await
+pub async fn get_agents_without_group(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/no_group", |url| url).await
 }
 
 // Status and summary endpoints
-pub async fn get_outdated_agents(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/outdated", |url| url).await
+pub async fn get_outdated_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/outdated", |url| url).await
 }
 
-pub async fn get_distinct_agents_stats(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/stats/distinct", |url| url).await
+pub async fn get_distinct_agents_stats(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/stats/distinct", |url| url).await
 }
 
-pub async fn get_agents_os_summary(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/summary/os", |url| url).await
+pub async fn get_agents_os_summary(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/summary/os", |url| url).await
 }
 
-pub async fn get_agents_status_summary(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "agents/summary/status", |url| url).await
+pub async fn get_agents_status_summary(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "agents/summary/status", |url| url).await
+}
+
+// Write operations
+//
+// Bulk operations first resolve the caller's filters to agent ids through
+// `GET /agents` (where the tenant filter applies), then send Wazuh that exact
+// `agents_list`. A dry run stops after the lookup. Every call is audited.
+
+/// How one write route maps onto Wazuh.
+struct Operation {
+    method: Method,
+    /// Wazuh path template; with the method, it also names the options Wazuh accepts.
+    path: &'static str,
+    /// Filters Wazuh applies itself as well, so they are forwarded besides selecting agents.
+    shared_filters: &'static [&'static str],
+    /// Filters the caller must give.
+    required: &'static [&'static str],
+    /// Filters added when the caller left them out, mirroring Wazuh's defaults.
+    defaults: &'static [(&'static str, &'static str)],
+    /// Cached paths made stale by the operation.
+    invalidates: &'static [&'static str],
+}
+
+impl Operation {
+    const fn new(method: Method, path: &'static str) -> Self {
+        Self { method, path, shared_filters: &[], required: &[], defaults: &[], invalidates: &["agents"] }
+    }
+
+    fn action(&self) -> String {
+        format!("{} {}", self.method, self.path)
+    }
+}
+
+const RESTART: Operation = Operation::new(Method::PUT, "agents/restart");
+const RESTART_ONE: Operation = Operation::new(Method::PUT, "agents/{agent_id}/restart");
+const RECONNECT: Operation = Operation::new(Method::PUT, "agents/reconnect");
+const UPGRADE: Operation = Operation::new(Method::PUT, "agents/upgrade");
+const DELETE: Operation = Operation {
+    method: Method::DELETE,
+    path: "agents",
+    shared_filters: &["status", "older_than"],
+    required: &["status"],
+    defaults: &[("older_than", "7d")],
+    invalidates: &["agents", "groups"],
+};
+
+/// The manager itself shows up as agent `000` but is never a target.
+const MANAGER_ID: &str = "000";
+
+pub async fn restart_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    run_audited(&state, payload, &RESTART).await
+}
+
+pub async fn restart_agent(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    run_audited(&state, payload, &RESTART_ONE).await
+}
+
+pub async fn reconnect_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    run_audited(&state, payload, &RECONNECT).await
+}
+
+pub async fn upgrade_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    run_audited(&state, payload, &UPGRADE).await
+}
+
+pub async fn delete_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    run_audited(&state, payload, &DELETE).await
+}
+
+/// Enrolls a new agent from `body` (`name`, optional `ip`). New agents land in
+/// the default group, so keys confined to a tenant cannot enroll.
+pub async fn enroll_agent(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    let result = enroll(&state, &payload).await;
+    let enrolled = result.as_ref().ok()
+        .and_then(|data| data["data"]["id"].as_str())
+        .map(String::from)
+        .into_iter()
+        .collect();
+    state.audit.record(AuditRecord::new(&payload, "POST agents", enrolled, result.as_ref().map(|_| ())));
+    result.map(|data| Json(data).into_response())
+}
+
+async fn enroll(state: &AppState, request: &WazuhRequest) -> Result<Value, NexusError> {
+    if let Some(tenant) = &request.tenant {
+        return Err(NexusError::Forbidden(format!("Tenant '{}' cannot enroll agents", tenant.name)));
+    }
+    let enrollment: Enrollment = serde_json::from_value(request.body.clone().unwrap_or(Value::Null))
+        .map_err(|e| NexusError::BadParameter(format!("Invalid enrollment body: {}", e)))?;
+    let body = json!(enrollment);
+
+    if request.dry_run {
+        return Ok(summary(vec![body], "Dry run: no agent was enrolled", true));
+    }
+
+    let data = send_wazuh_write(state, request, Method::POST, "agents", &request.query, Some(&body)).await?;
+    state.client.invalidate_path_prefix("agents");
+    Ok(data)
+}
+
+async fn run_audited(state: &AppState, mut request: WazuhRequest, operation: &Operation) -> ProxyResult {
+    let mut targets = Vec::new();
+    let result = run(state, &mut request, operation, &mut targets).await;
+    state.audit.record(AuditRecord::new(&request, &operation.action(), targets, result.as_ref().map(|_| ())));
+    result.map(|data| Json(data).into_response())
+}
+
+/// Resolves the agents `request` selects into `targets` and, unless it is a
+/// dry run, applies `operation` to exactly those.
+async fn run(state: &AppState, request: &mut WazuhRequest, operation: &Operation, targets: &mut Vec<String>) -> Result<Value, NexusError> {
+    // Rejects an `{agent_id}` outside the caller's tenant before anything else
+    tenants::enforce(state, request, operation.path).await?;
+
+    let (mut selector, mut options) = split_query(&request.query, operation)?;
+    if let Some(agent_id) = request.params.get("agent_id") {
+        selector.insert("agents_list".to_string(), Value::String(agent_id.clone()));
+    }
+
+    let agents = affected_agents(state, request, selector).await?;
+    *targets = agents.iter().filter_map(|agent| agent["id"].as_str().map(String::from)).collect();
+
+    if request.dry_run {
+        return Ok(summary(agents, "Dry run: no changes were made", true));
+    }
+    if targets.is_empty() {
+        // An empty `agents_list` would mean every agent to Wazuh
+        return Ok(summary(Vec::new(), "No agents matched the filters", false));
+    }
+
+    if !operation.path.contains("{agent_id}") {
+        options.insert("agents_list".to_string(), Value::String(targets.join(",")));
+    }
+    let data = send_wazuh_write(state, request, operation.method.clone(), operation.path, &options, None).await?;
+    for path in operation.invalidates {
+        state.client.invalidate_path_prefix(path);
+    }
+    Ok(data)
+}
+
+type Query = BTreeMap<String, Value>;
+
+/// Splits the caller's query into the filters selecting agents (checked
+/// against `GET /agents`) and the options of the write call itself.
+fn split_query(query: &Query, operation: &Operation) -> Result<(Query, Query), NexusError> {
+    let action = operation.action();
+    let mut selector = BTreeMap::new();
+    let mut options = BTreeMap::new();
+
+    let defaults = operation.defaults.iter()
+        .filter(|(name, _)| !query.contains_key(*name))
+        .map(|(name, value)| (name.to_string(), Value::String(value.to_string())));
+    for (name, value) in query.clone().into_iter().chain(defaults) {
+        let is_option = name != "agents_list" && is_allowed(&action, &name);
+        if !is_option || operation.shared_filters.contains(&name.as_str()) {
+            selector.insert(name.clone(), value.clone());
+        }
+        if is_option {
+            options.insert(name, value);
+        }
+    }
+
+    if let Some(missing) = operation.required.iter().find(|name| !selector.contains_key(**name)) {
+        return Err(NexusError::BadParameter(format!("{} needs the '{}' filter", action, missing)));
+    }
+    Ok((selector, options))
+}
+
+/// The agents matched by `selector`, as the caller is allowed to see them.
+async fn affected_agents(state: &AppState, request: &WazuhRequest, selector: Query) -> Result<Vec<Value>, NexusError> {
+    let mut lookup = request.clone();
+    lookup.query = selector;
+    lookup.query.insert("select".to_string(), Value::String("id,name,status,group".to_string()));
+
+    // Never act on a cached listing
+    let agents = collect_wazuh_items(state, lookup, "agents", false).await?;
+    Ok(agents.into_iter().filter(|agent| agent["id"] != MANAGER_ID).collect())
+}
+
+/// A Wazuh-shaped answer for calls nexus settles without sending them on.
+fn summary(items: Vec<Value>, message: &str, dry_run: bool) -> Value {
+    json!({
+        "data": {
+            "affected_items": items,
+            "total_affected_items": items.len(),
+            "total_failed_items": 0,
+            "failed_items": []
+        },
+        "message": message,
+        "dry_run": dry_run,
+        "error": 0
+    })
 }
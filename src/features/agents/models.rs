@@ -1 +1,10 @@
-// Agent-related models will be moved here
+use serde::{Deserialize, Serialize};
+
+/// Body of `POST /agents/enroll`, forwarded to Wazuh's `POST /agents`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Enrollment {
+    pub name: String,
+    /// Address the agent connects from; Wazuh accepts any address when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+}
@@ -1,10 +1,12 @@
 use axum::{
     Router,
-    routing::post,
+    routing::{delete, post, put},
 };
+use crate::shared::access::guard_writes;
+use crate::shared::state::AppState;
 use super::handlers::*;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         // Base agents endpoints
         .route("/agents", post(get_agents))
@@ -23,4 +25,18 @@ pub fn routes() -> Router {
         .route("/agents/stats/distinct", post(get_distinct_agents_stats))
         .route("/agents/summary/os", post(get_agents_os_summary))
         .route("/agents/summary/status", post(get_agents_status_summary))
+
+        // Write operations, which need write access to the module
+        .merge(guard_writes("agents", write_routes()))
+}
+
+fn write_routes() -> Router<AppState> {
+    Router::new()
+        .route("/agents", delete(delete_agents))
+        // `POST /agents` is already the listing, like every read route here
+        .route("/agents/enroll", post(enroll_agent))
+        .route("/agents/restart", put(restart_agents))
+        .route("/agents/reconnect", put(reconnect_agents))
+        .route("/agents/upgrade", put(upgrade_agents))
+        .route("/agents/:agent_id/restart", put(restart_agent))
 }
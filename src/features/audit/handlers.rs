@@ -0,0 +1,14 @@
+use axum::{extract::{Query, State}, Json};
+use crate::shared::admin::AdminAccess;
+use crate::shared::state::AppState;
+use super::models::{AuditListing, AuditQuery};
+
+pub async fn list_audit_records(
+    _admin: AdminAccess,
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Json<AuditListing> {
+    Json(AuditListing {
+        records: state.audit.recent(query.limit),
+    })
+}
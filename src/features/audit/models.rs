@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use crate::shared::audit::AuditRecord;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// Most recent records to return; 100 when omitted.
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditListing {
+    /// Newest first.
+    pub records: Vec<AuditRecord>,
+}
@@ -0,0 +1,12 @@
+use axum::{
+    Router,
+    routing::get,
+};
+use crate::shared::state::AppState;
+use super::handlers::*;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        // Admin endpoints, guarded by NEXUS_ADMIN_TOKEN
+        .route("/admin/audit", get(list_audit_records))
+}
@@ -1,59 +1,142 @@
 use axum::Json;
-use axum::http::StatusCode;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use crate::client::WazuhClient;
-use super::models::{AuthRequest, AuthResponse};
-
-pub async fn authenticate(Json(payload): Json<AuthRequest>) -> (StatusCode, Json<AuthResponse>) {
-    let client = WazuhClient::new();
-    let auth_url = format!("{}/security/user/authenticate", payload.endpoint);
-    
-    match client.get_with_auth(&auth_url, &payload.username, &payload.password).await {
-        Ok(response) => {
-            if response.status().as_u16() == 401 {
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    Json(AuthResponse {
-                        token: None,
-                        error: Some("Invalid credentials".to_string()),
-                    })
-                );
-            }
-
-            match WazuhClient::handle_json_response(response).await {
-                Ok(data) => {
-                    if let Some(token) = data["data"]["token"].as_str() {
-                        (
-                            StatusCode::OK,
-                            Json(AuthResponse {
-                                token: Some(token.to_string()),
-                                error: None,
-                            })
-                        )
-                    } else {
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(AuthResponse {
-                                token: None,
-                                error: Some("Token not found in response".to_string()),
-                            })
-                        )
-                    }
-                },
-                Err(e) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(AuthResponse {
-                        token: None,
-                        error: Some(e),
-                    })
-                ),
-            }
-        },
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(AuthResponse {
-                token: None,
-                error: Some(format!("Request failed: {}", e)),
-            })
-        ),
+use chrono::Utc;
+use crate::shared::error::NexusError;
+use crate::shared::path_params::WazuhCall;
+use crate::shared::rate_limit::ClientIp;
+use crate::shared::sessions::{token_from, wazuh_logout, CurrentSession, Session};
+use crate::shared::state::AppState;
+use super::jwt;
+use super::models::{AuthRequest, AuthResponse, LogoutResponse, RunAsRequest, SessionLoginRequest, SessionResponse, TokenInfo};
+
+/// Forwards a password login to Wazuh, subject to the per-IP and per-user
+/// quotas and the lockout after repeated 401s.
+pub async fn authenticate(State(state): State<AppState>, ClientIp(ip): ClientIp, Json(payload): Json<AuthRequest>) -> Response {
+    if let Err(e) = state.limits.logins.admit(ip, &payload.username) {
+        println!("Login for {} refused: {}", payload.username, e);
+        return e.into_response();
+    }
+
+    let result = forward_login(&state, &payload).await;
+    state.limits.logins.record(ip, &payload.username, &result);
+
+    let (status, token, error) = match result {
+        Ok(token) => (StatusCode::OK, Some(token), None),
+        Err(NexusError::Upstream { status: 401, .. }) => (StatusCode::UNAUTHORIZED, None, Some("Invalid credentials".to_string())),
+        Err(NexusError::Parse(message)) => (StatusCode::BAD_GATEWAY, None, Some(message)),
+        Err(e) => (e.status_code(), None, Some(e.to_string())),
+    };
+    (status, Json(AuthResponse { token, error })).into_response()
+}
+
+/// Password login against the selected manager; returns the Wazuh JWT.
+async fn forward_login(state: &AppState, payload: &AuthRequest) -> Result<String, NexusError> {
+    let upstream = state.config.upstreams.resolve(payload.upstream.as_deref(), payload.endpoint.as_deref())?;
+    let auth_url = format!("{}/security/user/authenticate", upstream.url);
+
+    let response = state.client.get_with_auth(&auth_url, &payload.username, &payload.password).await?;
+    // Keeps Wazuh's error body, so its `detail` reaches the caller
+    token_from(WazuhClient::json_result(response).await?)
+}
+
+/// Revokes a Wazuh JWT (`DELETE /security/user/authenticate`) and drops every
+/// cache entry fetched with it. Sessions are ended with `DELETE /session`.
+pub async fn logout(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> Result<Json<LogoutResponse>, NexusError> {
+    if payload.session.is_some() {
+        return Err(NexusError::BadParameter("Use DELETE /session to end a nexus session".to_string()));
+    }
+
+    let upstream = state.config.upstreams.resolve(payload.upstream.as_deref(), payload.endpoint.as_deref())?;
+    // Purge first: the cached data must go even if Wazuh already forgot the token
+    let purged = state.client.invalidate_token(&payload.token);
+    let data = wazuh_logout(&state.client, &upstream.url, &payload.token).await?;
+
+    println!("Logged out of '{}', purged {} cache entries", upstream.name, purged);
+    Ok(Json(LogoutResponse {
+        message: data["message"].as_str().map(String::from),
+        purged_cache_entries: purged,
+    }))
+}
+
+/// Logs in on behalf of an authorization context
+/// (`POST /security/user/authenticate/run_as`).
+pub async fn run_as(State(state): State<AppState>, ClientIp(ip): ClientIp, Json(payload): Json<RunAsRequest>) -> Result<Json<AuthResponse>, NexusError> {
+    let upstream = state.config.upstreams.resolve(payload.upstream.as_deref(), payload.endpoint.as_deref())?;
+    let auth_url = format!("{}/security/user/authenticate/run_as", upstream.url);
+    state.limits.logins.admit(ip, &payload.username)?;
+
+    let response = state.client
+        .post_with_auth(&auth_url, &payload.username, &payload.password, &payload.auth_context)
+        .await?;
+    let result = WazuhClient::json_result(response).await;
+    state.limits.logins.record(ip, &payload.username, &result);
+    let token = result
+        .map_err(|e| match e {
+            NexusError::Upstream { status: 401, .. } => NexusError::Unauthorized("Invalid credentials".to_string()),
+            other => other,
+        })
+        .and_then(token_from)?;
+
+    Ok(Json(AuthResponse {
+        token: Some(token),
+        error: None,
+    }))
+}
+
+/// Decodes the caller's Wazuh JWT (or the one held by their session): user,
+/// expiry and RBAC roles. The signature is not verified.
+pub async fn introspect_token(WazuhCall(payload): WazuhCall) -> Result<Json<TokenInfo>, NexusError> {
+    jwt::introspect(&payload.token, Utc::now()).map(Json)
+}
+
+/// Logs in to Wazuh and keeps the JWT server-side; the caller only gets a nexus
+/// session id (also set as a cookie).
+pub async fn create_session(State(state): State<AppState>, ClientIp(ip): ClientIp, Json(payload): Json<SessionLoginRequest>) -> Result<Response, NexusError> {
+    if !state.sessions.config().enabled {
+        return Err(NexusError::Unauthorized("Sessions are disabled (NEXUS_SESSIONS)".to_string()));
+    }
+
+    let upstream = state.config.upstreams.resolve(payload.upstream.as_deref(), payload.endpoint.as_deref())?;
+    state.limits.logins.admit(ip, &payload.username)?;
+    let result = state.sessions
+        .login(&state.client, upstream, &payload.username, &payload.password)
+        .await;
+    state.limits.logins.record(ip, &payload.username, &result);
+    let session = result
+        .map_err(|e| match e {
+            NexusError::Upstream { status: 401, .. } => NexusError::Unauthorized("Invalid credentials".to_string()),
+            other => other,
+        })?;
+
+    let cookie = state.sessions.cookie(Some(&session));
+    let body = session_response(&state, &session, true);
+    Ok(([(header::SET_COOKIE, cookie)], Json(body)).into_response())
+}
+
+pub async fn get_session(State(state): State<AppState>, CurrentSession(session): CurrentSession) -> Json<SessionResponse> {
+    Json(session_response(&state, &session, false))
+}
+
+/// Ends the session and revokes its JWT on Wazuh. The session is gone even if
+/// Wazuh cannot be reached.
+pub async fn delete_session(State(state): State<AppState>, CurrentSession(session): CurrentSession) -> Response {
+    state.sessions.remove(&session.id);
+    match session.logout(&state.client).await {
+        Ok(purged) => println!("Session of {} ended, purged {} cache entries", session.username, purged),
+        Err(e) => println!("Session of {} ended, but Wazuh logout failed: {}", session.username, e),
+    }
+    let cookie = state.sessions.cookie(None);
+    (StatusCode::NO_CONTENT, [(header::SET_COOKIE, cookie)]).into_response()
+}
+
+fn session_response(state: &AppState, session: &Session, include_id: bool) -> SessionResponse {
+    SessionResponse {
+        session: include_id.then(|| session.id.clone()),
+        upstream: session.upstream.clone(),
+        username: session.username.clone(),
+        idle_timeout_secs: state.sessions.config().idle_timeout.as_secs(),
     }
 }
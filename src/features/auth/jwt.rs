@@ -0,0 +1,90 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use crate::shared::error::NexusError;
+use super::models::TokenInfo;
+
+/// The claims Wazuh puts in its JWTs.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    iss: Option<String>,
+    nbf: Option<i64>,
+    exp: Option<i64>,
+    #[serde(default)]
+    run_as: bool,
+    #[serde(default)]
+    rbac_roles: Vec<Value>,
+    rbac_mode: Option<String>,
+}
+
+/// Decodes a Wazuh JWT as of `now`.
+///
+/// The signature is not checked: nexus does not hold the manager's signing key,
+/// so this describes what the token claims, not whether Wazuh still accepts it.
+pub fn introspect(token: &str, now: DateTime<Utc>) -> Result<TokenInfo, NexusError> {
+    let not_a_jwt = |reason: &str| NexusError::BadParameter(format!("Token is not a JWT: {}", reason));
+
+    let mut segments = token.split('.');
+    let (Some(_header), Some(payload), Some(_signature), None) = (segments.next(), segments.next(), segments.next(), segments.next()) else {
+        return Err(not_a_jwt("expected three segments"));
+    };
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| not_a_jwt("payload is not base64url"))?;
+    let claims: Claims = serde_json::from_slice(&payload).map_err(|e| not_a_jwt(&e.to_string()))?;
+
+    let timestamp = |secs: Option<i64>| secs.and_then(|s| Utc.timestamp_opt(s, 0).single());
+    let expires_at = timestamp(claims.exp);
+
+    Ok(TokenInfo {
+        username: claims.sub,
+        issuer: claims.iss,
+        not_before: timestamp(claims.nbf),
+        expires_at,
+        expires_in_secs: expires_at.map(|exp| (exp - now).num_seconds()),
+        expired: expires_at.is_some_and(|exp| exp <= now),
+        run_as: claims.run_as,
+        rbac_roles: claims.rbac_roles,
+        rbac_mode: claims.rbac_mode,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn jwt(claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"ES512","typ":"JWT"}"#);
+        format!("{}.{}.signature", header, URL_SAFE_NO_PAD.encode(claims.to_string()))
+    }
+
+    #[test]
+    fn test_introspect_reads_wazuh_claims() {
+        let now = Utc.timestamp_opt(1_700_000_100, 0).unwrap();
+        let token = jwt(json!({
+            "iss": "wazuh", "aud": "Wazuh API REST", "nbf": 1_700_000_000, "exp": 1_700_000_900,
+            "sub": "wazuh", "run_as": false, "rbac_roles": [1], "rbac_mode": "white"
+        }));
+
+        let info = introspect(&token, now).unwrap();
+        assert_eq!(info.username.as_deref(), Some("wazuh"));
+        assert_eq!(info.expires_in_secs, Some(800));
+        assert!(!info.expired);
+        assert_eq!(info.rbac_roles, vec![json!(1)]);
+        assert_eq!(info.rbac_mode.as_deref(), Some("white"));
+
+        let later = Utc.timestamp_opt(1_700_001_000, 0).unwrap();
+        assert!(introspect(&token, later).unwrap().expired);
+    }
+
+    #[test]
+    fn test_introspect_rejects_garbage() {
+        let now = Utc::now();
+        assert!(introspect("mock-service-token", now).is_err());
+        assert!(introspect("a.b.c.d", now).is_err());
+        assert!(introspect("a.!!!.c", now).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+mod jwt;
 mod models;
 mod routes;
 mod handlers;
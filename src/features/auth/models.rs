@@ -1,8 +1,15 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
 pub struct AuthRequest {
-    pub endpoint: String,
+    /// Name of the configured upstream manager; the default one when omitted.
+    #[serde(default)]
+    pub upstream: Option<String>,
+    /// Base URL of the manager, accepted only if it is a configured upstream.
+    #[serde(default)]
+    pub endpoint: Option<String>,
     pub username: String,
     pub password: String,
 }
@@ -12,3 +19,61 @@ pub struct AuthResponse {
     pub token: Option<String>,
     pub error: Option<String>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct SessionLoginRequest {
+    pub username: String,
+    pub password: String,
+    /// Upstream to log in to; the default one when omitted.
+    #[serde(default)]
+    pub upstream: Option<String>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    /// Opaque id, also set as the `nexus_session` cookie. Send it back as the
+    /// cookie or as `Authorization: Bearer <session>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    pub upstream: String,
+    pub username: String,
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunAsRequest {
+    #[serde(default)]
+    pub upstream: Option<String>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub username: String,
+    pub password: String,
+    /// Authorization context forwarded as the body of Wazuh's `run_as` login;
+    /// the user needs `allow_run_as` on the manager.
+    pub auth_context: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogoutResponse {
+    pub message: Option<String>,
+    /// Cache entries fetched with the revoked token that were dropped.
+    pub purged_cache_entries: usize,
+}
+
+/// What a Wazuh JWT claims about itself; see `jwt::introspect`.
+#[derive(Debug, Serialize)]
+pub struct TokenInfo {
+    pub username: Option<String>,
+    pub issuer: Option<String>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Negative once the token has expired.
+    pub expires_in_secs: Option<i64>,
+    pub expired: bool,
+    pub run_as: bool,
+    /// Wazuh RBAC role ids granted to the token.
+    pub rbac_roles: Vec<Value>,
+    pub rbac_mode: Option<String>,
+}
@@ -2,9 +2,13 @@ use axum::{
     Router,
     routing::post,
 };
-use super::handlers::authenticate;
+use crate::shared::state::AppState;
+use super::handlers::{authenticate, create_session, delete_session, get_session, introspect_token, logout, run_as};
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
-        .route("/auth", post(authenticate))
+        .route("/auth", post(authenticate).delete(logout))
+        .route("/auth/run_as", post(run_as))
+        .route("/auth/introspect", post(introspect_token))
+        .route("/session", post(create_session).get(get_session).delete(delete_session))
 }
@@ -0,0 +1,48 @@
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Value};
+use crate::client::CacheStats;
+use crate::shared::admin::AdminAccess;
+use crate::shared::state::AppState;
+use super::models::{CacheListing, InvalidateRequest, InvalidateResponse};
+
+pub async fn get_cache_stats(State(state): State<AppState>) -> Json<CacheStats> {
+    Json(state.client.cache_stats())
+}
+
+// Admin endpoints
+pub async fn list_cache_entries(_admin: AdminAccess, State(state): State<AppState>) -> Json<CacheListing> {
+    Json(CacheListing {
+        stats: state.client.cache_stats(),
+        entries: state.client.cache_entries(),
+    })
+}
+
+pub async fn invalidate_cache(
+    _admin: AdminAccess,
+    State(state): State<AppState>,
+    Json(payload): Json<InvalidateRequest>,
+) -> Result<Json<InvalidateResponse>, (StatusCode, Json<Value>)> {
+    if payload.prefix.is_none() && payload.path.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Either 'prefix' or 'path' must be provided" })),
+        ));
+    }
+
+    let mut removed = 0;
+    if let Some(prefix) = payload.prefix.as_deref() {
+        removed += state.client.invalidate_url_prefix(prefix);
+    }
+    if let Some(path) = payload.path.as_deref() {
+        removed += state.client.invalidate_path_prefix(path);
+    }
+
+    println!("Invalidated {} cache entries ({:?})", removed, payload);
+    Ok(Json(InvalidateResponse { removed }))
+}
+
+pub async fn flush_cache(_admin: AdminAccess, State(state): State<AppState>) -> Json<InvalidateResponse> {
+    let removed = state.client.flush_cache();
+    println!("Flushed {} cache entries", removed);
+    Json(InvalidateResponse { removed })
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use crate::client::{CacheEntrySummary, CacheStats};
+
+#[derive(Debug, Serialize)]
+pub struct CacheListing {
+    pub stats: CacheStats,
+    pub entries: Vec<CacheEntrySummary>,
+}
+
+/// Selects the entries to drop. `prefix` matches the full upstream URL
+/// (`https://wazuh:55000/agents`), `path` matches the Wazuh path on any manager
+/// (`syscollector/001`, `mitre`). Both may be given.
+#[derive(Debug, Deserialize)]
+pub struct InvalidateRequest {
+    pub prefix: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvalidateResponse {
+    pub removed: usize,
+}
@@ -0,0 +1,15 @@
+use axum::{
+    Router,
+    routing::{get, post},
+};
+use crate::shared::state::AppState;
+use super::handlers::*;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/cache/stats", get(get_cache_stats))
+
+        // Admin endpoints, guarded by NEXUS_ADMIN_TOKEN
+        .route("/admin/cache", get(list_cache_entries).delete(flush_cache))
+        .route("/admin/cache/invalidate", post(invalidate_cache))
+}
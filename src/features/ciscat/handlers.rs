@@ -1,6 +1,8 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
-pub async fn get_results(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "ciscat/{agent_id}/results", |url| url).await
+pub async fn get_results(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "ciscat/{agent_id}/results", |url| url).await
 }
@@ -2,9 +2,10 @@ use axum::{
     routing::post,
     Router,
 };
+use crate::shared::state::AppState;
 use super::handlers::*;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/ciscat/:agent_id/results", post(get_results))
 }
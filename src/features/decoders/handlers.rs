@@ -1,15 +1,17 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
 // Decoders information endpoints
-pub async fn get_decoders(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "decoders", |url| url).await
+pub async fn get_decoders(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "decoders", |url| url).await
 }
 
-pub async fn get_decoder_files(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "decoders/files", |url| url).await
+pub async fn get_decoder_files(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "decoders/files", |url| url).await
 }
 
-pub async fn get_decoder_parents(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "decoders/parents", |url| url).await
+pub async fn get_decoder_parents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "decoders/parents", |url| url).await
 }
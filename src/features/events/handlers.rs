@@ -0,0 +1,102 @@
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::{Extension, Json};
+use futures::stream::{self, Stream, StreamExt};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use crate::shared::access::Principal;
+use crate::shared::error::NexusError;
+use crate::shared::state::AppState;
+use crate::shared::tenants::Tenant;
+use crate::shared::watcher::{AgentEvent, EventFilter};
+use super::models::AgentEventListing;
+
+fn scope_of(principal: &Option<Extension<Arc<Principal>>>) -> Option<Arc<Tenant>> {
+    principal.as_ref().and_then(|Extension(p)| p.scope())
+}
+
+/// The events have no body to carry a Wazuh token, so callers without an API
+/// key identify themselves with a nexus session or `Authorization: Bearer
+/// <wazuh token>`. The token is checked against the default upstream.
+async fn check_caller(state: &AppState, principal: &Option<Extension<Arc<Principal>>>, headers: &HeaderMap) -> Result<(), NexusError> {
+    if principal.is_some() || state.sessions.from_headers(headers)?.is_some() {
+        return Ok(());
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| NexusError::Unauthorized("Send an API key, a nexus session or a Wazuh token".to_string()))?;
+
+    let upstream = state.config.upstreams.resolve(None, None)?;
+    match state.client.get_cached(&format!("{}/security/users/me", upstream.url), Some(token)).await {
+        Ok(_) => Ok(()),
+        Err(NexusError::Upstream { status: 401, .. }) => Err(NexusError::Unauthorized("Invalid Wazuh token".to_string())),
+        Err(e) => Err(e),
+    }
+}
+
+/// Recorded agent events, filtered by `since`/`until` (RFC 3339), `agent_id`,
+/// `kind` and `after_id`. Keys confined to a tenant or to groups only see their agents;
+/// anonymous callers get 401.
+pub async fn list_agent_events(
+    State(state): State<AppState>,
+    principal: Option<Extension<Arc<Principal>>>,
+    headers: HeaderMap,
+    Query(filter): Query<EventFilter>,
+) -> Result<Json<AgentEventListing>, NexusError> {
+    check_caller(&state, &principal, &headers).await?;
+    Ok(Json(AgentEventListing {
+        events: state.watcher.events(&filter, scope_of(&principal).as_deref()),
+        watching: state.config.watcher.interval.is_some(),
+    }))
+}
+
+/// Server-sent events, one `agent` event per [`AgentEvent`], with the event id
+/// as SSE id. A client reconnecting with `Last-Event-ID` (or `after_id`) first
+/// gets the stored events it missed.
+pub async fn stream_agent_events(
+    State(state): State<AppState>,
+    principal: Option<Extension<Arc<Principal>>>,
+    headers: HeaderMap,
+    Query(mut filter): Query<EventFilter>,
+) -> Result<Sse<impl Stream<Item = Result<Event, serde_json::Error>>>, NexusError> {
+    check_caller(&state, &principal, &headers).await?;
+    if let Some(last) = headers.get("last-event-id").and_then(|v| v.to_str().ok()).and_then(|v| v.parse().ok()) {
+        filter.after_id = Some(last);
+    }
+    let tenant = scope_of(&principal);
+
+    // Subscribe before reading the backlog so nothing falls in between
+    let live = state.watcher.subscribe();
+    let backlog = match filter.after_id {
+        Some(_) => state.watcher.events(&filter, tenant.as_deref()),
+        None => Vec::new(),
+    };
+    if let Some(last) = backlog.last() {
+        filter.after_id = Some(last.id);
+    }
+
+    let live = stream::unfold(live, |mut live| async move {
+        loop {
+            match live.recv().await {
+                Ok(event) => return Some((event, live)),
+                // Events dropped for a slow client; it can resume with Last-Event-ID
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .filter(move |event| {
+        let wanted = filter.matches(event) && event.visible_to(tenant.as_deref());
+        async move { wanted }
+    });
+
+    let events = stream::iter(backlog).chain(live).map(|event: AgentEvent| {
+        Event::default().id(event.id.to_string()).event("agent").json_data(&event)
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,10 @@
+use serde::Serialize;
+use crate::shared::watcher::AgentEvent;
+
+#[derive(Debug, Serialize)]
+pub struct AgentEventListing {
+    /// Oldest first.
+    pub events: Vec<AgentEvent>,
+    /// Whether the poller runs; without it the history stays empty.
+    pub watching: bool,
+}
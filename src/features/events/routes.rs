@@ -0,0 +1,13 @@
+use axum::{
+    Router,
+    routing::get,
+};
+use crate::shared::state::AppState;
+use super::handlers::*;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        // Agent status history recorded by the watcher
+        .route("/events/agents", get(list_agent_events))
+        .route("/events/agents/stream", get(stream_agent_events))
+}
@@ -1,19 +1,21 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
 // Groups information endpoints
-pub async fn get_groups(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "groups", |url| url).await
+pub async fn get_groups(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "groups", |url| url).await
 }
 
-pub async fn get_group_files(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "groups/{group_id}/files", |url| url).await
+pub async fn get_group_files(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "groups/{group_id}/files", |url| url).await
 }
 
-pub async fn get_group_agents(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "groups/{group_id}/agents", |url| url).await
+pub async fn get_group_agents(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "groups/{group_id}/agents", |url| url).await
 }
 
-pub async fn get_group_configuration(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "groups/{group_id}/configuration", |url| url).await
+pub async fn get_group_configuration(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "groups/{group_id}/configuration", |url| url).await
 }
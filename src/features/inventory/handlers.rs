@@ -0,0 +1,201 @@
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Json;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use crate::shared::common::{collect_wazuh_items, fetch_wazuh_json, ProxyResult, ResponseFormat, WazuhRequest};
+use crate::shared::error::NexusError;
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
+use super::models::{HardwareInfo, InventoryRecord, InventoryResponse, NetAddress, OsInfo};
+
+/// Agents described at the same time when `NEXUS_INVENTORY_CONCURRENCY` is
+/// unset. Each one takes three Wazuh calls.
+const DEFAULT_CONCURRENCY: usize = 8;
+
+const CSV_COLUMNS: &[&str] = &[
+    "id", "name", "status", "ip", "groups", "version", "last_keep_alive",
+    "os_name", "os_version", "os_platform", "architecture", "hostname", "release",
+    "cpu_name", "cpu_cores", "ram_total_kb", "board_serial", "addresses", "errors",
+];
+
+/// Every agent matched by `query` (the filters of `GET /agents`), joined with
+/// its OS, hardware and network addresses. `query.group` may also be a list.
+pub async fn get_inventory(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    let format = payload.format;
+    let records = collect_inventory(&state, payload).await?;
+
+    Ok(match format {
+        ResponseFormat::Json => Json(InventoryResponse { total: records.len(), items: records }).into_response(),
+        ResponseFormat::Ndjson => {
+            let lines: String = records
+                .iter()
+                .map(|record| serde_json::to_string(record).unwrap_or_default() + "\n")
+                .collect();
+            ([(header::CONTENT_TYPE, "application/x-ndjson")], lines).into_response()
+        }
+        ResponseFormat::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"inventory.csv\""),
+            ],
+            to_csv(&records),
+        )
+            .into_response(),
+    })
+}
+
+async fn collect_inventory(state: &AppState, mut request: WazuhRequest) -> Result<Vec<InventoryRecord>, NexusError> {
+    group_filter(&mut request.query)?;
+    let agents = collect_wazuh_items(state, request.clone(), "agents", true).await?;
+    println!("Building inventory for {} agents", agents.len());
+
+    // `buffered` keeps the order of the agent listing
+    let concurrency = state.config.inventory_concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+    Ok(stream::iter(agents)
+        .map(|agent| describe(state, &request, agent))
+        .buffered(concurrency)
+        .collect()
+        .await)
+}
+
+/// Turns a list of groups into a `q` clause, since Wazuh's `group` filter only
+/// takes one.
+fn group_filter(query: &mut BTreeMap<String, Value>) -> Result<(), NexusError> {
+    let Some(Value::Array(groups)) = query.get("group").cloned() else {
+        return Ok(());
+    };
+    query.remove("group");
+
+    let clauses = groups
+        .iter()
+        .map(|g| g.as_str().map(|g| format!("group={}", g)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| NexusError::BadParameter("Query parameter 'group' must be a string or a list of strings".to_string()))?;
+    if clauses.is_empty() {
+        return Ok(());
+    }
+
+    let filter = clauses.join(",");
+    let q = match query.get("q").and_then(Value::as_str) {
+        Some(own) if !own.trim().is_empty() => format!("({});({})", own, filter),
+        _ => filter,
+    };
+    query.insert("q".to_string(), Value::String(q));
+    Ok(())
+}
+
+/// Fetches the three syscollector sections of one agent concurrently. A failed
+/// section is noted in `errors` instead of failing the whole inventory.
+async fn describe(state: &AppState, request: &WazuhRequest, agent: Value) -> InventoryRecord {
+    let mut record = InventoryRecord::from_agent(&agent);
+    let (os, hardware, addresses) = futures::join!(
+        section(state, request, &record.id, "syscollector/{agent_id}/os"),
+        section(state, request, &record.id, "syscollector/{agent_id}/hardware"),
+        section(state, request, &record.id, "syscollector/{agent_id}/netaddr"),
+    );
+
+    match os {
+        Ok(items) => record.os = items.first().map(OsInfo::from_item),
+        Err(e) => record.errors.push(format!("os: {}", e)),
+    }
+    match hardware {
+        Ok(items) => record.hardware = items.first().map(HardwareInfo::from_item),
+        Err(e) => record.errors.push(format!("hardware: {}", e)),
+    }
+    match addresses {
+        Ok(items) => record.addresses = items.iter().map(NetAddress::from_item).collect(),
+        Err(e) => record.errors.push(format!("netaddr: {}", e)),
+    }
+    record
+}
+
+async fn section(state: &AppState, request: &WazuhRequest, agent_id: &str, template: &str) -> Result<Vec<Value>, NexusError> {
+    let mut call = request.clone();
+    call.params = HashMap::from([("agent_id".to_string(), agent_id.to_string())]);
+    call.query = BTreeMap::new();
+
+    let data = fetch_wazuh_json(state, call, template, |url| url).await?;
+    Ok(data["data"]["affected_items"].as_array().cloned().unwrap_or_default())
+}
+
+fn to_csv(records: &[InventoryRecord]) -> String {
+    let mut out = CSV_COLUMNS.join(",") + "\r\n";
+    for record in records {
+        let os = record.os.as_ref();
+        let hardware = record.hardware.as_ref();
+        let addresses: Vec<String> = record.addresses
+            .iter()
+            .filter_map(|a| a.address.as_ref().map(|address| format!("{}:{}", a.iface.as_deref().unwrap_or(""), address)))
+            .collect();
+
+        let row = [
+            Some(record.id.clone()),
+            record.name.clone(),
+            record.status.clone(),
+            record.ip.clone(),
+            Some(record.groups.join(";")),
+            record.version.clone(),
+            record.last_keep_alive.clone(),
+            os.and_then(|o| o.name.clone()),
+            os.and_then(|o| o.version.clone()),
+            os.and_then(|o| o.platform.clone()),
+            os.and_then(|o| o.architecture.clone()),
+            os.and_then(|o| o.hostname.clone()),
+            os.and_then(|o| o.release.clone()),
+            hardware.and_then(|h| h.cpu_name.clone()),
+            hardware.and_then(|h| h.cpu_cores).map(|n| n.to_string()),
+            hardware.and_then(|h| h.ram_total_kb).map(|n| n.to_string()),
+            hardware.and_then(|h| h.board_serial.clone()),
+            Some(addresses.join(";")),
+            Some(record.errors.join(";")),
+        ];
+        let cells: Vec<String> = row.iter().map(|cell| csv_cell(cell.as_deref().unwrap_or(""))).collect();
+        out.push_str(&cells.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Quotes a value when needed (RFC 4180). Values that a spreadsheet would run
+/// as a formula get a leading `'`, since agents choose their own names.
+fn csv_cell(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_csv_cell_quoting() {
+        assert_eq!(csv_cell("web-01"), "web-01");
+        assert_eq!(csv_cell("Intel(R) Xeon(R), 2.20GHz"), "\"Intel(R) Xeon(R), 2.20GHz\"");
+        assert_eq!(csv_cell("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_cell("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+    }
+
+    #[test]
+    fn test_group_list_becomes_q_clause() {
+        let mut query: BTreeMap<String, Value> = serde_json::from_value(json!({ "group": ["web", "db"], "q": "status=active" })).unwrap();
+        group_filter(&mut query).unwrap();
+        assert_eq!(query.get("group"), None);
+        assert_eq!(query["q"], "(status=active);(group=web,group=db)");
+
+        let mut query: BTreeMap<String, Value> = serde_json::from_value(json!({ "group": "web" })).unwrap();
+        group_filter(&mut query).unwrap();
+        assert_eq!(query["group"], "web");
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// One agent with its status and the syscollector data nexus could fetch.
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryRecord {
+    pub id: String,
+    pub name: Option<String>,
+    pub status: Option<String>,
+    pub ip: Option<String>,
+    pub groups: Vec<String>,
+    /// Wazuh agent version, e.g. `Wazuh v4.7.0`.
+    pub version: Option<String>,
+    pub last_keep_alive: Option<String>,
+    pub os: Option<OsInfo>,
+    pub hardware: Option<HardwareInfo>,
+    pub addresses: Vec<NetAddress>,
+    /// Sections that could not be fetched, as `"<section>: <error>"`. Agents
+    /// that never connected simply have no data and no error.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OsInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub platform: Option<String>,
+    pub architecture: Option<String>,
+    pub hostname: Option<String>,
+    /// Kernel release.
+    pub release: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HardwareInfo {
+    pub cpu_name: Option<String>,
+    pub cpu_cores: Option<u64>,
+    pub ram_total_kb: Option<u64>,
+    pub board_serial: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetAddress {
+    pub iface: Option<String>,
+    pub proto: Option<String>,
+    pub address: Option<String>,
+    pub netmask: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryResponse {
+    pub total: usize,
+    pub items: Vec<InventoryRecord>,
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+impl InventoryRecord {
+    /// Starts a record from one `GET /agents` item.
+    pub fn from_agent(agent: &Value) -> Self {
+        Self {
+            id: text(&agent["id"]).unwrap_or_default(),
+            name: text(&agent["name"]),
+            status: text(&agent["status"]),
+            ip: text(&agent["ip"]),
+            groups: agent["group"]
+                .as_array()
+                .map(|groups| groups.iter().filter_map(text).collect())
+                .unwrap_or_default(),
+            version: text(&agent["version"]),
+            last_keep_alive: text(&agent["lastKeepAlive"]),
+            os: None,
+            hardware: None,
+            addresses: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl OsInfo {
+    /// From the first item of `syscollector/{agent_id}/os`.
+    pub fn from_item(item: &Value) -> Self {
+        Self {
+            name: text(&item["os"]["name"]),
+            version: text(&item["os"]["version"]),
+            platform: text(&item["os"]["platform"]),
+            architecture: text(&item["architecture"]),
+            hostname: text(&item["hostname"]),
+            release: text(&item["release"]),
+        }
+    }
+}
+
+impl HardwareInfo {
+    /// From the first item of `syscollector/{agent_id}/hardware`.
+    pub fn from_item(item: &Value) -> Self {
+        Self {
+            cpu_name: text(&item["cpu"]["name"]),
+            cpu_cores: item["cpu"]["cores"].as_u64(),
+            ram_total_kb: item["ram"]["total"].as_u64(),
+            board_serial: text(&item["board_serial"]),
+        }
+    }
+}
+
+impl NetAddress {
+    /// From one item of `syscollector/{agent_id}/netaddr`.
+    pub fn from_item(item: &Value) -> Self {
+        Self {
+            iface: text(&item["iface"]),
+            proto: text(&item["proto"]),
+            address: text(&item["address"]),
+            netmask: text(&item["netmask"]),
+        }
+    }
+}
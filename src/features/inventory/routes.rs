@@ -0,0 +1,12 @@
+use axum::{
+    Router,
+    routing::post,
+};
+use crate::shared::state::AppState;
+use super::handlers::*;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        // Agents joined with their syscollector data
+        .route("/inventory", post(get_inventory))
+}
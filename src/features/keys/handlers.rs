@@ -0,0 +1,59 @@
+use axum::{extract::{Path, State}, http::StatusCode, Json};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use crate::shared::access::KeyStore;
+use crate::shared::admin::AdminAccess;
+use crate::shared::state::AppState;
+use super::models::{IssueKeyRequest, IssuedKey, KeyListing};
+
+type AdminResult<T> = Result<T, (StatusCode, Json<Value>)>;
+
+fn key_store(state: &AppState) -> AdminResult<&Arc<KeyStore>> {
+    state.access.as_ref().ok_or_else(|| (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({ "error": "API keys are disabled: NEXUS_API_KEYS_FILE is not set" })),
+    ))
+}
+
+pub async fn list_keys(_admin: AdminAccess, State(state): State<AppState>) -> AdminResult<Json<KeyListing>> {
+    let store = key_store(&state)?;
+    Ok(Json(KeyListing {
+        roles: store.roles(),
+        tenants: store.tenants(),
+        keys: store.keys(),
+    }))
+}
+
+pub async fn issue_key(
+    _admin: AdminAccess,
+    State(state): State<AppState>,
+    Json(payload): Json<IssueKeyRequest>,
+) -> AdminResult<(StatusCode, Json<IssuedKey>)> {
+    let key = key_store(&state)?
+        .issue(&payload.id, payload.roles.clone(), payload.tenant.clone())
+        .map_err(|e| (e.status_code(), Json(json!({ "error": e.to_string() }))))?;
+
+    println!("Issued API key '{}' with roles {:?}", payload.id, payload.roles);
+    Ok((StatusCode::CREATED, Json(IssuedKey {
+        id: payload.id,
+        key,
+        roles: payload.roles,
+        tenant: payload.tenant,
+    })))
+}
+
+pub async fn revoke_key(
+    _admin: AdminAccess,
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+) -> AdminResult<StatusCode> {
+    let removed = key_store(&state)?
+        .revoke(&key_id)
+        .map_err(|e| (e.status_code(), Json(json!({ "error": e.to_string() }))))?;
+
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, Json(json!({ "error": format!("No key '{}'", key_id) }))));
+    }
+    println!("Revoked API key '{}'", key_id);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,7 @@
+mod models;
+mod routes;
+mod handlers;
+
+pub use routes::routes;
+pub use handlers::*;
+pub use models::*;
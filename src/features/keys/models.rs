@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::shared::access::{KeySummary, Role};
+use crate::shared::tenants::Tenant;
+
+#[derive(Debug, Serialize)]
+pub struct KeyListing {
+    pub roles: BTreeMap<String, Role>,
+    pub tenants: BTreeMap<String, Tenant>,
+    pub keys: Vec<KeySummary>,
+}
+
+/// Roles must already be defined in the key store file.
+#[derive(Debug, Deserialize)]
+pub struct IssueKeyRequest {
+    pub id: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Tenant the key is confined to; must be defined in the key store file.
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+/// The only time the secret is returned; nexus keeps just its hash.
+#[derive(Debug, Serialize)]
+pub struct IssuedKey {
+    pub id: String,
+    pub key: String,
+    pub roles: Vec<String>,
+    pub tenant: Option<String>,
+}
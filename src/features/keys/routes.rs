@@ -0,0 +1,13 @@
+use axum::{
+    Router,
+    routing::{delete, get},
+};
+use crate::shared::state::AppState;
+use super::handlers::*;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        // Admin endpoints, guarded by NEXUS_ADMIN_TOKEN
+        .route("/admin/keys", get(list_keys).post(issue_key))
+        .route("/admin/keys/:key_id", delete(revoke_key))
+}
@@ -1,11 +1,13 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
 // CDB lists information endpoints
-pub async fn get_lists(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "lists", |url| url).await
+pub async fn get_lists(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "lists", |url| url).await
 }
 
-pub async fn get_lists_files(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "lists/files", |url| url).await
+pub async fn get_lists_files(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "lists/files", |url| url).await
 }
@@ -1,43 +1,45 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
 // API info
-pub async fn get_api_info(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "", |url| url).await
+pub async fn get_api_info(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "", |url| url).await
 }
 
 // Basic manager information
-pub async fn get_manager_status(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/status", |url| url).await
+pub async fn get_manager_status(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/status", |url| url).await
 }
 
-pub async fn get_manager_info(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/info", |url| url).await
+pub async fn get_manager_info(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/info", |url| url).await
 }
 
 // Configuration
-pub async fn get_manager_configuration(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/configuration", |url| url).await
+pub async fn get_manager_configuration(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/configuration", |url| url).await
 }
 
 // Statistics
-pub async fn get_manager_stats(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/stats", |url| url).await
+pub async fn get_manager_stats(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/stats", |url| url).await
 }
 
-pub async fn get_manager_hourly_stats(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/stats/hourly", |url| url).await
+pub async fn get_manager_hourly_stats(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/stats/hourly", |url| url).await
 }
 
-pub async fn get_manager_weekly_stats(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/stats/weekly", |url| url).await
+pub async fn get_manager_weekly_stats(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/stats/weekly", |url| url).await
 }
 
 // Logs
-pub async fn get_manager_logs(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/logs", |url| url).await
+pub async fn get_manager_logs(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/logs", |url| url).await
 }
 
-pub async fn get_manager_logs_summary(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "manager/logs/summary", |url| url).await
+pub async fn get_manager_logs_summary(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "manager/logs/summary", |url| url).await
 }
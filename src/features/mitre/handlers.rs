@@ -1,31 +1,33 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
 // MITRE information endpoints
-pub async fn get_mitre_metadata(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/metadata", |url| url).await
+pub async fn get_mitre_metadata(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/metadata", |url| url).await
 }
 
-pub async fn get_mitre_references(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/references", |url| url).await
+pub async fn get_mitre_references(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/references", |url| url).await
 }
 
-pub async fn get_mitre_techniques(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/techniques", |url| url).await
+pub async fn get_mitre_techniques(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/techniques", |url| url).await
 }
 
-pub async fn get_mitre_tactics(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/tactics", |url| url).await
+pub async fn get_mitre_tactics(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/tactics", |url| url).await
 }
 
-pub async fn get_mitre_groups(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/groups", |url| url).await
+pub async fn get_mitre_groups(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/groups", |url| url).await
 }
 
-pub async fn get_mitre_mitigations(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/mitigations", |url| url).await
+pub async fn get_mitre_mitigations(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/mitigations", |url| url).await
 }
 
-pub async fn get_mitre_software(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "mitre/software", |url| url).await
+pub async fn get_mitre_software(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "mitre/software", |url| url).await
 }
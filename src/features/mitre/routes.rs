@@ -2,9 +2,10 @@ use axum::{
     Router,
     routing::post,
 };
+use crate::shared::state::AppState;
 use super::handlers::*;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         // MITRE information endpoints
         .route("/mitre/groups", post(get_mitre_groups))
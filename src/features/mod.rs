@@ -1,8 +1,13 @@
 pub mod agents;
+pub mod audit;
 pub mod auth;
+pub mod cache;
 pub mod ciscat;
 pub mod decoders;
+pub mod events;
 pub mod groups;
+pub mod inventory;
+pub mod keys;
 pub mod lists;
 pub mod manager;
 pub mod mitre;
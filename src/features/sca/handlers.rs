@@ -1,10 +1,12 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
-pub async fn get_sca(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "sca/{agent_id}", |url| url).await
+pub async fn get_sca(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "sca/{agent_id}", |url| url).await
 }
 
-pub async fn get_checks(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "sca/{agent_id}/checks/{policy_id}", |url| url).await
+pub async fn get_checks(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "sca/{agent_id}/checks/{policy_id}", |url| url).await
 }
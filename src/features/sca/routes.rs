@@ -2,9 +2,10 @@ use axum::{
     routing::post,
     Router,
 };
+use crate::shared::state::AppState;
 use super::handlers::*;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/sca/:agent_id", post(get_sca))
         .route("/sca/:agent_id/checks/:policy_id", post(get_checks))
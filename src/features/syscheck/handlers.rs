@@ -1,10 +1,12 @@
-use axum::Json;
-use crate::shared::common::{WazuhRequest, handle_wazuh_request};
+use axum::extract::State;
+use crate::shared::common::{ProxyResult, handle_wazuh_request};
+use crate::shared::path_params::WazuhCall;
+use crate::shared::state::AppState;
 
-pub async fn get_syscheck(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "syscheck/{agent_id}", |url| url).await
+pub async fn get_syscheck(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "syscheck/{agent_id}", |url| url).await
 }
 
-pub async fn get_last_scan(Json(payload): Json<WazuhRequest>) -> Json<serde_json::Value> {
-    handle_wazuh_request(payload, "syscheck/{agent_id}/last_scan", |url| url).await
+pub async fn get_last_scan(State(state): State<AppState>, WazuhCall(payload): WazuhCall) -> ProxyResult {
+    handle_wazuh_request(&state, payload, "syscheck/{agent_id}/last_scan", |url| url).await
 }
@@ -2,9 +2,10 @@ use axum::{
     routing::post,
     Router,
 };
+use crate::shared::state::AppState;
 use super::handlers::*;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/syscheck/:agent_id", post(get_syscheck))
         .route("/syscheck/:agent_id/last_scan", post(get_last_scan))
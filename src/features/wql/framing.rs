@@ -0,0 +1,225 @@
+//! Length-prefixed frames for `WqlFraming::Framed` connections.
+//!
+//! Every frame is a 12-byte header followed by `length` bytes of payload:
+//!
+//! | bytes | field                                   |
+//! |-------|-----------------------------------------|
+//! | 0..4  | magic `WQLF`                            |
+//! | 4     | frame format version, `1`               |
+//! | 5     | content type, `1` = JSON                |
+//! | 6     | compression, `0` = none, `1` = gzip     |
+//! | 7     | reserved, `0`                           |
+//! | 8..12 | payload length, big-endian `u32`        |
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{Read, Write};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+
+use crate::shared::wql::WqlCompression;
+
+pub const MAGIC: &[u8; 4] = b"WQLF";
+pub const HEADER_LEN: usize = 12;
+const FORMAT_VERSION: u8 = 1;
+const CONTENT_JSON: u8 = 1;
+
+/// Why a frame could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    /// The connection closed, failed or timed out.
+    Io(String),
+    /// Bytes arrived, but not a frame nexus accepts.
+    Invalid(String),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(message) | Self::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub compression: WqlCompression,
+    /// Payload bytes on the wire, i.e. after compression.
+    pub length: u32,
+}
+
+impl FrameHeader {
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = FORMAT_VERSION;
+        header[5] = CONTENT_JSON;
+        header[6] = match self.compression {
+            WqlCompression::None => 0,
+            WqlCompression::Gzip => 1,
+        };
+        header[8..].copy_from_slice(&self.length.to_be_bytes());
+        header
+    }
+
+    /// Rejects anything nexus cannot read, including payloads over `max_len`,
+    /// before the payload itself is read.
+    pub fn decode(header: &[u8; HEADER_LEN], max_len: usize) -> Result<Self, String> {
+        if &header[..4] != MAGIC {
+            return Err("Not a WQL frame (bad magic); is the gateway using legacy framing?".to_string());
+        }
+        if header[4] != FORMAT_VERSION {
+            return Err(format!("Unsupported WQL frame version {}", header[4]));
+        }
+        if header[5] != CONTENT_JSON {
+            return Err(format!("Unsupported WQL frame content type {}", header[5]));
+        }
+        let compression = match header[6] {
+            0 => WqlCompression::None,
+            1 => WqlCompression::Gzip,
+            other => return Err(format!("Unsupported WQL frame compression {}", other)),
+        };
+        let length = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+        if length as usize > max_len {
+            return Err(format!("Response too large: frame of {} bytes exceeds the {} byte limit", length, max_len));
+        }
+        Ok(Self { compression, length })
+    }
+}
+
+/// Header and payload of one JSON frame, compressed as asked.
+pub fn encode(json: &[u8], compression: WqlCompression) -> Result<Vec<u8>, String> {
+    let payload = match compression {
+        WqlCompression::None => json.to_vec(),
+        WqlCompression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(json).and_then(|_| encoder.finish()).map_err(|e| format!("Failed to compress frame: {}", e))?
+        }
+    };
+    let length = u32::try_from(payload.len()).map_err(|_| "Request too large for one frame".to_string())?;
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend_from_slice(&FrameHeader { compression, length }.encode());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+/// Decompresses a payload, refusing to inflate past `max_len`.
+pub fn decode_payload(header: &FrameHeader, payload: Vec<u8>, max_len: usize) -> Result<Vec<u8>, String> {
+    match header.compression {
+        WqlCompression::None => Ok(payload),
+        WqlCompression::Gzip => {
+            let mut json = Vec::new();
+            GzDecoder::new(payload.as_slice())
+                .take(max_len as u64 + 1)
+                .read_to_end(&mut json)
+                .map_err(|e| format!("Failed to decompress frame: {}", e))?;
+            if json.len() > max_len {
+                return Err(format!("Response too large: more than {} bytes after decompression", max_len));
+            }
+            Ok(json)
+        }
+    }
+}
+
+pub async fn write_frame<W: AsyncWrite + Unpin>(stream: &mut W, json: &[u8], compression: WqlCompression) -> Result<(), String> {
+    let frame = encode(json, compression)?;
+    stream.write_all(&frame).await.map_err(|e| format!("Failed to send frame: {}", e))?;
+    stream.flush().await.map_err(|e| format!("Failed to send frame: {}", e))
+}
+
+/// Reads one frame and returns its decompressed JSON. `read_timeout` bounds the
+/// wait for the header and for each part of the payload.
+pub async fn read_frame<R: AsyncRead + Unpin>(stream: &mut R, max_len: usize, read_timeout: Duration) -> Result<Vec<u8>, ReadError> {
+    let mut header = [0u8; HEADER_LEN];
+    read_exact(stream, &mut header, read_timeout).await.map_err(ReadError::Io)?;
+    let header = FrameHeader::decode(&header, max_len).map_err(ReadError::Invalid)?;
+
+    let mut payload = vec![0u8; header.length as usize];
+    read_exact(stream, &mut payload, read_timeout).await.map_err(ReadError::Io)?;
+    decode_payload(&header, payload, max_len).map_err(ReadError::Invalid)
+}
+
+async fn read_exact<R: AsyncRead + Unpin>(stream: &mut R, buffer: &mut [u8], read_timeout: Duration) -> Result<(), String> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match timeout(read_timeout, stream.read(&mut buffer[filled..])).await {
+            Ok(Ok(0)) if filled == 0 => return Err("Connection closed by server".to_string()),
+            Ok(Ok(0)) => return Err("Connection closed in the middle of a frame".to_string()),
+            Ok(Ok(n)) => filled += n,
+            Ok(Err(e)) => return Err(format!("Failed to read response: {}", e)),
+            Err(_) => return Err("Read timeout".to_string()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: usize = 1024 * 1024;
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    async fn roundtrip(frame: Vec<u8>, max_len: usize) -> Result<Vec<u8>, String> {
+        read_frame(&mut frame.as_slice(), max_len, TIMEOUT).await.map_err(|e| e.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_frames_roundtrip() {
+        let json = br#"{"status":true,"data":"x"}"#;
+        for compression in [WqlCompression::None, WqlCompression::Gzip] {
+            let frame = encode(json, compression).unwrap();
+            assert_eq!(&frame[..4], MAGIC);
+            assert_eq!(roundtrip(frame, LIMIT).await.unwrap(), json, "{:?}", compression);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_several_frames_share_a_stream() {
+        let mut stream = encode(b"{\"n\":1}", WqlCompression::None).unwrap();
+        stream.extend(encode(b"{\"n\":2}", WqlCompression::Gzip).unwrap());
+        let mut reader = stream.as_slice();
+
+        assert_eq!(read_frame(&mut reader, LIMIT, TIMEOUT).await.unwrap(), b"{\"n\":1}");
+        assert_eq!(read_frame(&mut reader, LIMIT, TIMEOUT).await.unwrap(), b"{\"n\":2}");
+        assert_eq!(
+            read_frame(&mut reader, LIMIT, TIMEOUT).await.unwrap_err(),
+            ReadError::Io("Connection closed by server".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_frames_are_rejected() {
+        let err = roundtrip(br#"{"status":true,"data":"legacy response"}"#.to_vec(), LIMIT).await.unwrap_err();
+        assert!(err.contains("bad magic"), "{}", err);
+
+        let mut unknown = encode(b"{}", WqlCompression::None).unwrap();
+        unknown[6] = 9;
+        assert!(roundtrip(unknown, LIMIT).await.unwrap_err().contains("compression 9"));
+
+        let mut truncated = encode(b"{\"data\":\"abcdef\"}", WqlCompression::None).unwrap();
+        truncated.truncate(HEADER_LEN + 4);
+        assert!(roundtrip(truncated, LIMIT).await.unwrap_err().contains("middle of a frame"));
+    }
+
+    #[tokio::test]
+    async fn test_size_limits() {
+        let json = vec![b' '; 4096];
+
+        let err = roundtrip(encode(&json, WqlCompression::None).unwrap(), 1024).await.unwrap_err();
+        assert!(err.contains("exceeds the 1024 byte limit"), "{}", err);
+
+        // Compresses far below the limit, but must not be inflated past it
+        let bomb = encode(&json, WqlCompression::Gzip).unwrap();
+        assert!(bomb.len() < 1024);
+        let err = roundtrip(bomb, 1024).await.unwrap_err();
+        assert!(err.contains("after decompression"), "{}", err);
+
+        // Larger than the old fixed 10MB cap
+        let large = vec![b'a'; 11 * 1024 * 1024];
+        let frame = encode(&large, WqlCompression::None).unwrap();
+        assert_eq!(roundtrip(frame, 16 * 1024 * 1024).await.unwrap().len(), large.len());
+    }
+}
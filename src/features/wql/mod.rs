@@ -1,8 +1,16 @@
 mod models;
 mod routes;
+mod framing;
 mod handlers;
+mod pool;
+mod replay;
+mod signing;
+mod transport;
 pub mod report;
 
 pub use routes::routes;
 pub use handlers::*;
 pub use models::*;
+pub use pool::{spawn_reaper, PoolStats};
+pub use signing::Version;
+pub use transport::WqlTransport;
@@ -10,6 +10,12 @@ pub struct Agent {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthRequest {
+    /// `2` for HMAC signing; absent in v1 requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    /// Client key used for a v2 signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
     pub client_id: String,
     pub timestamp: u64,
     pub nonce: String,
@@ -18,21 +24,17 @@ pub struct AuthRequest {
     pub wql_query: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WazuhAuthRequest {
-    pub endpoint: String,
-    pub username: String,
-    pub password: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WazuhAuthResponse {
-    pub token: Option<String>,
-    pub error: Option<String>,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Response {
+    /// Absent in v1, so v1 responses serialize exactly as the gateway signed them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    /// Server key used for a v2 signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// Nonce of the request being answered; always set in v2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
     pub status: bool,
     pub data: String,
     pub session_id: String,
@@ -53,19 +55,14 @@ pub struct AgentResult {
 }
 
 // New enum for report types
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub enum ReportType {
+    #[default]
     Daily,
     Weekly,
     Monthly,
 }
 
-impl Default for ReportType {
-    fn default() -> Self {
-        ReportType::Daily
-    }
-}
-
 // New simplified response structure
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SimplifiedQueryResponse {
@@ -0,0 +1,255 @@
+//! Bounded pool of TLS connections to the WQL gateway.
+//!
+//! At most `pool_size` connections are open at once; callers beyond that wait
+//! up to `pool_acquire_timeout` for one to be returned. Framed connections go
+//! back to the pool with their gateway session after a successful exchange, so
+//! later requests skip the TCP and TLS handshakes and the authentication.
+//! Legacy connections carry a single request and are never returned; for them
+//! the pool only bounds how many are open.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+use crate::shared::state::AppState;
+use crate::shared::wql::WqlConfig;
+use super::transport::Connection;
+
+/// Served by `GET /wql/pool/stats`.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct PoolStats {
+    pub max_size: usize,
+    /// Connections currently open, idle or in use.
+    pub open: usize,
+    pub idle: usize,
+    pub in_use: usize,
+    /// Requests waiting for a connection to be returned.
+    pub waiting: usize,
+    /// Connections opened, i.e. TCP and TLS handshakes done.
+    pub opened: u64,
+    /// Requests sent on a connection that was already open.
+    pub reused: u64,
+    /// Closed after sitting idle longer than `pool_idle_timeout`.
+    pub closed_idle: u64,
+    /// Closed after reaching `pool_max_lifetime`.
+    pub closed_expired: u64,
+    /// Found closed or out of step by the health check before reuse.
+    pub closed_unhealthy: u64,
+    /// Requests that gave up waiting for a connection.
+    pub acquire_timeouts: u64,
+}
+
+struct Idle {
+    connection: Connection,
+    opened_at: Instant,
+    idle_since: Instant,
+}
+
+/// Why an idle connection is closed rather than reused.
+enum Eviction {
+    Idle,
+    Expired,
+    Unhealthy,
+}
+
+pub struct Pool {
+    max_size: usize,
+    idle_timeout: Duration,
+    max_lifetime: Duration,
+    acquire_timeout: Duration,
+    permits: Semaphore,
+    /// Most recently returned last.
+    idle: Mutex<Vec<Idle>>,
+    open: AtomicUsize,
+    waiting: AtomicUsize,
+    opened: AtomicU64,
+    reused: AtomicU64,
+    closed_idle: AtomicU64,
+    closed_expired: AtomicU64,
+    closed_unhealthy: AtomicU64,
+    acquire_timeouts: AtomicU64,
+}
+
+impl Pool {
+    pub fn new(config: &WqlConfig) -> Self {
+        Self {
+            max_size: config.pool_size,
+            idle_timeout: config.pool_idle_timeout,
+            max_lifetime: config.pool_max_lifetime,
+            acquire_timeout: config.pool_acquire_timeout,
+            permits: Semaphore::new(config.pool_size),
+            idle: Mutex::new(Vec::new()),
+            open: AtomicUsize::new(0),
+            waiting: AtomicUsize::new(0),
+            opened: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+            closed_idle: AtomicU64::new(0),
+            closed_expired: AtomicU64::new(0),
+            closed_unhealthy: AtomicU64::new(0),
+            acquire_timeouts: AtomicU64::new(0),
+        }
+    }
+
+    /// Hands out the most recently used healthy idle connection, or opens a new
+    /// one once a slot is free.
+    pub async fn acquire(&self, config: &WqlConfig) -> Result<Lease<'_>, String> {
+        let permit = {
+            let _waiting = Waiting::new(&self.waiting);
+            match timeout(self.acquire_timeout, self.permits.acquire()).await {
+                Ok(permit) => permit.map_err(|_| "The WQL connection pool is closed".to_string())?,
+                Err(_) => {
+                    self.acquire_timeouts.fetch_add(1, Ordering::Relaxed);
+                    return Err(format!(
+                        "No WQL connection became free within {}s ({} in use)",
+                        self.acquire_timeout.as_secs(), self.max_size
+                    ));
+                }
+            }
+        };
+
+        self.evict_idle();
+        loop {
+            let next = self.idle.lock().unwrap().pop();
+            let Some(mut idle) = next else { break };
+            if idle.connection.is_healthy() {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                return Ok(Lease { pool: self, connection: Some(idle.connection), opened_at: idle.opened_at, reused: true, _permit: permit });
+            }
+            self.close(Eviction::Unhealthy);
+        }
+
+        let connection = Connection::open(config).await?;
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        self.open.fetch_add(1, Ordering::Relaxed);
+        Ok(Lease { pool: self, connection: Some(connection), opened_at: Instant::now(), reused: false, _permit: permit })
+    }
+
+    /// Closes idle connections past `pool_idle_timeout` or `pool_max_lifetime`.
+    pub fn evict_idle(&self) {
+        let now = Instant::now();
+        let mut evicted = Vec::new();
+        self.idle.lock().unwrap().retain(|idle| match self.eviction(idle, now) {
+            Some(reason) => {
+                evicted.push(reason);
+                false
+            }
+            None => true,
+        });
+        for reason in evicted {
+            self.close(reason);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.evict_idle();
+        let idle = self.idle.lock().unwrap().len();
+        let open = self.open.load(Ordering::Relaxed);
+        PoolStats {
+            max_size: self.max_size,
+            open,
+            idle,
+            in_use: open.saturating_sub(idle),
+            waiting: self.waiting.load(Ordering::Relaxed),
+            opened: self.opened.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            closed_idle: self.closed_idle.load(Ordering::Relaxed),
+            closed_expired: self.closed_expired.load(Ordering::Relaxed),
+            closed_unhealthy: self.closed_unhealthy.load(Ordering::Relaxed),
+            acquire_timeouts: self.acquire_timeouts.load(Ordering::Relaxed),
+        }
+    }
+
+    fn eviction(&self, idle: &Idle, now: Instant) -> Option<Eviction> {
+        if now.duration_since(idle.opened_at) >= self.max_lifetime {
+            Some(Eviction::Expired)
+        } else if now.duration_since(idle.idle_since) >= self.idle_timeout {
+            Some(Eviction::Idle)
+        } else {
+            None
+        }
+    }
+
+    /// Accounts for a connection that was dropped.
+    fn close(&self, reason: Eviction) {
+        self.open.fetch_sub(1, Ordering::Relaxed);
+        let counter = match reason {
+            Eviction::Idle => &self.closed_idle,
+            Eviction::Expired => &self.closed_expired,
+            Eviction::Unhealthy => &self.closed_unhealthy,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A connection checked out of the pool. Dropping it closes the connection;
+/// `release` hands it back for reuse.
+pub struct Lease<'a> {
+    pool: &'a Pool,
+    connection: Option<Connection>,
+    opened_at: Instant,
+    /// Whether the connection already served an earlier request.
+    pub reused: bool,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Lease<'_> {
+    pub fn connection(&mut self) -> &mut Connection {
+        self.connection.as_mut().expect("connection is only taken on release")
+    }
+
+    /// Returns the connection to the pool, unless it has outlived `pool_max_lifetime`.
+    pub fn release(mut self) {
+        let Some(connection) = self.connection.take() else { return };
+        let idle = Idle { connection, opened_at: self.opened_at, idle_since: Instant::now() };
+        match self.pool.eviction(&idle, idle.idle_since) {
+            Some(reason) => self.pool.close(reason),
+            None => self.pool.idle.lock().unwrap().push(idle),
+        }
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        if self.connection.take().is_some() {
+            self.pool.open.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Counts a caller as waiting until it gets a slot, gives up or is cancelled.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Closes idle connections in the background so they do not stay open long
+/// after the last report. `None` when the WQL transport is not configured.
+pub fn spawn_reaper(state: &AppState) -> Option<JoinHandle<()>> {
+    let transport = state.wql.as_ref()?;
+    let interval = transport.config().pool_idle_timeout / 2;
+    let transport = Arc::downgrade(transport);
+
+    Some(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match transport.upgrade() {
+                Some(transport) => transport.evict_idle(),
+                None => return,
+            }
+        }
+    }))
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::shared::wql::WqlConfig;
+use super::models::{AuthRequest, Response};
+use super::signing::{self, Version};
+
+/// Decides whether a gateway response may be used: it must be validly signed,
+/// answer the request that was sent, be fresh and not have been accepted before.
+///
+/// v1 gateways do not echo the request nonce, so their responses are only
+/// protected by the skew window and the duplicate check, which relies on the
+/// gateway's per-connection `session_id` to tell identical answers apart; framed
+/// connections keep their session, so they only speak v2. A v2 request is never
+/// answered by such a response: the only one accepted is a v1 gateway turning
+/// it down.
+#[derive(Default)]
+pub struct ReplayGuard {
+    /// Responses already accepted, with the unix time after which they fail
+    /// the skew check anyway and can be forgotten.
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+impl ReplayGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `now` is the current unix time in seconds.
+    pub fn accept(&self, config: &WqlConfig, request: &AuthRequest, response: &Response, now: u64) -> Result<(), String> {
+        // Nothing unsigned reaches the cache
+        signing::verify_response(config, response)?;
+
+        match (&response.nonce, Version::of(response)) {
+            (Some(nonce), _) if *nonce != request.nonce => {
+                return Err("Response answers a different request (nonce mismatch)".to_string());
+            }
+            (None, Version::V2) => return Err("v2 response does not carry the request nonce".to_string()),
+            (None, Version::V1) if request.version == Some(2) && response.status => {
+                return Err("v1 response to a v2 request does not carry the request nonce".to_string());
+            }
+            _ => {}
+        }
+
+        let skew = config.max_skew.as_secs();
+        if response.timestamp.abs_diff(now) > skew {
+            return Err(format!(
+                "Stale response: timestamp {} is more than {}s away from local time {}",
+                response.timestamp, skew, now
+            ));
+        }
+
+        // v2 nonces are ours and unique; v1 responses are told apart by their signature
+        let key = match &response.nonce {
+            Some(nonce) => format!("nonce:{}", nonce),
+            None => format!("signature:{}", response.signature),
+        };
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, expires| *expires >= now);
+        if seen.contains_key(&key) {
+            return Err("Replayed response rejected".to_string());
+        }
+        seen.insert(key, response.timestamp.saturating_add(skew));
+        Ok(())
+    }
+
+    /// Responses currently remembered.
+    #[cfg(test)]
+    fn remembered(&self) -> usize {
+        self.seen.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::wql::signing::tests::{config, gateway_sign, response};
+
+    const NOW: u64 = 1_700_000_001;
+
+    fn request(nonce: &str) -> AuthRequest {
+        AuthRequest {
+            version: Some(2),
+            key_id: Some("c-2024".to_string()),
+            client_id: "client1".to_string(),
+            timestamp: NOW,
+            nonce: nonce.to_string(),
+            signature: String::new(),
+            session_id: None,
+            wql_query: "{}".to_string(),
+        }
+    }
+
+    fn v1_request(nonce: &str) -> AuthRequest {
+        AuthRequest { version: None, key_id: None, ..request(nonce) }
+    }
+
+    fn signed(version: Version, nonce: Option<&str>, timestamp: u64) -> Response {
+        let mut response = Response { nonce: nonce.map(str::to_string), timestamp, ..response() };
+        gateway_sign(&mut response, version, "s-new", "new-secret");
+        response
+    }
+
+    #[test]
+    fn test_fresh_responses_are_accepted() {
+        let (config, guard) = (config(), ReplayGuard::new());
+
+        assert_eq!(guard.accept(&config, &request("n1"), &signed(Version::V2, Some("n1"), NOW), NOW), Ok(()));
+        assert_eq!(guard.accept(&config, &v1_request("n2"), &signed(Version::V1, None, NOW - 10), NOW), Ok(()));
+        assert_eq!(guard.remembered(), 2);
+    }
+
+    #[test]
+    fn test_tampered_frames_are_rejected() {
+        let (config, guard) = (config(), ReplayGuard::new());
+
+        for version in [Version::V1, Version::V2] {
+            let mut response = signed(version, Some("n1"), NOW);
+            response.data = r#"{"hits":{"total":{"value":0}}}"#.to_string();
+            assert!(guard.accept(&config, &request("n1"), &response, NOW).is_err(), "{:?}", version);
+
+            let mut response = signed(version, Some("n1"), NOW);
+            response.timestamp += 1;
+            assert!(guard.accept(&config, &request("n1"), &response, NOW).is_err(), "{:?}", version);
+        }
+        assert_eq!(guard.remembered(), 0, "Rejected frames must not be remembered");
+    }
+
+    #[test]
+    fn test_responses_to_other_requests_are_rejected() {
+        let (config, guard) = (config(), ReplayGuard::new());
+
+        let err = guard.accept(&config, &request("n2"), &signed(Version::V2, Some("n1"), NOW), NOW).unwrap_err();
+        assert!(err.contains("nonce mismatch"), "{}", err);
+
+        let err = guard.accept(&config, &request("n2"), &signed(Version::V2, None, NOW), NOW).unwrap_err();
+        assert!(err.contains("does not carry the request nonce"), "{}", err);
+    }
+
+    #[test]
+    fn test_v1_frames_only_turn_down_v2_requests() {
+        let (config, guard) = (config(), ReplayGuard::new());
+
+        let err = guard.accept(&config, &request("n1"), &signed(Version::V1, None, NOW), NOW).unwrap_err();
+        assert!(err.contains("v1 response to a v2 request"), "{}", err);
+
+        let mut turned_down = Response { status: false, nonce: None, timestamp: NOW, ..response() };
+        gateway_sign(&mut turned_down, Version::V1, "s-new", "new-secret");
+        assert_eq!(guard.accept(&config, &request("n1"), &turned_down, NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_stale_frames_are_rejected() {
+        let (config, guard) = (config(), ReplayGuard::new());
+        let skew = config.max_skew.as_secs();
+
+        for timestamp in [NOW - skew - 1, NOW + skew + 1] {
+            let err = guard.accept(&config, &request("n1"), &signed(Version::V2, Some("n1"), timestamp), NOW).unwrap_err();
+            assert!(err.contains("Stale response"), "{}", err);
+        }
+        // The edges of the window are still fresh
+        assert_eq!(guard.accept(&config, &request("n1"), &signed(Version::V2, Some("n1"), NOW - skew), NOW), Ok(()));
+        assert_eq!(guard.accept(&config, &v1_request("n2"), &signed(Version::V1, None, NOW + skew), NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_replayed_frames_are_rejected() {
+        let (config, guard) = (config(), ReplayGuard::new());
+
+        for version in [Version::V1, Version::V2] {
+            let response = signed(version, Some("n1"), NOW);
+            let guard = ReplayGuard::new();
+            assert_eq!(guard.accept(&config, &request("n1"), &response, NOW), Ok(()));
+            let err = guard.accept(&config, &request("n1"), &response, NOW + 1).unwrap_err();
+            assert!(err.contains("Replayed"), "{:?}: {}", version, err);
+        }
+
+        // A v1 gateway without nonces: the same signed frame twice
+        let response = signed(Version::V1, None, NOW);
+        assert_eq!(guard.accept(&config, &v1_request("n1"), &response, NOW), Ok(()));
+        assert!(guard.accept(&config, &v1_request("n2"), &response, NOW).unwrap_err().contains("Replayed"));
+    }
+
+    #[test]
+    fn test_entries_are_forgotten_once_stale() {
+        let (config, guard) = (config(), ReplayGuard::new());
+        let skew = config.max_skew.as_secs();
+
+        guard.accept(&config, &request("n1"), &signed(Version::V2, Some("n1"), NOW), NOW).unwrap();
+        guard.accept(&config, &request("n2"), &signed(Version::V2, Some("n2"), NOW + skew), NOW + skew + 1).unwrap();
+
+        assert_eq!(guard.remembered(), 1, "The first response is past its window and was dropped");
+    }
+}
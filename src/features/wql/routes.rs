@@ -1,14 +1,15 @@
 use axum::{
     Router,
     routing::{post, get},
-    extract::{Path as AxumPath, Query},
-    response::{IntoResponse, Response},
+    extract::{Path as AxumPath, Query, State},
     http::{header, StatusCode},
 };
 use serde::Deserialize;
 use serde_json::json;
 use std::path::PathBuf;
-use super::handlers::handle_wql_query;
+use crate::shared::path_params::validate_segment;
+use crate::shared::state::AppState;
+use super::handlers::{get_pool_stats, handle_wql_query};
 use super::models::ReportType;
 use tokio::fs;
 
@@ -22,13 +23,25 @@ struct WqlQuery {
     report_type: Option<String>,
 }
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/wql/:group", post(handle_wql_query_wrapper))
+        .route("/wql/pool/stats", get(get_pool_stats))
         .route("/reports/:filename", get(serve_pdf))
 }
 
 async fn serve_pdf(AxumPath(filename): AxumPath<String>) -> ApiResponse {
+    if validate_segment("filename", &filename).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            [
+                (header::CONTENT_TYPE, "text/plain"),
+                (header::CONTENT_DISPOSITION, "inline"),
+            ],
+            b"Invalid report name".to_vec()
+        );
+    }
+
     let pdf_path = PathBuf::from("reports").join(filename);
     
     match fs::read(&pdf_path).await {
@@ -55,18 +68,19 @@ fn parse_report_type(report_type: Option<String>) -> ReportType {
     match report_type.as_deref() {
         Some("weekly") => ReportType::Weekly,
         Some("monthly") => ReportType::Monthly,
-        Some("daily") | _ => ReportType::Daily, // Default to daily if not specified or unknown
+        _ => ReportType::Daily, // Default to daily if not specified or unknown
     }
 }
 
 async fn handle_wql_query_wrapper(
+    State(state): State<AppState>,
     AxumPath(group): AxumPath<String>,
     Query(params): Query<WqlQuery>,
 ) -> ApiResponse {
     let report_type = parse_report_type(params.report_type);
     
     // Call the original handler
-    match handle_wql_query(group, report_type).await {
+    match handle_wql_query(&state, group, report_type).await {
         Ok(full_response) => {
             // Check if PDF format was requested
             if params.format.as_deref() == Some("pdf") {
@@ -0,0 +1,302 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::shared::wql::{WqlConfig, WqlKey};
+use super::models::{AuthRequest, Response};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Protocol version a request is signed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+impl Version {
+    /// The version a response claims; v1 gateways send no `version` field.
+    pub fn of(response: &Response) -> Self {
+        match response.version {
+            Some(2) => Self::V2,
+            _ => Self::V1,
+        }
+    }
+}
+
+/// v1: `client_id:timestamp:nonce`, hashed with the key appended.
+fn v1_request_input(request: &AuthRequest) -> String {
+    format!("{}:{}:{}", request.client_id, request.timestamp, request.nonce)
+}
+
+/// v2 also covers the key id and the query itself, so a captured signature
+/// cannot be reused for a different query.
+fn v2_request_input(request: &AuthRequest) -> String {
+    format!(
+        "v2\n{}\n{}\n{}\n{}\n{}",
+        request.client_id,
+        request.key_id.as_deref().unwrap_or_default(),
+        request.timestamp,
+        request.nonce,
+        hex_sha256(request.wql_query.as_bytes()),
+    )
+}
+
+/// v1 signs the response JSON with an empty `signature`.
+fn v1_response_input(response: &Response) -> Result<String, String> {
+    let mut unsigned = response.clone();
+    unsigned.signature = String::new();
+    serde_json::to_string(&unsigned).map_err(|e| format!("Failed to serialize response: {}", e))
+}
+
+/// v2 covers the echoed request nonce, binding the response to its request.
+fn v2_response_input(response: &Response) -> String {
+    format!(
+        "v2\n{}\n{}\n{}\n{}\n{}\n{}",
+        response.key_id.as_deref().unwrap_or_default(),
+        response.nonce.as_deref().unwrap_or_default(),
+        response.status,
+        response.session_id,
+        response.timestamp,
+        hex_sha256(response.data.as_bytes()),
+    )
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn v1_digest(data: &str, key: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(data.as_bytes());
+    hasher.update(key.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn hmac(key: &str) -> HmacSha256 {
+    HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length")
+}
+
+/// Compares without stopping at the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Fills in `version`, `key_id` and `signature`. v2 needs `config.client_key_id`.
+pub fn sign_request(config: &WqlConfig, version: Version, request: &mut AuthRequest) {
+    match version {
+        Version::V1 => {
+            request.version = None;
+            request.key_id = None;
+            request.signature = BASE64.encode(v1_digest(&v1_request_input(request), &config.client_key));
+        }
+        Version::V2 => {
+            request.version = Some(2);
+            request.key_id = config.client_key_id.clone();
+            let mut mac = hmac(&config.client_key);
+            mac.update(v2_request_input(request).as_bytes());
+            request.signature = BASE64.encode(mac.finalize().into_bytes());
+        }
+    }
+}
+
+/// Checks the response signature against the configured server keys. v1
+/// responses may match any key; v2 responses must match the key they name.
+pub fn verify_response(config: &WqlConfig, response: &Response) -> Result<(), String> {
+    let signature = BASE64
+        .decode(&response.signature)
+        .map_err(|_| "Invalid response signature".to_string())?;
+
+    let valid = match Version::of(response) {
+        Version::V1 => {
+            let input = v1_response_input(response)?;
+            // Every key is tried so the time taken does not reveal which one matched
+            config.server_keys.iter().fold(false, |valid, key| {
+                constant_time_eq(&v1_digest(&input, &key.secret), &signature) | valid
+            })
+        }
+        Version::V2 => {
+            let key = v2_key(&config.server_keys, response.key_id.as_deref())?;
+            let mut mac = hmac(&key.secret);
+            mac.update(v2_response_input(response).as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err("Invalid response signature".to_string())
+    }
+}
+
+fn v2_key<'a>(keys: &'a [WqlKey], key_id: Option<&str>) -> Result<&'a WqlKey, String> {
+    let key_id = key_id.ok_or("Response is missing its key_id")?;
+    keys.iter()
+        .find(|k| k.id.as_deref() == Some(key_id))
+        .ok_or_else(|| format!("Response is signed with unknown key '{}'", key_id))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::shared::wql::{WqlCompression, WqlFraming, WqlProtocol};
+    use std::time::Duration;
+
+    pub(in crate::features::wql) fn config() -> WqlConfig {
+        WqlConfig {
+            address: "gateway:8080".to_string(),
+            server_name: "gateway".to_string(),
+            insecure: false,
+            ca_file: None,
+            protocol: WqlProtocol::Auto,
+            client_id: "client1".to_string(),
+            client_key: "client-secret".to_string(),
+            client_key_id: Some("c-2024".to_string()),
+            server_keys: vec![
+                WqlKey { id: Some("s-old".to_string()), secret: "old-secret".to_string() },
+                WqlKey { id: Some("s-new".to_string()), secret: "new-secret".to_string() },
+            ],
+            connect_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(60),
+            request_timeout: Duration::from_secs(300),
+            max_retries: 5,
+            max_skew: Duration::from_secs(300),
+            framing: WqlFraming::Legacy,
+            compression: WqlCompression::None,
+            max_response_bytes: 10 * 1024 * 1024,
+            pool_size: 8,
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_lifetime: Duration::from_secs(600),
+            pool_acquire_timeout: Duration::from_secs(60),
+        }
+    }
+
+    fn unsigned_request() -> AuthRequest {
+        AuthRequest {
+            version: None,
+            key_id: None,
+            client_id: "client1".to_string(),
+            timestamp: 1_700_000_000,
+            nonce: "nonce-1".to_string(),
+            signature: String::new(),
+            session_id: None,
+            wql_query: r#"{"query":{"match_all":{}}}"#.to_string(),
+        }
+    }
+
+    /// Signs `response` the way the gateway does.
+    pub(in crate::features::wql) fn gateway_sign(response: &mut Response, version: Version, key_id: &str, secret: &str) {
+        response.signature = String::new();
+        match version {
+            Version::V1 => {
+                response.version = None;
+                response.key_id = None;
+                response.signature = BASE64.encode(v1_digest(&v1_response_input(response).unwrap(), secret));
+            }
+            Version::V2 => {
+                response.version = Some(2);
+                response.key_id = Some(key_id.to_string());
+                let mut mac = hmac(secret);
+                mac.update(v2_response_input(response).as_bytes());
+                response.signature = BASE64.encode(mac.finalize().into_bytes());
+            }
+        }
+    }
+
+    pub(in crate::features::wql) fn response() -> Response {
+        Response {
+            version: None,
+            key_id: None,
+            nonce: Some("nonce-1".to_string()),
+            status: true,
+            data: r#"{"hits":{"total":{"value":3}}}"#.to_string(),
+            session_id: "session-1".to_string(),
+            timestamp: 1_700_000_001,
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_v1_request_signature_is_unchanged() {
+        let mut request = unsigned_request();
+        sign_request(&config(), Version::V1, &mut request);
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"client1:1700000000:nonce-1");
+        hasher.update(b"client-secret");
+        assert_eq!(request.signature, BASE64.encode(hasher.finalize()));
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("version").is_none() && json.get("key_id").is_none());
+    }
+
+    #[test]
+    fn test_v2_request_signature_covers_the_query() {
+        let config = config();
+        let mut request = unsigned_request();
+        sign_request(&config, Version::V2, &mut request);
+        assert_eq!(request.version, Some(2));
+        assert_eq!(request.key_id.as_deref(), Some("c-2024"));
+
+        let mut mac = hmac("client-secret");
+        mac.update(v2_request_input(&request).as_bytes());
+        assert!(mac.verify_slice(&BASE64.decode(&request.signature).unwrap()).is_ok());
+
+        let mut other = AuthRequest { wql_query: "{}".to_string(), ..unsigned_request() };
+        sign_request(&config, Version::V2, &mut other);
+        assert_ne!(other.signature, request.signature);
+    }
+
+    #[test]
+    fn test_v2_response_accepts_any_active_key() {
+        let config = config();
+        for (id, secret) in [("s-old", "old-secret"), ("s-new", "new-secret")] {
+            let mut response = response();
+            gateway_sign(&mut response, Version::V2, id, secret);
+            assert_eq!(verify_response(&config, &response), Ok(()));
+        }
+
+        let mut wrong_key = response();
+        gateway_sign(&mut wrong_key, Version::V2, "s-new", "old-secret");
+        assert!(verify_response(&config, &wrong_key).is_err());
+
+        let mut unknown = response();
+        gateway_sign(&mut unknown, Version::V2, "s-retired", "retired-secret");
+        assert!(verify_response(&config, &unknown).unwrap_err().contains("unknown key 's-retired'"));
+    }
+
+    #[test]
+    fn test_tampered_responses_are_rejected() {
+        let config = config();
+        for version in [Version::V1, Version::V2] {
+            let mut response = response();
+            gateway_sign(&mut response, version, "s-new", "new-secret");
+            assert_eq!(verify_response(&config, &response), Ok(()));
+
+            let tampered = Response { data: r#"{"hits":{"total":{"value":0}}}"#.to_string(), ..response.clone() };
+            assert!(verify_response(&config, &tampered).is_err(), "{:?}", version);
+
+            let flipped = Response { status: false, ..response.clone() };
+            assert!(verify_response(&config, &flipped).is_err(), "{:?}", version);
+
+            let rebound = Response { nonce: Some("nonce-2".to_string()), ..response.clone() };
+            assert!(verify_response(&config, &rebound).is_err(), "{:?}", version);
+
+            let garbage = Response { signature: "not base64!".to_string(), ..response };
+            assert!(verify_response(&config, &garbage).is_err(), "{:?}", version);
+        }
+    }
+
+    #[test]
+    fn test_v1_response_matches_legacy_gateway() {
+        let mut response = Response { nonce: None, ..response() };
+        let unsigned = serde_json::to_string(&response).unwrap();
+        let mut hasher = Sha256::new();
+        hasher.update(unsigned.as_bytes());
+        hasher.update(b"old-secret");
+        response.signature = BASE64.encode(hasher.finalize());
+
+        assert_eq!(verify_response(&config(), &response), Ok(()));
+    }
+}
@@ -0,0 +1,340 @@
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH, Duration};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
+use tokio::net::{lookup_host, TcpSocket, TcpStream};
+use tokio::time::timeout;
+use tokio_native_tls::TlsStream;
+use uuid::Uuid;
+
+use crate::shared::wql::{WqlConfig, WqlFraming, WqlProtocol};
+use super::framing;
+use super::models::{AuthRequest, Response};
+use super::pool::{Pool, PoolStats};
+use super::replay::ReplayGuard;
+use super::signing::{self, Version};
+
+const INITIAL_BUFFER_SIZE: usize = 8192;
+const CHUNK_SIZE: usize = 1024 * 64; // Reduced to 64KB chunks for better stability
+
+/// Why an exchange failed.
+enum ExchangeError {
+    /// Sending or reading failed: the connection is gone, possibly closed by
+    /// the gateway while it sat in the pool.
+    Io(String),
+    /// Anything else, such as a response that fails verification.
+    Other(String),
+}
+
+impl From<String> for ExchangeError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<framing::ReadError> for ExchangeError {
+    fn from(error: framing::ReadError) -> Self {
+        match error {
+            framing::ReadError::Io(message) => Self::Io(message),
+            framing::ReadError::Invalid(message) => Self::Other(message),
+        }
+    }
+}
+
+impl ExchangeError {
+    fn into_message(self) -> String {
+        match self {
+            Self::Io(message) | Self::Other(message) => message,
+        }
+    }
+}
+
+/// Reads a legacy response, which ends when the gateway closes the connection.
+async fn stream_response(config: &WqlConfig, stream: &mut TlsStream<TcpStream>) -> Result<Vec<u8>, ExchangeError> {
+    let mut response_data = Vec::with_capacity(INITIAL_BUFFER_SIZE);
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut total_bytes = 0;
+
+    loop {
+        match timeout(config.read_timeout, stream.read(&mut buffer)).await {
+            Ok(read_result) => {
+                match read_result {
+                    Ok(0) => {
+                        if total_bytes == 0 {
+                            return Err(ExchangeError::Io("Connection closed by server".to_string()));
+                        }
+                        break;
+                    },
+                    Ok(n) => {
+                        if total_bytes + n > config.max_response_bytes {
+                            return Err(ExchangeError::Other("Response too large".to_string()));
+                        }
+                        response_data.extend_from_slice(&buffer[..n]);
+                        total_bytes += n;
+                    }
+                    Err(e) => return Err(ExchangeError::Io(format!("Failed to read response: {}", e))),
+                }
+            },
+            Err(_) => return Err(ExchangeError::Io("Read timeout".to_string())),
+        }
+    }
+
+    Ok(response_data)
+}
+
+async fn connect_tcp(config: &WqlConfig) -> Result<TcpStream, String> {
+    let addrs = lookup_host(&config.address)
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", config.address, e))?;
+
+    let mut last_error = format!("{} did not resolve to any address", config.address);
+    for addr in addrs {
+        let socket = if addr.is_ipv4() { TcpSocket::new_v4() } else { TcpSocket::new_v6() }
+            .map_err(|e| format!("Failed to create socket: {}", e))?;
+
+        // Set TCP_NODELAY
+        socket.set_nodelay(true)
+            .map_err(|e| format!("Failed to set TCP_NODELAY: {}", e))?;
+
+        match timeout(config.connect_timeout, socket.connect(addr)).await {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => last_error = format!("Failed to connect to {}: {}", addr, e),
+            Err(_) => last_error = format!("Connection timeout to {}", addr),
+        }
+    }
+    Err(last_error)
+}
+
+/// A TLS connection to the gateway.
+pub struct Connection {
+    stream: TlsStream<TcpStream>,
+    /// Session the gateway opened on this connection, sent with later requests
+    /// so it can skip authenticating them again. Only framed connections carry
+    /// more than one request.
+    session_id: Option<String>,
+}
+
+impl Connection {
+    pub async fn open(config: &WqlConfig) -> Result<Self, String> {
+        let stream = connect_tcp(config).await?;
+        let connector = config.tls_connector()?;
+
+        let stream = connector.connect(&config.server_name, stream)
+            .await
+            .map_err(|e| format!("TLS handshake with {} failed: {}", config.server_name, e))?;
+        Ok(Self { stream, session_id: None })
+    }
+
+    /// An idle connection has nothing to read: the gateway closing it, or data
+    /// that answers no request, means it cannot be used any more. Checked
+    /// without waiting.
+    pub fn is_healthy(&mut self) -> bool {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut byte = [0u8; 1];
+        let mut buffer = ReadBuf::new(&mut byte);
+        matches!(Pin::new(&mut self.stream).poll_read(&mut cx, &mut buffer), Poll::Pending)
+    }
+
+    /// Sends one signed request and returns the verified response.
+    async fn exchange(
+        &mut self,
+        config: &WqlConfig,
+        replay: &ReplayGuard,
+        version: Version,
+        wql_query: String,
+    ) -> Result<Response, ExchangeError> {
+        let mut request = AuthRequest {
+            version: None,
+            key_id: None,
+            client_id: config.client_id.clone(),
+            timestamp: unix_now()?,
+            nonce: Uuid::new_v4().to_string(),
+            signature: String::new(),
+            session_id: self.session_id.clone(),
+            wql_query,
+        };
+        signing::sign_request(config, version, &mut request);
+
+        let request_json = serde_json::to_string(&request)
+            .map_err(|e| e.to_string())?;
+
+        let response_json = match config.framing {
+            WqlFraming::Framed => {
+                let stream = &mut self.stream;
+                match timeout(config.request_timeout, framing::write_frame(stream, request_json.as_bytes(), config.compression)).await {
+                    Ok(result) => result.map_err(ExchangeError::Io)?,
+                    Err(_) => return Err(ExchangeError::Io("Request timeout".to_string())),
+                }
+                framing::read_frame(stream, config.max_response_bytes, config.read_timeout).await?
+            }
+            WqlFraming::Legacy => {
+                self.send_chunked(config, &request_json).await?;
+                stream_response(config, &mut self.stream).await?
+            }
+        };
+
+        let response: Response = serde_json::from_slice(&response_json)
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        replay.accept(config, &request, &response, unix_now()?)?;
+        if response.status && !response.session_id.is_empty() {
+            self.session_id = Some(response.session_id.clone());
+        }
+        Ok(response)
+    }
+
+    async fn send_chunked(&mut self, config: &WqlConfig, request_json: &str) -> Result<(), ExchangeError> {
+        let stream = &mut self.stream;
+
+        // Split request into chunks if it's large
+        let chunks: Vec<&[u8]> = request_json.as_bytes()
+            .chunks(CHUNK_SIZE)
+            .collect();
+
+        // Send request with timeout
+        match timeout(config.request_timeout, async {
+            for chunk in chunks {
+                stream.write_all(chunk).await?;
+                stream.flush().await?;
+                // Small delay between chunks
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok::<(), std::io::Error>(())
+        }).await {
+            Ok(result) => result.map_err(|e| ExchangeError::Io(e.to_string())),
+            Err(_) => Err(ExchangeError::Io("Request timeout".to_string())),
+        }
+    }
+}
+
+/// Client for the WQL gateway, shared by all report jobs through `AppState`.
+pub struct WqlTransport {
+    config: WqlConfig,
+    /// Version the gateway answered with under `WqlProtocol::Auto`; 0 until known.
+    negotiated: AtomicU8,
+    replay: ReplayGuard,
+    pool: Pool,
+}
+
+impl WqlTransport {
+    pub fn new(config: WqlConfig) -> Self {
+        Self {
+            pool: Pool::new(&config),
+            config,
+            negotiated: AtomicU8::new(0),
+            replay: ReplayGuard::new(),
+        }
+    }
+
+    pub fn config(&self) -> &WqlConfig {
+        &self.config
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// Closes idle connections past their idle timeout or lifetime.
+    pub fn evict_idle(&self) {
+        self.pool.evict_idle();
+    }
+
+    /// Version the next request is signed with.
+    pub fn version(&self) -> Version {
+        match (self.config.protocol, &self.config.client_key_id) {
+            (WqlProtocol::V1, _) | (WqlProtocol::Auto, None) => Version::V1,
+            (WqlProtocol::V2, _) => Version::V2,
+            (WqlProtocol::Auto, Some(_)) => match self.negotiated.load(Ordering::Relaxed) {
+                1 => Version::V1,
+                _ => Version::V2,
+            },
+        }
+    }
+
+    /// Sends `wql_query` to the gateway, retrying up to `max_retries` times with
+    /// exponential backoff. Connections come from the pool; framed ones go
+    /// back to it after a successful exchange, legacy ones carry a single request.
+    pub async fn send_request_with_retry(&self, wql_query: String) -> Result<Response, String> {
+        let config = &self.config;
+        let mut retries = 0;
+
+        loop {
+            let version = self.version();
+            let last_error = match self.pool.acquire(config).await {
+                Ok(mut lease) => match lease.connection().exchange(config, &self.replay, version, wql_query.clone()).await {
+                    Ok(response) => {
+                        if config.framing == WqlFraming::Framed && response.status {
+                            lease.release();
+                        }
+                        match self.negotiate(version, &response) {
+                            // Sent again right away, in v1: the gateway did not fail
+                            Ok(true) => continue,
+                            Ok(false) => return Ok(response),
+                            Err(e) => e,
+                        }
+                    }
+                    // The gateway may have closed an idle connection; try a fresh one
+                    Err(ExchangeError::Io(e)) if lease.reused => {
+                        println!("Reused WQL connection failed, reconnecting: {}", e);
+                        continue;
+                    }
+                    Err(e) => e.into_message(),
+                },
+                Err(e) => e,
+            };
+
+            if retries >= config.max_retries {
+                return Err(format!("Max retries exceeded. Last error: {}", last_error));
+            }
+
+            retries += 1;
+            println!("Request failed, retrying ({}/{}): {}", retries, config.max_retries, last_error);
+
+            // Exponential backoff with jitter
+            let backoff = 2u64.pow(retries) + (rand::random::<u64>() % 1000);
+            tokio::time::sleep(Duration::from_millis(backoff)).await;
+        }
+    }
+
+    /// Remembers which version the gateway speaks. Returns `true` when a v2
+    /// request was turned down by a v1 gateway and has to be sent again as v1.
+    ///
+    /// Only an explicit refusal (`status: false`) leads to v1, and never once
+    /// the gateway has answered in v2: a v1 frame is then an attempt to
+    /// downgrade the connection.
+    fn negotiate(&self, sent: Version, response: &Response) -> Result<bool, String> {
+        match (sent, Version::of(response)) {
+            (Version::V2, Version::V1) => {
+                if self.config.protocol == WqlProtocol::V2 {
+                    return Err("The WQL gateway answered in protocol v1, but NEXUS_WQL_PROTOCOL requires v2".to_string());
+                }
+                if response.status {
+                    return Err("The WQL gateway answered a v2 request in protocol v1".to_string());
+                }
+                if self.config.framing == WqlFraming::Framed {
+                    return Err("The WQL gateway only speaks protocol v1, which NEXUS_WQL_FRAMING=framed cannot use".to_string());
+                }
+                if self.negotiated.load(Ordering::Relaxed) == 2 {
+                    return Err("The WQL gateway turned down protocol v2 after speaking it; not falling back to v1".to_string());
+                }
+                if self.negotiated.swap(1, Ordering::Relaxed) != 1 {
+                    println!("WQL gateway {} does not speak protocol v2, falling back to v1", self.config.address);
+                }
+                Ok(true)
+            }
+            (_, Version::V2) => {
+                self.negotiated.store(2, Ordering::Relaxed);
+                Ok(false)
+            }
+            (Version::V1, Version::V1) => Ok(false),
+        }
+    }
+}
+
+fn unix_now() -> Result<u64, String> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| e.to_string())
+}
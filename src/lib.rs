@@ -5,30 +5,62 @@ pub mod client;
 #[cfg(test)]
 pub mod tests;
 
-use axum::Router;
-use axum::routing::get;
+use axum::{extract::State, middleware, routing::get, Json, Router};
+use serde_json::{json, Value};
+use shared::access::{self, guard};
+use shared::rate_limit::quota;
+use shared::state::AppState;
 
-pub fn create_router() -> Router {
+/// Builds the application. Each feature module is wrapped in an access guard
+/// and a quota named after it, so API key roles and `NEXUS_RATE_LIMITS` apply
+/// per module.
+pub fn create_router(state: AppState) -> Router {
     Router::new()
-        .merge(features::agents::routes())
-        .merge(features::auth::routes())
-        .merge(features::ciscat::routes())
-        .merge(features::decoders::routes())
-        .merge(features::groups::routes())
-        .merge(features::lists::routes())
-        .merge(features::manager::routes())
-        .merge(features::mitre::routes())
-        .merge(features::rootcheck::routes())
-        .merge(features::rules::routes())
-        .merge(features::sca::routes())
-        .merge(features::security::routes())
-        .merge(features::syscheck::routes())
-        .merge(features::syscollector::routes())
-        .merge(features::tasks::routes())
-        .merge(features::wql::routes())
+        .merge(features::keys::routes())
+        .merge(features::audit::routes())
+        .merge(module(&state, "agents", features::agents::routes()))
+        .merge(module(&state, "auth", features::auth::routes()))
+        .merge(module(&state, "cache", features::cache::routes()))
+        .merge(module(&state, "ciscat", features::ciscat::routes()))
+        .merge(module(&state, "decoders", features::decoders::routes()))
+        .merge(module(&state, "events", features::events::routes()))
+        .merge(module(&state, "groups", features::groups::routes()))
+        .merge(module(&state, "inventory", features::inventory::routes()))
+        .merge(module(&state, "lists", features::lists::routes()))
+        .merge(module(&state, "manager", features::manager::routes()))
+        .merge(module(&state, "mitre", features::mitre::routes()))
+        .merge(module(&state, "rootcheck", features::rootcheck::routes()))
+        .merge(module(&state, "rules", features::rules::routes()))
+        .merge(module(&state, "sca", features::sca::routes()))
+        .merge(module(&state, "security", features::security::routes()))
+        .merge(module(&state, "syscheck", features::syscheck::routes()))
+        .merge(module(&state, "syscollector", features::syscollector::routes()))
+        .merge(module(&state, "tasks", features::tasks::routes()))
+        .merge(module(&state, "wql", features::wql::routes()))
         .route("/health", get(health_check))
+        .layer(middleware::from_fn_with_state(state.clone(), access::authenticate))
+        .with_state(state)
 }
 
-async fn health_check() -> axum::http::StatusCode {
-    axum::http::StatusCode::OK
+fn module(state: &AppState, name: &'static str, routes: Router<AppState>) -> Router<AppState> {
+    guard(name, quota(state, name, routes))
+}
+
+/// Always 200 while the process is up; `status` is `degraded` when any
+/// upstream's circuit breaker is not closed.
+async fn health_check(State(state): State<AppState>) -> Json<Value> {
+    let upstreams: Vec<Value> = state.config.upstreams
+        .iter()
+        .map(|upstream| json!({
+            "name": upstream.name,
+            "url": upstream.url,
+            "breaker": state.client.breaker_state(&upstream.name),
+        }))
+        .collect();
+
+    let degraded = upstreams.iter().any(|u| u["breaker"]["state"] != "closed");
+    Json(json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "upstreams": upstreams,
+    }))
 }
@@ -2,10 +2,25 @@ use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
 
 use sensex_nexus::create_router;
+use sensex_nexus::features::wql;
+use sensex_nexus::shared::state::AppState;
+use sensex_nexus::shared::{warmup, watcher};
 
 #[tokio::main]
 async fn main() {
-    let app = create_router()
+    let state = match AppState::from_env() {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    warmup::spawn(state.clone());
+    watcher::spawn(state.clone());
+    wql::spawn_reaper(&state);
+
+    let app = create_router(state)
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -17,7 +32,7 @@ async fn main() {
     println!("Server running on http://{}", addr);
 
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
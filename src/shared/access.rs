@@ -0,0 +1,514 @@
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, Path, State},
+    http::{Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, RwLock};
+use super::admin::constant_time_eq;
+use super::error::NexusError;
+use super::state::AppState;
+use super::tenants::Tenant;
+
+/// Header carrying a nexus API key. Separate from `Authorization`, which may
+/// already hold a nexus session.
+pub const API_KEY_HEADER: &str = "x-nexus-api-key";
+
+/// Every issued key starts with this, so a leaked one is easy to recognise.
+pub const API_KEY_PREFIX: &str = "nxk_";
+
+/// Path parameters that name a Wazuh group and are checked against the
+/// caller's group scope here; agent ids and listings are checked by
+/// `tenants::enforce`.
+const GROUP_PARAMS: [&str; 2] = ["group_id", "group"];
+
+/// Matches every module or every group in a role.
+const WILDCARD: &str = "*";
+
+/// API keys are required on every route except `/health` and `/admin` (which
+/// has its own token) once a key store is configured.
+#[derive(Debug, Clone, Default)]
+pub struct AccessConfig {
+    /// JSON file holding roles and hashed keys.
+    pub key_file: Option<PathBuf>,
+}
+
+impl AccessConfig {
+    /// Reads `NEXUS_API_KEYS_FILE`.
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            key_file: env::var("NEXUS_API_KEYS_FILE").ok().filter(|p| !p.is_empty()).map(PathBuf::from),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    /// Also grants `Read`.
+    Write,
+}
+
+/// What a key holding this role may call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Role {
+    /// Feature module name (`agents`, `syscollector`, `wql`, ...) or `*`.
+    #[serde(default)]
+    pub modules: BTreeMap<String, Permission>,
+    /// Wazuh groups whose agents and group routes the role may address; `*` for all.
+    #[serde(default = "all_groups")]
+    pub groups: Vec<String>,
+}
+
+fn all_groups() -> Vec<String> {
+    vec![WILDCARD.to_string()]
+}
+
+/// A key as persisted: only the SHA-256 of the secret is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredKey {
+    pub id: String,
+    pub key_sha256: String,
+    pub roles: Vec<String>,
+    /// Confines the key to the agents of this tenant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// A key as shown by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeySummary {
+    pub id: String,
+    pub roles: Vec<String>,
+    pub tenant: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct KeyFile {
+    #[serde(default)]
+    roles: BTreeMap<String, Role>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tenants: BTreeMap<String, Tenant>,
+    #[serde(default)]
+    keys: Vec<StoredKey>,
+}
+
+/// The caller behind a valid API key, with the union of its roles. Inserted
+/// into the request extensions by [`authenticate`].
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub key_id: String,
+    pub modules: BTreeMap<String, Permission>,
+    /// `None` when any of the roles covers every group.
+    pub groups: Option<BTreeSet<String>>,
+    pub tenant: Option<Arc<Tenant>>,
+}
+
+impl Principal {
+    pub fn allows(&self, module: &str, needed: Permission) -> bool {
+        [module, WILDCARD]
+            .iter()
+            .filter_map(|name| self.modules.get(*name))
+            .any(|granted| *granted >= needed)
+    }
+
+    /// The agent groups the key is confined to: its tenant's, its roles', or
+    /// those both cover. `None` when neither limits it.
+    pub fn scope(&self) -> Option<Arc<Tenant>> {
+        match (&self.tenant, &self.groups) {
+            (tenant, None) => tenant.clone(),
+            (None, Some(groups)) => Some(Arc::new(Tenant { name: self.key_id.clone(), groups: groups.clone() })),
+            (Some(tenant), Some(groups)) => Some(Arc::new(Tenant {
+                name: tenant.name.clone(),
+                groups: tenant.groups.intersection(groups).cloned().collect(),
+            })),
+        }
+    }
+
+    /// Whether both the roles and the tenant, if any, cover `group`.
+    pub fn allows_group(&self, group: &str) -> bool {
+        self.groups.as_ref().is_none_or(|groups| groups.contains(group))
+            && self.tenant.as_ref().is_none_or(|tenant| tenant.owns(group))
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyStoreError {
+    /// The request to issue or revoke a key is not acceptable.
+    Invalid(String),
+    Conflict(String),
+    /// The key file could not be written.
+    Io(String),
+}
+
+impl KeyStoreError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            KeyStoreError::Invalid(_) => StatusCode::BAD_REQUEST,
+            KeyStoreError::Conflict(_) => StatusCode::CONFLICT,
+            KeyStoreError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyStoreError::Invalid(message) | KeyStoreError::Conflict(message) => write!(f, "{}", message),
+            KeyStoreError::Io(message) => write!(f, "Failed to save key store: {}", message),
+        }
+    }
+}
+
+/// Roles and API keys backed by a local JSON file. Roles are edited in the
+/// file; keys are issued and revoked through `/admin/keys` and written back.
+pub struct KeyStore {
+    path: PathBuf,
+    file: RwLock<KeyFile>,
+}
+
+impl KeyStore {
+    /// Loads `path`; a missing file is an empty store that is created on the
+    /// first issued key.
+    pub fn open(path: &FsPath) -> Result<Self, String> {
+        let mut file = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str::<KeyFile>(&contents)
+                .map_err(|e| format!("Invalid key store {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => KeyFile::default(),
+            Err(e) => return Err(format!("Failed to read key store {}: {}", path.display(), e)),
+        };
+
+        for (name, tenant) in file.tenants.iter_mut() {
+            tenant.name = name.clone();
+        }
+
+        let roles = &file.roles;
+        let tenants = &file.tenants;
+        let mut ids = BTreeSet::new();
+        for key in file.keys.iter_mut() {
+            key.key_sha256.make_ascii_lowercase();
+            if !ids.insert(key.id.as_str()) {
+                return Err(format!("Key store {}: duplicate key id '{}'", path.display(), key.id));
+            }
+            if key.key_sha256.len() != 64 || !key.key_sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(format!("Key store {}: key '{}' has an invalid key_sha256", path.display(), key.id));
+            }
+            if let Some(role) = key.roles.iter().find(|r| !roles.contains_key(*r)) {
+                return Err(format!("Key store {}: key '{}' uses unknown role '{}'", path.display(), key.id, role));
+            }
+            if let Some(tenant) = key.tenant.as_ref().filter(|t| !tenants.contains_key(*t)) {
+                return Err(format!("Key store {}: key '{}' uses unknown tenant '{}'", path.display(), key.id, tenant));
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: RwLock::new(file),
+        })
+    }
+
+    /// Resolves a presented secret to its principal.
+    pub fn authenticate(&self, secret: &str) -> Option<Principal> {
+        let digest = hash_key(secret);
+        let file = self.file.read().unwrap();
+        let key = file.keys.iter().find(|k| constant_time_eq(k.key_sha256.as_bytes(), digest.as_bytes()))?;
+
+        let mut modules = BTreeMap::new();
+        let mut groups = Some(BTreeSet::new());
+        for role in key.roles.iter().filter_map(|name| file.roles.get(name)) {
+            for (module, permission) in &role.modules {
+                let granted = modules.entry(module.clone()).or_insert(*permission);
+                *granted = (*granted).max(*permission);
+            }
+            if role.groups.iter().any(|g| g == WILDCARD) {
+                groups = None;
+            } else if let Some(groups) = groups.as_mut() {
+                groups.extend(role.groups.iter().cloned());
+            }
+        }
+
+        Some(Principal {
+            key_id: key.id.clone(),
+            modules,
+            groups,
+            tenant: key.tenant.as_ref().and_then(|t| file.tenants.get(t)).cloned().map(Arc::new),
+        })
+    }
+
+    /// Creates a key with `roles`, optionally confined to `tenant`, and returns
+    /// its secret, which is not stored and cannot be shown again.
+    pub fn issue(&self, id: &str, roles: Vec<String>, tenant: Option<String>) -> Result<String, KeyStoreError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(KeyStoreError::Invalid("Key id must be non-empty and use only letters, digits, '-' and '_'".to_string()));
+        }
+
+        let mut file = self.file.write().unwrap();
+        if let Some(role) = roles.iter().find(|r| !file.roles.contains_key(*r)) {
+            return Err(KeyStoreError::Invalid(format!("Unknown role '{}'", role)));
+        }
+        if let Some(tenant) = tenant.as_ref().filter(|t| !file.tenants.contains_key(*t)) {
+            return Err(KeyStoreError::Invalid(format!("Unknown tenant '{}'", tenant)));
+        }
+        if file.keys.iter().any(|k| k.id == id) {
+            return Err(KeyStoreError::Conflict(format!("Key '{}' already exists", id)));
+        }
+
+        let secret = format!("{}{}", API_KEY_PREFIX, URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>()));
+        let mut updated = file.clone();
+        updated.keys.push(StoredKey {
+            id: id.to_string(),
+            key_sha256: hash_key(&secret),
+            roles,
+            tenant,
+            created_at: Some(Utc::now()),
+        });
+        self.save(&updated)?;
+        *file = updated;
+        Ok(secret)
+    }
+
+    /// Removes the key; `false` when no key has this id.
+    pub fn revoke(&self, id: &str) -> Result<bool, KeyStoreError> {
+        let mut file = self.file.write().unwrap();
+        if !file.keys.iter().any(|k| k.id == id) {
+            return Ok(false);
+        }
+
+        let mut updated = file.clone();
+        updated.keys.retain(|k| k.id != id);
+        self.save(&updated)?;
+        *file = updated;
+        Ok(true)
+    }
+
+    pub fn keys(&self) -> Vec<KeySummary> {
+        self.file.read().unwrap()
+            .keys
+            .iter()
+            .map(|k| KeySummary {
+                id: k.id.clone(),
+                roles: k.roles.clone(),
+                tenant: k.tenant.clone(),
+                created_at: k.created_at,
+            })
+            .collect()
+    }
+
+    pub fn roles(&self) -> BTreeMap<String, Role> {
+        self.file.read().unwrap().roles.clone()
+    }
+
+    pub fn tenants(&self) -> BTreeMap<String, Tenant> {
+        self.file.read().unwrap().tenants.clone()
+    }
+
+    // Writes to a temporary file next to the store and renames it over, so a
+    // crash never leaves a truncated key file behind.
+    fn save(&self, file: &KeyFile) -> Result<(), KeyStoreError> {
+        let io = |e: std::io::Error| KeyStoreError::Io(e.to_string());
+        let dir = self.path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(FsPath::new("."));
+        let contents = serde_json::to_vec_pretty(file).map_err(|e| KeyStoreError::Io(e.to_string()))?;
+
+        let mut tmp = tempfile::NamedTempFile::new_in(dir).map_err(io)?;
+        tmp.write_all(&contents).map_err(io)?;
+        tmp.persist(&self.path).map_err(|e| io(e.error))?;
+        Ok(())
+    }
+}
+
+fn hash_key(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Router-wide middleware: when a key store is configured, every request
+/// outside `/health` and `/admin` must carry a valid `X-Nexus-Api-Key`.
+pub async fn authenticate(State(state): State<AppState>, mut req: Request<Body>, next: Next<Body>) -> Response {
+    let Some(store) = state.access.as_ref() else {
+        return next.run(req).await;
+    };
+    if is_exempt(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let principal = req.headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|key| store.authenticate(key.trim()));
+
+    match principal {
+        Some(principal) => {
+            req.extensions_mut().insert(Arc::new(principal));
+            next.run(req).await
+        }
+        None => NexusError::Unauthorized("Missing or invalid API key".to_string()).into_response(),
+    }
+}
+
+fn is_exempt(path: &str) -> bool {
+    path == "/health" || path == "/admin" || path.starts_with("/admin/")
+}
+
+/// Requires read access to `module` on every route of `router`, and access to
+/// the group named by a `:group_id` or `:group` path parameter (by role and by
+/// tenant).
+///
+/// Lets everything through when no [`Principal`] was attached, i.e. API keys
+/// are disabled or the route is exempt.
+pub fn guard(module: &'static str, router: Router<AppState>) -> Router<AppState> {
+    router.route_layer(middleware::from_fn(move |req: Request<Body>, next: Next<Body>| {
+        authorize(module, Permission::Read, req, next)
+    }))
+}
+
+/// Like [`guard`], but requires write access. Feature modules wrap the routes
+/// that change state on the manager in it.
+pub fn guard_writes(module: &'static str, router: Router<AppState>) -> Router<AppState> {
+    router.route_layer(middleware::from_fn(move |req: Request<Body>, next: Next<Body>| {
+        authorize(module, Permission::Write, req, next)
+    }))
+}
+
+async fn authorize(module: &'static str, needed: Permission, req: Request<Body>, next: Next<Body>) -> Response {
+    let Some(principal) = req.extensions().get::<Arc<Principal>>().cloned() else {
+        return next.run(req).await;
+    };
+    if !principal.allows(module, needed) {
+        return NexusError::Forbidden(format!("Key '{}' has no access to '{}'", principal.key_id, module)).into_response();
+    }
+
+    let (mut parts, body) = req.into_parts();
+    if let Ok(Path(params)) = Path::<HashMap<String, String>>::from_request_parts(&mut parts, &()).await {
+        let denied = GROUP_PARAMS.iter().filter_map(|name| params.get(*name)).find(|g| !principal.allows_group(g));
+        if let Some(group) = denied {
+            return NexusError::Forbidden(format!("Key '{}' has no access to group '{}'", principal.key_id, group)).into_response();
+        }
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_with(roles: serde_json::Value, keys: &[(&str, &str, &[&str])]) -> KeyStore {
+        let keys: Vec<StoredKey> = keys
+            .iter()
+            .map(|(id, secret, roles)| StoredKey {
+                id: id.to_string(),
+                key_sha256: hash_key(secret),
+                roles: roles.iter().map(|r| r.to_string()).collect(),
+                tenant: None,
+                created_at: None,
+            })
+            .collect();
+        KeyStore {
+            path: PathBuf::from("unused.json"),
+            file: RwLock::new(KeyFile {
+                roles: serde_json::from_value(roles).unwrap(),
+                tenants: BTreeMap::new(),
+                keys,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_roles_are_merged() {
+        let store = store_with(
+            serde_json::json!({
+                "inventory": { "modules": { "syscollector": "read", "agents": "read" }, "groups": ["web"] },
+                "ops": { "modules": { "agents": "write" }, "groups": ["db"] }
+            }),
+            &[("both", "nxk_both", &["inventory", "ops"])],
+        );
+
+        let principal = store.authenticate("nxk_both").unwrap();
+        assert!(principal.allows("agents", Permission::Write));
+        assert!(principal.allows("syscollector", Permission::Read));
+        assert!(!principal.allows("syscollector", Permission::Write));
+        assert!(!principal.allows("wql", Permission::Read));
+        assert!(principal.allows_group("web") && principal.allows_group("db"));
+        assert!(!principal.allows_group("finance"));
+        assert!(store.authenticate("nxk_other").is_none());
+    }
+
+    #[test]
+    fn test_scope_combines_roles_and_tenant() {
+        let store = store_with(
+            serde_json::json!({ "inventory": { "modules": { "agents": "read" }, "groups": ["web", "db"] } }),
+            &[("web", "nxk_web", &["inventory"])],
+        );
+        let mut principal = store.authenticate("nxk_web").unwrap();
+        assert_eq!(principal.scope().unwrap().groups, BTreeSet::from(["db".to_string(), "web".to_string()]));
+
+        principal.tenant = Some(Arc::new(Tenant { name: "acme".to_string(), groups: BTreeSet::from(["web".to_string(), "acme".to_string()]) }));
+        let scope = principal.scope().unwrap();
+        assert_eq!(scope.name, "acme");
+        assert_eq!(scope.groups, BTreeSet::from(["web".to_string()]));
+
+        principal.groups = None;
+        assert_eq!(principal.scope().unwrap().groups.len(), 2, "Only the tenant limits it");
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let store = store_with(
+            serde_json::json!({ "auditor": { "modules": { "*": "read" } } }),
+            &[("auditor", "nxk_auditor", &["auditor"])],
+        );
+
+        let principal = store.authenticate("nxk_auditor").unwrap();
+        assert!(principal.allows("wql", Permission::Read));
+        assert!(!principal.allows("wql", Permission::Write));
+        assert!(principal.allows_group("anything"));
+    }
+
+    #[test]
+    fn test_open_rejects_unknown_role() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let key = serde_json::json!({ "id": "k", "key_sha256": hash_key("nxk_k"), "roles": ["missing"] });
+        fs::write(&path, serde_json::json!({ "roles": {}, "keys": [key] }).to_string()).unwrap();
+
+        let error = KeyStore::open(&path).err().unwrap();
+        assert!(error.contains("unknown role 'missing'"), "{}", error);
+    }
+
+    #[test]
+    fn test_issue_and_revoke_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        fs::write(&path, serde_json::json!({ "roles": { "reader": { "modules": { "rules": "read" } } } }).to_string()).unwrap();
+
+        let store = KeyStore::open(&path).unwrap();
+        let secret = store.issue("grafana", vec!["reader".to_string()], None).unwrap();
+        assert!(secret.starts_with(API_KEY_PREFIX));
+        assert!(matches!(store.issue("grafana", vec![], None), Err(KeyStoreError::Conflict(_))));
+        assert!(matches!(store.issue("other", vec!["admin".to_string()], None), Err(KeyStoreError::Invalid(_))));
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains(&secret), "Only the hash is written");
+        let reopened = KeyStore::open(&path).unwrap();
+        assert_eq!(reopened.authenticate(&secret).unwrap().key_id, "grafana");
+
+        assert!(reopened.revoke("grafana").unwrap());
+        assert!(!reopened.revoke("grafana").unwrap());
+        assert!(KeyStore::open(&path).unwrap().authenticate(&secret).is_none());
+    }
+}
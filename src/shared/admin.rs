@@ -0,0 +1,48 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use super::state::AppState;
+
+pub const ADMIN_TOKEN_HEADER: &str = "x-nexus-admin-token";
+
+/// Extractor that only succeeds when the request carries the configured admin
+/// credential. Put it first in the argument list of every `/admin` handler.
+pub struct AdminAccess;
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminAccess {
+    type Rejection = (StatusCode, Json<Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let expected = state.config.admin_token.as_deref().ok_or_else(|| (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Admin API is disabled: NEXUS_ADMIN_TOKEN is not set" })),
+        ))?;
+
+        let provided = parts.headers
+            .get(ADMIN_TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+
+        if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+            Ok(AdminAccess)
+        } else {
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "error": "Missing or invalid admin token" })),
+            ))
+        }
+    }
+}
+
+/// Compares two byte strings without short-circuiting on the first difference.
+/// Both sides are hashed first so the comparison does not reveal their lengths.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use crate::client::WazuhClient;
+use super::common::WazuhRequest;
+use super::error::NexusError;
+
+/// Where state-changing calls are recorded.
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    /// Every record is appended to this file as one JSON line.
+    pub file: Option<PathBuf>,
+    /// How many recent records `GET /admin/audit` can return.
+    pub retain: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            retain: 1000,
+        }
+    }
+}
+
+impl AuditConfig {
+    /// Reads `NEXUS_AUDIT_LOG` (path) and `NEXUS_AUDIT_RETAIN` (records kept in memory).
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self {
+            file: env::var("NEXUS_AUDIT_LOG").ok().filter(|p| !p.is_empty()).map(PathBuf::from),
+            ..Self::default()
+        };
+        if let Ok(value) = env::var("NEXUS_AUDIT_RETAIN") {
+            config.retain = value
+                .parse()
+                .map_err(|_| format!("NEXUS_AUDIT_RETAIN must be a number, got '{}'", value))?;
+        }
+        Ok(config)
+    }
+}
+
+/// One mutation (or dry run of one) as seen by nexus.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub at: DateTime<Utc>,
+    /// `key:<id>` for API keys, `session:<user>` for nexus sessions, otherwise
+    /// `token:<fingerprint>` of the Wazuh JWT.
+    pub actor: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub upstream: Option<String>,
+    /// Method and Wazuh path template, e.g. `PUT agents/restart`.
+    pub action: String,
+    /// Agent ids the call was sent for (or would have been, for a dry run).
+    pub agents: Vec<String>,
+    pub dry_run: bool,
+    /// HTTP status given to the caller.
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(request: &WazuhRequest, action: &str, agents: Vec<String>, outcome: Result<(), &NexusError>) -> Self {
+        let actor = match (&request.key_id, &request.session) {
+            (Some(key_id), _) => format!("key:{}", key_id),
+            (None, Some(session)) => format!("session:{}", session.username),
+            (None, None) => format!("token:{}", WazuhClient::token_fingerprint(&request.token)),
+        };
+        Self {
+            at: Utc::now(),
+            actor,
+            tenant: request.tenant.as_ref().map(|t| t.name.clone()),
+            upstream: request.upstream.clone(),
+            action: action.to_string(),
+            agents,
+            dry_run: request.dry_run,
+            status: outcome.err().map(|e| e.status_code().as_u16()).unwrap_or(200),
+            error: outcome.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Append-only record of every mutation, kept in memory and optionally on disk.
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+    recent: Mutex<VecDeque<AuditRecord>>,
+    retain: usize,
+}
+
+impl AuditLog {
+    /// A log that only keeps the last `retain` records in memory.
+    pub fn new(retain: usize) -> Self {
+        Self {
+            file: None,
+            recent: Mutex::new(VecDeque::new()),
+            retain,
+        }
+    }
+
+    pub fn open(config: &AuditConfig) -> Result<Self, String> {
+        let mut log = Self::new(config.retain);
+        if let Some(path) = &config.file {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Cannot open audit log {}: {}", path.display(), e))?;
+            log.file = Some(Mutex::new(file));
+        }
+        Ok(log)
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        let dry_run = if record.dry_run { " (dry run)" } else { "" };
+        println!("Audit: {}{} by {} on {:?} -> {}", record.action, dry_run, record.actor, record.agents, record.status);
+
+        if let Some(file) = &self.file {
+            let line = serde_json::to_string(&record).unwrap_or_default();
+            if let Err(e) = writeln!(file.lock().unwrap(), "{}", line) {
+                println!("Warning: failed to write audit record: {}", e);
+            }
+        }
+
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back(record);
+        while recent.len() > self.retain {
+            recent.pop_front();
+        }
+    }
+
+    /// Up to `limit` records, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<AuditRecord> {
+        self.recent.lock().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+}
@@ -1,38 +1,202 @@
 use axum::Json;
+use axum::response::{IntoResponse, Response};
+use reqwest::Method;
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use crate::client::WazuhClient;
+use super::error::NexusError;
+use super::pagination::Paginator;
+use super::path_params::{encode_segment, validate_segment};
+use super::query::append_query;
+use super::sessions::{with_reauth, Session};
+use super::state::AppState;
+use super::tenants::{self, Tenant};
 
-#[derive(Debug, Deserialize)]
+/// What every proxying handler returns: Wazuh's JSON (or an NDJSON stream) on
+/// success, otherwise an error that renders with the matching HTTP status.
+pub type ProxyResult = Result<Response, NexusError>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    /// One `affected_items` entry per line; only valid together with `all_pages`.
+    Ndjson,
+    /// Comma separated values; only offered by routes with a fixed record shape
+    /// such as `/inventory`.
+    Csv,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct WazuhRequest {
-    pub endpoint: String,
+    /// Name of the configured upstream manager; the default one when omitted.
+    #[serde(default)]
+    pub upstream: Option<String>,
+    /// Base URL of the manager. Only accepted when it is exactly one of the
+    /// configured upstreams; kept for clients that predate `upstream`.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Wazuh JWT from `/auth`. Left empty when the caller uses a nexus session.
+    #[serde(default)]
     pub token: String,
+    /// Set by [`WazuhCall`](super::path_params::WazuhCall) when the caller sent a
+    /// nexus session; `token` then holds the session's current JWT.
+    #[serde(skip)]
+    pub session: Option<Arc<Session>>,
+    /// Set by [`WazuhCall`](super::path_params::WazuhCall) from the caller's API
+    /// key; recorded in the audit log.
+    #[serde(skip)]
+    pub tenant: Option<Arc<Tenant>>,
+    /// Set by [`WazuhCall`](super::path_params::WazuhCall) from the caller's API
+    /// key: the agent groups its tenant and roles confine the call to.
+    #[serde(skip)]
+    pub scope: Option<Arc<Tenant>>,
+    /// Id of the caller's API key, recorded in the audit log.
+    #[serde(skip)]
+    pub key_id: Option<String>,
+    /// Values substituted into the `{placeholders}` of the Wazuh path.
     #[serde(default)]
     pub params: std::collections::HashMap<String, String>,
+    /// Wazuh query parameters such as `limit`, `offset`, `sort`, `search`,
+    /// `select` and `q`, validated per endpoint and appended to the URL.
+    #[serde(default)]
+    pub query: BTreeMap<String, Value>,
+    /// Follow `offset`/`limit` until every `affected_items` entry is fetched.
+    #[serde(default)]
+    pub all_pages: bool,
+    #[serde(default)]
+    pub format: ResponseFormat,
+    /// Write routes only: report what would change instead of changing it.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Write routes only: JSON body forwarded to Wazuh, e.g. `name` and `ip`
+    /// when enrolling an agent.
+    #[serde(default)]
+    pub body: Option<Value>,
 }
 
-pub async fn handle_wazuh_request(request: WazuhRequest, url_path: &str, handler: impl FnOnce(String) -> String) -> Json<serde_json::Value> {
-    let client = WazuhClient::new();
-    
-    // Replace URL parameters with actual values
-    let mut final_path = url_path.to_string();
-    for (key, value) in request.params.iter() {
-        final_path = final_path.replace(&format!("{{{}}}", key), value);
+pub async fn handle_wazuh_request(state: &AppState, mut request: WazuhRequest, url_path: &str, handler: impl FnOnce(String) -> String) -> ProxyResult {
+    tenants::enforce(state, &mut request, url_path).await?;
+
+    if request.format == ResponseFormat::Ndjson && !request.all_pages {
+        return Err(NexusError::BadParameter("format 'ndjson' requires all_pages".to_string()));
     }
-    
-    let url = handler(format!("{}/{}", request.endpoint, final_path));
+    if request.format == ResponseFormat::Csv {
+        return Err(NexusError::BadParameter(format!("format 'csv' is not supported by /{}", url_path)));
+    }
+
+    if request.all_pages {
+        let base_url = handler(resolve_url(state, &request, url_path)?);
+        println!("Proxying all pages of: {}", base_url);
+
+        let scope = tenants::listing_scope(&request, url_path);
+        let paginator = Paginator::new(state, base_url, url_path, request.query, request.token, request.session)?.scoped_to(scope);
+        return match request.format {
+            ResponseFormat::Ndjson => paginator.into_ndjson().await,
+            ResponseFormat::Json | ResponseFormat::Csv => paginator.collect().await.map(|data| Json(data).into_response()),
+        };
+    }
+
+    let scope = tenants::listing_scope(&request, url_path);
+    let mut data = fetch_wazuh_json(state, request, url_path, handler).await?;
+    if let Some(tenant) = scope {
+        tenants::retain_in_envelope(&tenant, url_path, &mut data);
+    }
+    Ok(Json(data).into_response())
+}
+
+/// Proxies a single call and returns Wazuh's JSON, for callers inside nexus.
+pub async fn fetch_wazuh_json(state: &AppState, request: WazuhRequest, url_path: &str, handler: impl FnOnce(String) -> String) -> Result<Value, NexusError> {
+    let url = append_query(&handler(resolve_url(state, &request, url_path)?), url_path, &request.query)?;
     
     println!("Proxying request to: {}", url);
     
-    match client.get_cached(&url, Some(&request.token)).await {
+    let result = with_reauth(&state.client, request.session.as_ref(), &request.token, |token| {
+        let url = &url;
+        async move { state.client.get_cached(url, Some(&token)).await }
+    })
+    .await;
+
+    match result {
         Ok(data) => {
             println!("Received response from Wazuh for {}", url);
-            Json(data)
+            Ok(data)
         },
         Err(e) => {
             println!("Error from Wazuh for {}: {}", url, e);
-            Json(serde_json::json!({
-                "error": e
-            }))
+            Err(e)
         },
     }
 }
+
+/// Every `affected_items` entry of a list endpoint, following pages, with the
+/// caller's tenant applied like on the proxied route. `cached: false` always
+/// asks Wazuh, for callers that act on the answer.
+pub async fn collect_wazuh_items(state: &AppState, mut request: WazuhRequest, url_path: &str, cached: bool) -> Result<Vec<Value>, NexusError> {
+    tenants::enforce(state, &mut request, url_path).await?;
+    let base_url = resolve_url(state, &request, url_path)?;
+    let scope = tenants::listing_scope(&request, url_path);
+    let mut paginator = Paginator::new(state, base_url, url_path, request.query, request.token, request.session)?.scoped_to(scope);
+    if !cached {
+        paginator = paginator.bypass_cache();
+    }
+    let data = paginator.collect().await?;
+
+    Ok(data["data"]["affected_items"].as_array().cloned().unwrap_or_default())
+}
+
+/// Sends a state-changing call to Wazuh. `query` is validated against
+/// `"{METHOD} {url_path}"`; the answer is never cached and, once the call may
+/// have reached the manager, never retried.
+pub async fn send_wazuh_write(
+    state: &AppState,
+    request: &WazuhRequest,
+    method: Method,
+    url_path: &str,
+    query: &BTreeMap<String, Value>,
+    body: Option<&Value>,
+) -> Result<Value, NexusError> {
+    let url = append_query(&resolve_url(state, request, url_path)?, &format!("{} {}", method, url_path), query)?;
+
+    println!("Sending {} to: {}", method, url);
+
+    with_reauth(&state.client, request.session.as_ref(), &request.token, |token| {
+        let (method, url) = (method.clone(), &url);
+        async move {
+            let response = state.client.send_write(method, url, &token, body).await?;
+            WazuhClient::json_result(response).await
+        }
+    })
+    .await
+}
+
+/// Builds the upstream URL (without query) on the selected manager by filling
+/// the path template.
+fn resolve_url(state: &AppState, request: &WazuhRequest, url_path: &str) -> Result<String, NexusError> {
+    let upstream = state.config.upstreams.resolve(request.upstream.as_deref(), request.endpoint.as_deref())?;
+
+    // Replace URL parameters with actual values
+    let mut final_path = url_path.to_string();
+    for (key, value) in request.params.iter() {
+        let placeholder = format!("{{{}}}", key);
+        if final_path.contains(&placeholder) {
+            validate_segment(key, value)?;
+            final_path = final_path.replace(&placeholder, &encode_segment(value));
+        }
+    }
+
+    if let Some(missing) = unresolved_placeholder(&final_path) {
+        return Err(NexusError::BadParameter(format!("Missing path parameter '{}'", missing)));
+    }
+
+    Ok(format!("{}/{}", upstream.url, final_path))
+}
+
+fn unresolved_placeholder(path: &str) -> Option<&str> {
+    let start = path.find('{')?;
+    let end = start + path[start..].find('}')?;
+    Some(&path[start + 1..end])
+}
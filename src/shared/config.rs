@@ -0,0 +1,89 @@
+use dotenv::dotenv;
+use std::env;
+use crate::client::{BreakerConfig, CacheConfig, RetryConfig};
+use super::access::AccessConfig;
+use super::audit::AuditConfig;
+use super::pagination::PaginationConfig;
+use super::rate_limit::RateLimitConfig;
+use super::sessions::SessionConfig;
+use super::upstreams::UpstreamRegistry;
+use super::warmup::WarmupConfig;
+use super::watcher::WatcherConfig;
+use super::wql::WqlConfig;
+
+/// Process-wide settings read once at startup and shared through `AppState`.
+#[derive(Debug, Clone, Default)]
+pub struct AppConfig {
+    /// The Wazuh managers requests may be sent to.
+    pub upstreams: UpstreamRegistry,
+    /// Service account used by background jobs such as WQL report generation.
+    pub wazuh_username: Option<String>,
+    pub wazuh_password: Option<String>,
+    pub cache: CacheConfig,
+    /// Credential for the `/admin` routes, sent as `X-Nexus-Admin-Token`.
+    /// The admin API is disabled when unset.
+    pub admin_token: Option<String>,
+    pub warmup: WarmupConfig,
+    pub pagination: PaginationConfig,
+    pub retry: RetryConfig,
+    pub breaker: BreakerConfig,
+    pub sessions: SessionConfig,
+    pub access: AccessConfig,
+    pub rate_limits: RateLimitConfig,
+    pub audit: AuditConfig,
+    /// Agents whose syscollector data `/inventory` fetches at the same time;
+    /// a default applies when unset.
+    pub inventory_concurrency: Option<usize>,
+    pub watcher: WatcherConfig,
+    /// Gateway answering WQL report queries; `/wql` reports fail when unset.
+    pub wql: Option<WqlConfig>,
+}
+
+impl AppConfig {
+    pub fn from_env() -> Result<Self, String> {
+        dotenv().ok();
+
+        Ok(Self {
+            upstreams: UpstreamRegistry::from_env()?,
+            wazuh_username: env::var("WAZUH_USERNAME").ok(),
+            wazuh_password: env::var("WAZUH_PASSWORD").ok(),
+            cache: CacheConfig::from_env()?,
+            admin_token: env::var("NEXUS_ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            warmup: WarmupConfig::from_env()?,
+            pagination: PaginationConfig::from_env()?,
+            retry: RetryConfig::from_env()?,
+            breaker: BreakerConfig::from_env()?,
+            sessions: SessionConfig::from_env()?,
+            access: AccessConfig::from_env()?,
+            rate_limits: RateLimitConfig::from_env()?,
+            audit: AuditConfig::from_env()?,
+            inventory_concurrency: positive_env("NEXUS_INVENTORY_CONCURRENCY")?,
+            watcher: WatcherConfig::from_env()?,
+            wql: WqlConfig::from_env()?,
+        })
+    }
+
+    /// Returns the service account as `(url, username, password)` on the default
+    /// upstream, or a message naming the first missing setting.
+    pub fn service_account(&self) -> Result<(&str, &str, &str), String> {
+        let url = self.upstreams.default_upstream()
+            .map(|u| u.url.as_str())
+            .ok_or_else(|| "WAZUH_URL or NEXUS_UPSTREAMS must be set in .env file".to_string())?;
+        let username = self.wazuh_username.as_deref()
+            .ok_or_else(|| "WAZUH_USERNAME must be set in .env file".to_string())?;
+        let password = self.wazuh_password.as_deref()
+            .ok_or_else(|| "WAZUH_PASSWORD must be set in .env file".to_string())?;
+        Ok((url, username, password))
+    }
+}
+
+/// Reads a positive integer from `name`, if set.
+fn positive_env(name: &str) -> Result<Option<usize>, String> {
+    match env::var(name) {
+        Ok(value) => match value.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Some(n)),
+            _ => Err(format!("{} must be a positive number, got '{}'", name, value)),
+        },
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,124 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Everything that can go wrong while proxying a call to Wazuh.
+///
+/// `Clone` because one upstream result may be handed to several coalesced callers.
+#[derive(Debug, Clone)]
+pub enum NexusError {
+    /// Wazuh answered with a non-2xx status; `body` is its payload (JSON when possible).
+    Upstream { status: u16, body: Value },
+    /// The request never got a response: connect failure, TLS error, timeout.
+    Transport { message: String, timeout: bool },
+    /// Wazuh answered 2xx but the body was not valid JSON.
+    Parse(String),
+    /// Paging through a list endpoint gave inconsistent results.
+    Pagination(String),
+    /// The caller sent something we refuse to forward.
+    BadParameter(String),
+    /// The request named a manager or URL that is not in the upstream registry.
+    UnknownUpstream(String),
+    /// The caller is not authenticated towards nexus (missing token or session).
+    Unauthorized(String),
+    /// The caller's API key does not grant access to this module or group.
+    Forbidden(String),
+    /// The caller exceeded a quota or is locked out after failed logins.
+    RateLimited { message: String, retry_after_secs: u64 },
+    /// The manager failed repeatedly and its circuit breaker is failing fast.
+    CircuitOpen { upstream: String, retry_in_secs: u64 },
+}
+
+impl NexusError {
+    pub fn transport(error: reqwest::Error) -> Self {
+        NexusError::Transport {
+            timeout: error.is_timeout(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            NexusError::Upstream { status, .. } => {
+                StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY)
+            }
+            NexusError::Transport { timeout: true, .. } => StatusCode::GATEWAY_TIMEOUT,
+            NexusError::Transport { .. } | NexusError::Parse(_) | NexusError::Pagination(_) => StatusCode::BAD_GATEWAY,
+            NexusError::BadParameter(_) | NexusError::UnknownUpstream(_) => StatusCode::BAD_REQUEST,
+            NexusError::CircuitOpen { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            NexusError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            NexusError::Forbidden(_) => StatusCode::FORBIDDEN,
+            NexusError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            NexusError::Upstream { .. } => "upstream",
+            NexusError::Transport { .. } => "transport",
+            NexusError::Parse(_) => "parse",
+            NexusError::Pagination(_) => "pagination",
+            NexusError::BadParameter(_) => "bad_parameter",
+            NexusError::UnknownUpstream(_) => "unknown_upstream",
+            NexusError::CircuitOpen { .. } => "circuit_open",
+            NexusError::Unauthorized(_) => "unauthorized",
+            NexusError::Forbidden(_) => "forbidden",
+            NexusError::RateLimited { .. } => "rate_limited",
+        }
+    }
+}
+
+impl fmt::Display for NexusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NexusError::Upstream { status, body } => {
+                // Wazuh errors carry a human readable `title`/`detail`
+                let detail = body.get("detail")
+                    .or_else(|| body.get("title"))
+                    .and_then(|d| d.as_str())
+                    .unwrap_or("no detail");
+                write!(f, "Wazuh returned {}: {}", status, detail)
+            }
+            NexusError::Transport { message, .. } => write!(f, "Request failed: {}", message),
+            NexusError::Parse(message) => write!(f, "Failed to parse response: {}", message),
+            NexusError::Pagination(message) => write!(f, "Pagination failed: {}", message),
+            NexusError::BadParameter(message) => write!(f, "Bad parameter: {}", message),
+            NexusError::UnknownUpstream(message) => write!(f, "Upstream not allowed: {}", message),
+            NexusError::Unauthorized(message) => write!(f, "Unauthorized: {}", message),
+            NexusError::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            NexusError::RateLimited { message, retry_after_secs } => {
+                write!(f, "{}, retry in {}s", message, retry_after_secs)
+            }
+            NexusError::CircuitOpen { upstream, retry_in_secs } => write!(
+                f,
+                "Upstream '{}' is failing, requests are paused for {}s",
+                upstream, retry_in_secs
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NexusError {}
+
+impl IntoResponse for NexusError {
+    fn into_response(self) -> Response {
+        let mut body = json!({
+            "error": self.to_string(),
+            "kind": self.kind(),
+        });
+        if let NexusError::Upstream { status, body: upstream } = &self {
+            body["upstream_status"] = json!(status);
+            body["upstream"] = upstream.clone();
+        }
+
+        let mut response = (self.status_code(), Json(body)).into_response();
+        if let NexusError::RateLimited { retry_after_secs, .. } = &self {
+            response.headers_mut().insert(header::RETRY_AFTER, (*retry_after_secs).into());
+        }
+        response
+    }
+}
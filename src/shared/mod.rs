@@ -1,3 +1,23 @@
+pub mod access;
+pub mod admin;
+pub mod audit;
 pub mod common;
+pub mod config;
+pub mod error;
+pub mod pagination;
+pub mod path_params;
+pub mod query;
+pub mod rate_limit;
+pub mod sessions;
+pub mod state;
+pub mod tenants;
+pub mod upstreams;
+pub mod watcher;
+pub mod wql;
+pub mod warmup;
 
 pub use common::*;
+pub use config::AppConfig;
+pub use error::NexusError;
+pub use path_params::WazuhCall;
+pub use state::AppState;
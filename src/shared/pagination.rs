@@ -0,0 +1,259 @@
+use axum::body::{Bytes, StreamBody};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::env;
+use std::sync::Arc;
+use super::error::NexusError;
+use super::query::{append_query, is_allowed};
+use super::sessions::{with_reauth, Session};
+use super::state::AppState;
+use super::tenants::{self, Tenant};
+
+const DEFAULT_PAGE_SIZE: u64 = 500;
+const DEFAULT_MAX_ITEMS: u64 = 100_000;
+
+/// Limits for `all_pages` requests.
+#[derive(Debug, Clone)]
+pub struct PaginationConfig {
+    /// `limit` sent to Wazuh for every page.
+    pub page_size: u64,
+    /// Requests whose `total_affected_items` is larger than this are refused.
+    pub max_items: u64,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            max_items: DEFAULT_MAX_ITEMS,
+        }
+    }
+}
+
+impl PaginationConfig {
+    /// Reads `NEXUS_PAGE_SIZE` and `NEXUS_MAX_ITEMS`.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Ok(value) = env::var("NEXUS_PAGE_SIZE") {
+            config.page_size = value
+                .parse()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| format!("NEXUS_PAGE_SIZE must be a positive number, got '{}'", value))?;
+        }
+        if let Ok(value) = env::var("NEXUS_MAX_ITEMS") {
+            config.max_items = value
+                .parse()
+                .map_err(|_| format!("NEXUS_MAX_ITEMS must be a number, got '{}'", value))?;
+        }
+        Ok(config)
+    }
+}
+
+struct Page {
+    envelope: Value,
+    items: Vec<Value>,
+    total: u64,
+}
+
+impl Page {
+    fn parse(mut envelope: Value) -> Result<Self, NexusError> {
+        let total = envelope["data"]["total_affected_items"]
+            .as_u64()
+            .ok_or_else(|| NexusError::Parse("Response has no data.total_affected_items".to_string()))?;
+        let items = match envelope["data"]["affected_items"].take() {
+            Value::Array(items) => items,
+            _ => return Err(NexusError::Parse("Response has no data.affected_items".to_string())),
+        };
+        Ok(Self { envelope, items, total })
+    }
+}
+
+/// Walks a Wazuh list endpoint with `offset`/`limit` until every affected item
+/// has been fetched.
+pub struct Paginator {
+    state: AppState,
+    base_url: String,
+    template: String,
+    query: BTreeMap<String, Value>,
+    token: String,
+    session: Option<Arc<Session>>,
+    // Whether `collect` reads pages through the cache.
+    cached: bool,
+    // Group scope whose items are the only ones passed on.
+    scope: Option<Arc<Tenant>>,
+}
+
+impl Paginator {
+    pub fn new(
+        state: &AppState,
+        base_url: String,
+        template: &str,
+        query: BTreeMap<String, Value>,
+        token: String,
+        session: Option<Arc<Session>>,
+    ) -> Result<Self, NexusError> {
+        if !is_allowed(template, "offset") || !is_allowed(template, "limit") {
+            return Err(NexusError::BadParameter(format!("/{} does not support all_pages", template)));
+        }
+        if query.contains_key("offset") || query.contains_key("limit") {
+            return Err(NexusError::BadParameter(
+                "'offset' and 'limit' are managed by nexus when all_pages is set".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            state: state.clone(),
+            base_url,
+            template: template.to_string(),
+            query,
+            token,
+            session,
+            cached: true,
+            scope: None,
+        })
+    }
+
+    /// Makes `collect` always ask Wazuh, for callers that act on the result.
+    pub fn bypass_cache(mut self) -> Self {
+        self.cached = false;
+        self
+    }
+
+    /// Drops items outside `tenant` from what is returned or streamed.
+    pub fn scoped_to(mut self, tenant: Option<Arc<Tenant>>) -> Self {
+        self.scope = tenant;
+        self
+    }
+
+    async fn fetch_page(&self, offset: u64, cached: bool) -> Result<Page, NexusError> {
+        let mut query = self.query.clone();
+        query.insert("offset".to_string(), json!(offset));
+        query.insert("limit".to_string(), json!(self.state.config.pagination.page_size));
+        let url = append_query(&self.base_url, &self.template, &query)?;
+
+        let client = &self.state.client;
+        let data = with_reauth(client, self.session.as_ref(), &self.token, |token| {
+            let url = &url;
+            async move {
+                if cached {
+                    client.get_cached(url, Some(&token)).await
+                } else {
+                    client.get_json(url, Some(&token)).await
+                }
+            }
+        })
+        .await?;
+        Page::parse(data)
+    }
+
+    fn check_ceiling(&self, total: u64) -> Result<(), NexusError> {
+        let max_items = self.state.config.pagination.max_items;
+        if total > max_items {
+            return Err(NexusError::BadParameter(format!(
+                "/{} has {} items, more than the {} allowed for all_pages (NEXUS_MAX_ITEMS); narrow it with q or search",
+                self.template, total, max_items
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_total(expected: u64, received: u64) -> Result<(), NexusError> {
+        if expected != received {
+            return Err(NexusError::Pagination(format!(
+                "Wazuh reported {} items but {} were returned; the data changed while paging",
+                expected, received
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fetches every page (through the cache unless bypassed) and returns the
+    /// first page's envelope with `data.affected_items` holding all items.
+    pub async fn collect(self) -> Result<Value, NexusError> {
+        let first = self.fetch_page(0, self.cached).await?;
+        self.check_ceiling(first.total)?;
+
+        let total = first.total;
+        let mut envelope = first.envelope;
+        let mut items = first.items;
+
+        while (items.len() as u64) < total {
+            let page = self.fetch_page(items.len() as u64, self.cached).await?;
+            if page.items.is_empty() {
+                break;
+            }
+            items.extend(page.items);
+        }
+
+        Self::check_total(total, items.len() as u64)?;
+        envelope["data"]["affected_items"] = Value::Array(items);
+        if let Some(tenant) = &self.scope {
+            tenants::retain_in_envelope(tenant, &self.template, &mut envelope);
+        }
+        Ok(envelope)
+    }
+
+    /// Streams every item as one JSON line without buffering the whole set.
+    ///
+    /// The first page is fetched up front so upstream errors still get a proper
+    /// status code. Failures after that end the stream with an `{"error": ...}` line.
+    pub async fn into_ndjson(self) -> Result<Response, NexusError> {
+        let first = self.fetch_page(0, false).await?;
+        self.check_ceiling(first.total)?;
+
+        let total = first.total;
+        let state = (self, first.items, 0u64, false);
+        let lines = stream::unfold(state, move |(paginator, mut pending, sent, done)| async move {
+            if done {
+                return None;
+            }
+
+            if pending.is_empty() && sent < total {
+                match paginator.fetch_page(sent, false).await {
+                    Ok(page) => pending = page.items,
+                    Err(e) => return Some((vec![error_line(&e)], (paginator, pending, sent, true))),
+                }
+            }
+
+            if pending.is_empty() {
+                // Either everything was sent or Wazuh stopped returning items early
+                return match Self::check_total(total, sent) {
+                    Ok(()) => None,
+                    Err(e) => Some((vec![error_line(&e)], (paginator, pending, sent, true))),
+                };
+            }
+
+            let sent = sent + pending.len() as u64;
+            if let Some(tenant) = &paginator.scope {
+                tenant.retain_own(&paginator.template, &mut pending);
+            }
+            let batch: Vec<Bytes> = pending.iter().map(item_line).collect();
+            Some((batch, (paginator, Vec::new(), sent, false)))
+        })
+        .flat_map(|batch| stream::iter(batch.into_iter().map(Ok::<_, Infallible>)));
+
+        Ok(ndjson_response(lines))
+    }
+}
+
+fn item_line(item: &Value) -> Bytes {
+    let mut line = item.to_string();
+    line.push('\n');
+    Bytes::from(line)
+}
+
+fn error_line(error: &NexusError) -> Bytes {
+    item_line(&json!({ "error": error.to_string() }))
+}
+
+fn ndjson_response<S>(lines: S) -> Response
+where
+    S: Stream<Item = Result<Bytes, Infallible>> + Send + 'static,
+{
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], StreamBody::new(lines)).into_response()
+}
@@ -0,0 +1,155 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequest, FromRequestParts, Path},
+    http::Request,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use super::access::Principal;
+use super::common::WazuhRequest;
+use super::error::NexusError;
+use super::state::AppState;
+
+/// Extractor for proxying handlers: the JSON [`WazuhRequest`] body with the
+/// route's path parameters merged into `params` and the caller's nexus session
+/// and tenant, if any, attached.
+///
+/// The path is the source of truth. A body parameter with the same name is only
+/// accepted when it carries the same value; otherwise the request is rejected
+/// with 400 instead of silently picking one of them.
+pub struct WazuhCall(pub WazuhRequest);
+
+#[async_trait]
+impl FromRequest<AppState, Body> for WazuhCall {
+    type Rejection = Response;
+
+    async fn from_request(req: Request<Body>, state: &AppState) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+        let Path(path_params) = Path::<HashMap<String, String>>::from_request_parts(&mut parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let session = state.sessions.from_headers(&parts.headers).map_err(IntoResponse::into_response)?;
+        let principal = parts.extensions.get::<Arc<Principal>>().cloned();
+        let Json(mut request) = Json::<WazuhRequest>::from_request(Request::from_parts(parts, body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        merge_path_params(&mut request, path_params).map_err(IntoResponse::into_response)?;
+        request.tenant = principal.as_ref().and_then(|p| p.tenant.clone());
+        request.scope = principal.as_ref().and_then(|p| p.scope());
+        request.key_id = principal.map(|p| p.key_id.clone());
+
+        match session {
+            Some(session) => {
+                if !request.token.is_empty() {
+                    return Err(NexusError::BadParameter("Send either a Wazuh token or a nexus session, not both".to_string()).into_response());
+                }
+                if request.upstream.as_ref().is_some_and(|name| *name != session.upstream) {
+                    return Err(NexusError::BadParameter(format!("The session is bound to upstream '{}'", session.upstream)).into_response());
+                }
+                request.upstream = Some(session.upstream.clone());
+                request.token = session.token();
+                request.session = Some(session);
+            }
+            None if request.token.is_empty() => {
+                return Err(NexusError::Unauthorized("Send a Wazuh token or a nexus session".to_string()).into_response());
+            }
+            None => {}
+        }
+
+        Ok(WazuhCall(request))
+    }
+}
+
+fn merge_path_params(request: &mut WazuhRequest, path_params: HashMap<String, String>) -> Result<(), NexusError> {
+    for (name, value) in path_params {
+        match request.params.get(&name) {
+            Some(existing) if *existing != value => {
+                return Err(NexusError::BadParameter(format!(
+                    "Parameter '{}' is '{}' in the path but '{}' in the body",
+                    name, value, existing
+                )));
+            }
+            _ => {
+                request.params.insert(name, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rejects values that could change which Wazuh resource a path points at.
+pub fn validate_segment(name: &str, value: &str) -> Result<(), NexusError> {
+    let reason = if value.is_empty() {
+        Some("must not be empty")
+    } else if value.contains('/') || value.contains('\\') {
+        Some("must not contain '/' or '\\'")
+    } else if value.contains("..") {
+        Some("must not contain '..'")
+    } else if value.chars().any(char::is_control) {
+        Some("must not contain control characters")
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(NexusError::BadParameter(format!("Parameter '{}' {}", name, reason))),
+        None => Ok(()),
+    }
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters so the
+/// value stays a single path segment.
+pub fn encode_segment(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_segment_rejects_traversal() {
+        assert!(validate_segment("agent_id", "001").is_ok());
+        assert!(validate_segment("policy_id", "cis_debian10").is_ok());
+        assert!(validate_segment("agent_id", "..").is_err());
+        assert!(validate_segment("agent_id", "../security").is_err());
+        assert!(validate_segment("agent_id", "001/stats").is_err());
+        assert!(validate_segment("agent_id", "001\\stats").is_err());
+        assert!(validate_segment("agent_id", "").is_err());
+    }
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("001"), "001");
+        assert_eq!(encode_segment("cis_debian-10.x~"), "cis_debian-10.x~");
+        assert_eq!(encode_segment("a b?c#d"), "a%20b%3Fc%23d");
+        assert_eq!(encode_segment("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn test_merge_rejects_conflicting_body_param() {
+        let mut request: WazuhRequest = serde_json::from_value(serde_json::json!({
+            "endpoint": "https://wazuh:55000",
+            "token": "t",
+            "params": { "agent_id": "002" }
+        }))
+        .unwrap();
+
+        let path = HashMap::from([("agent_id".to_string(), "001".to_string())]);
+        assert!(merge_path_params(&mut request, path).is_err());
+
+        let path = HashMap::from([("agent_id".to_string(), "002".to_string())]);
+        assert!(merge_path_params(&mut request, path).is_ok());
+    }
+}
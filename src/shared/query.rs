@@ -0,0 +1,256 @@
+use reqwest::Url;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use super::error::NexusError;
+
+/// Accepted by every Wazuh endpoint.
+const COMMON: &[&str] = &["pretty", "wait_for_complete"];
+
+/// Paging, sorting and filtering shared by Wazuh list endpoints.
+const LIST: &[&str] = &["offset", "limit", "sort", "search", "select", "q", "distinct"];
+
+/// Endpoint specific parameters, keyed by the path template used in the handlers.
+/// Templates marked `list` additionally accept [`LIST`].
+fn endpoint_params(template: &str) -> Option<(bool, &'static [&'static str])> {
+    let params: (bool, &'static [&'static str]) = match template {
+        "" => (false, &[]),
+
+        // Agents
+        "agents" => (true, &[
+            "agents_list", "status", "older_than", "os.platform", "os.version", "os.name", "manager",
+            "version", "group", "node_name", "name", "ip", "registerIP", "group_config_status",
+        ]),
+        "agents/{agent_id}/config/{component}/{configuration}" => (false, &[]),
+        "agents/{agent_id}/group/is_sync" => (false, &[]),
+        "agents/{agent_id}/daemons/stats" => (false, &["daemons_list"]),
+        "agents/{agent_id}/stats/{component}" => (false, &[]),
+        "agents/no_group" => (true, &[]),
+        "agents/outdated" => (true, &[]),
+        "agents/stats/distinct" => (true, &["fields"]),
+        "agents/summary/os" => (true, &[]),
+        "agents/summary/status" => (false, &["agents_list"]),
+
+        // Agent write operations, keyed by method and path since `agents` is
+        // also a list endpoint.
+        "PUT agents/restart" | "PUT agents/reconnect" => (false, &["agents_list"]),
+        "PUT agents/{agent_id}/restart" => (false, &[]),
+        "PUT agents/upgrade" => (false, &[
+            "agents_list", "wpk_repo", "upgrade_version", "use_http", "force", "package_type",
+        ]),
+        "DELETE agents" => (false, &["agents_list", "status", "older_than", "purge"]),
+        "POST agents" => (false, &[]),
+
+        // CIS-CAT
+        "ciscat/{agent_id}/results" => (true, &[
+            "benchmark", "profile", "pass", "fail", "error", "notchecked", "unknown", "score",
+        ]),
+
+        // Decoders
+        "decoders" => (true, &["decoder_names", "filename", "relative_dirname", "status"]),
+        "decoders/files" => (true, &["filename", "relative_dirname", "status"]),
+        "decoders/parents" => (true, &[]),
+
+        // Groups
+        "groups" => (true, &["groups_list", "hash"]),
+        "groups/{group_id}/agents" => (true, &["status"]),
+        "groups/{group_id}/configuration" => (false, &["offset", "limit"]),
+        "groups/{group_id}/files" => (true, &["hash"]),
+
+        // CDB lists
+        "lists" | "lists/files" => (true, &["filename", "relative_dirname"]),
+
+        // Manager
+        "manager/status" | "manager/info" => (false, &[]),
+        "manager/configuration" => (false, &["section", "field", "raw", "distinct"]),
+        "manager/stats" => (false, &["date"]),
+        "manager/stats/hourly" | "manager/stats/weekly" => (false, &[]),
+        "manager/logs" => (true, &["tag", "level"]),
+        "manager/logs/summary" => (false, &[]),
+
+        // MITRE
+        "mitre/groups" => (true, &["group_ids"]),
+        "mitre/metadata" => (false, &[]),
+        "mitre/mitigations" => (true, &["mitigation_ids"]),
+        "mitre/references" => (true, &["reference_ids"]),
+        "mitre/software" => (true, &["software_ids"]),
+        "mitre/tactics" => (true, &["tactic_ids"]),
+        "mitre/techniques" => (true, &["technique_ids"]),
+
+        // Rootcheck
+        "rootcheck/{agent_id}" => (true, &["status", "pci_dss", "cis"]),
+        "rootcheck/{agent_id}/last_scan" => (false, &[]),
+
+        // Rules
+        "rules" => (true, &[
+            "rule_ids", "status", "group", "level", "filename", "relative_dirname", "pci_dss", "gdpr",
+            "gpg13", "hipaa", "tsc", "mitre", "nist-800-53",
+        ]),
+        "rules/groups" => (true, &[]),
+        "rules/files" => (true, &["status", "filename", "relative_dirname"]),
+
+        // SCA
+        "sca/{agent_id}" => (true, &["name", "description", "references"]),
+        "sca/{agent_id}/checks/{policy_id}" => (true, &[
+            "title", "description", "rationale", "remediation", "command", "reason", "file", "process",
+            "directory", "registry", "references", "result", "condition",
+        ]),
+
+        // Security
+        "security/actions" => (false, &["endpoint"]),
+        "security/resources" => (false, &["resource"]),
+        "security/config" => (false, &[]),
+
+        // Syscheck
+        "syscheck/{agent_id}" => (true, &[
+            "file", "arch", "value.name", "value.type", "type", "summary", "md5", "sha1", "sha256", "hash",
+        ]),
+        "syscheck/{agent_id}/last_scan" => (false, &[]),
+
+        // Syscollector
+        "syscollector/{agent_id}/hardware" | "syscollector/{agent_id}/os" => (false, &["select"]),
+        "syscollector/{agent_id}/hotfixes" => (true, &["hotfix"]),
+        "syscollector/{agent_id}/netaddr" => (true, &["iface", "proto", "address", "broadcast", "netmask"]),
+        "syscollector/{agent_id}/netiface" => (true, &["name", "adapter", "type", "state", "mtu"]),
+        "syscollector/{agent_id}/netproto" => (true, &["iface", "type", "gateway", "dhcp"]),
+        "syscollector/{agent_id}/packages" => (true, &["vendor", "name", "architecture", "format", "version"]),
+        "syscollector/{agent_id}/ports" => (true, &[
+            "pid", "protocol", "local.ip", "local.port", "remote.ip", "tx_queue", "state", "process",
+        ]),
+        "syscollector/{agent_id}/processes" => (true, &[
+            "pid", "state", "ppid", "egroup", "euser", "fgroup", "name", "nlwp", "pgrp", "priority",
+            "rgroup", "ruser", "sgroup", "suser",
+        ]),
+
+        // Tasks
+        "tasks/status" => (true, &["tasks_list", "agents_list", "command", "node", "module", "status"]),
+
+        _ => return None,
+    };
+    Some(params)
+}
+
+/// Whether `name` may be forwarded to the endpoint behind `template`.
+pub fn is_allowed(template: &str, name: &str) -> bool {
+    if COMMON.contains(&name) {
+        return true;
+    }
+    match endpoint_params(template) {
+        Some((list, specific)) => specific.contains(&name) || (list && LIST.contains(&name)),
+        None => false,
+    }
+}
+
+/// Turns one JSON query value into its Wazuh string form. Arrays become the
+/// comma separated lists Wazuh expects for `*_list`/`*_ids` parameters.
+fn to_query_value(name: &str, value: &Value) -> Result<String, NexusError> {
+    let scalar = |v: &Value| match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+
+    let text = match value {
+        Value::Array(items) => items
+            .iter()
+            .map(scalar)
+            .collect::<Option<Vec<_>>>()
+            .map(|parts| parts.join(",")),
+        other => scalar(other),
+    }
+    .ok_or_else(|| NexusError::BadParameter(format!(
+        "Query parameter '{}' must be a string, number, boolean or a list of those", name
+    )))?;
+
+    if (name == "offset" || name == "limit") && text.parse::<u64>().is_err() {
+        return Err(NexusError::BadParameter(format!(
+            "Query parameter '{}' must be a non-negative integer", name
+        )));
+    }
+
+    Ok(text)
+}
+
+/// Validates `query` against the endpoint behind `template` and appends it to
+/// `url`, URL-encoded and in key order so equal queries give equal cache keys.
+pub fn append_query(url: &str, template: &str, query: &BTreeMap<String, Value>) -> Result<String, NexusError> {
+    if query.is_empty() {
+        return Ok(url.to_string());
+    }
+
+    let mut parsed = Url::parse(url)
+        .map_err(|e| NexusError::BadParameter(format!("Invalid endpoint URL '{}': {}", url, e)))?;
+
+    {
+        let mut pairs = parsed.query_pairs_mut();
+        for (name, value) in query {
+            if !is_allowed(template, name) {
+                return Err(NexusError::BadParameter(format!(
+                    "Query parameter '{}' is not supported by /{}", name, template
+                )));
+            }
+            pairs.append_pair(name, &to_query_value(name, value)?);
+        }
+    }
+
+    Ok(parsed.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(value: Value) -> BTreeMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_query_is_encoded_in_key_order() {
+        let url = append_query(
+            "https://wazuh:55000/agents",
+            "agents",
+            &query(json!({ "status": "active", "limit": 10, "q": "os.name=Ubuntu;name~web 01" })),
+        )
+        .unwrap();
+
+        assert_eq!(url, "https://wazuh:55000/agents?limit=10&q=os.name%3DUbuntu%3Bname%7Eweb+01&status=active");
+    }
+
+    #[test]
+    fn test_lists_are_joined_with_commas() {
+        let url = append_query(
+            "https://wazuh:55000/agents/summary/status",
+            "agents/summary/status",
+            &query(json!({ "agents_list": ["001", "002"] })),
+        )
+        .unwrap();
+
+        assert_eq!(url, "https://wazuh:55000/agents/summary/status?agents_list=001%2C002");
+    }
+
+    #[test]
+    fn test_unknown_parameters_are_rejected() {
+        let err = append_query("https://wazuh:55000/manager/info", "manager/info", &query(json!({ "limit": 1 })));
+        assert!(matches!(err, Err(NexusError::BadParameter(_))));
+
+        let err = append_query("https://wazuh:55000/agents", "agents", &query(json!({ "rule_ids": "1" })));
+        assert!(matches!(err, Err(NexusError::BadParameter(_))));
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let err = append_query("https://wazuh:55000/rules", "rules", &query(json!({ "limit": "ten" })));
+        assert!(matches!(err, Err(NexusError::BadParameter(_))));
+
+        let err = append_query("https://wazuh:55000/rules", "rules", &query(json!({ "q": { "level": 10 } })));
+        assert!(matches!(err, Err(NexusError::BadParameter(_))));
+    }
+
+    #[test]
+    fn test_common_parameters_are_always_allowed() {
+        assert!(is_allowed("manager/info", "pretty"));
+        assert!(is_allowed("unknown/template", "wait_for_complete"));
+        assert!(!is_allowed("unknown/template", "limit"));
+    }
+}
@@ -0,0 +1,435 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, HeaderMap, Request},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use super::access::Principal;
+use super::error::NexusError;
+use super::state::AppState;
+
+/// Keys are dropped from a limiter once it tracks this many and they are idle.
+const RETAIN_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Password logins (`/auth`, `/auth/run_as`, `/session`) per client IP.
+    pub auth_per_ip: Option<Quota>,
+    /// Password logins per username, whatever the IP.
+    pub auth_per_user: Option<Quota>,
+    /// Consecutive rejected logins before an IP or username is locked out; 0 disables.
+    pub lockout_after: u32,
+    /// First lockout; doubles with every further failure.
+    pub lockout_base: Duration,
+    pub lockout_max: Duration,
+    /// Quotas per feature module, keyed by API key or client IP.
+    pub routes: BTreeMap<String, Quota>,
+    /// Take the client IP from the first `X-Forwarded-For` entry. Only enable
+    /// behind a proxy that sets it.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            auth_per_ip: Some(Quota::per_minute(nonzero!(20u32))),
+            auth_per_user: Some(Quota::per_minute(nonzero!(10u32))),
+            lockout_after: 5,
+            lockout_base: Duration::from_secs(30),
+            lockout_max: Duration::from_secs(15 * 60),
+            routes: BTreeMap::new(),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reads `NEXUS_AUTH_RATE_PER_IP`, `NEXUS_AUTH_RATE_PER_USER` (e.g. `20/min`,
+    /// `off`), `NEXUS_AUTH_LOCKOUT_AFTER`, `NEXUS_AUTH_LOCKOUT_SECS`,
+    /// `NEXUS_AUTH_LOCKOUT_MAX_SECS`, `NEXUS_RATE_LIMITS` (`wql=5/min,agents=50/s`)
+    /// and `NEXUS_TRUST_FORWARDED_FOR`.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+
+        if let Ok(value) = env::var("NEXUS_AUTH_RATE_PER_IP") {
+            config.auth_per_ip = parse_optional_quota(&value).map_err(|e| format!("NEXUS_AUTH_RATE_PER_IP: {}", e))?;
+        }
+        if let Ok(value) = env::var("NEXUS_AUTH_RATE_PER_USER") {
+            config.auth_per_user = parse_optional_quota(&value).map_err(|e| format!("NEXUS_AUTH_RATE_PER_USER: {}", e))?;
+        }
+        if let Ok(value) = env::var("NEXUS_AUTH_LOCKOUT_AFTER") {
+            config.lockout_after = value
+                .parse()
+                .map_err(|_| format!("NEXUS_AUTH_LOCKOUT_AFTER must be a number, got '{}'", value))?;
+        }
+        if let Some(secs) = env_secs("NEXUS_AUTH_LOCKOUT_SECS")? {
+            config.lockout_base = secs;
+        }
+        if let Some(secs) = env_secs("NEXUS_AUTH_LOCKOUT_MAX_SECS")? {
+            config.lockout_max = secs;
+        }
+        if let Ok(spec) = env::var("NEXUS_RATE_LIMITS") {
+            for rule in spec.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+                let (module, quota) = rule
+                    .split_once('=')
+                    .ok_or_else(|| format!("NEXUS_RATE_LIMITS: expected module=quota, got '{}'", rule))?;
+                let quota = parse_quota(quota.trim()).map_err(|e| format!("NEXUS_RATE_LIMITS: {}", e))?;
+                config.routes.insert(module.trim().to_string(), quota);
+            }
+        }
+        if let Ok(value) = env::var("NEXUS_TRUST_FORWARDED_FOR") {
+            config.trust_forwarded_for = matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes");
+        }
+        Ok(config)
+    }
+}
+
+fn env_secs(name: &str) -> Result<Option<Duration>, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(|secs| Some(Duration::from_secs(secs)))
+            .map_err(|_| format!("{} must be a number of seconds, got '{}'", name, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parses `count/unit` with unit `s`, `min` or `hour`; `off` or `0` gives `None`.
+pub fn parse_optional_quota(spec: &str) -> Result<Option<Quota>, String> {
+    match spec.trim() {
+        "off" | "0" => Ok(None),
+        spec => parse_quota(spec).map(Some),
+    }
+}
+
+pub fn parse_quota(spec: &str) -> Result<Quota, String> {
+    let invalid = || format!("expected a quota like '20/min', got '{}'", spec);
+    let (count, unit) = spec.split_once('/').ok_or_else(invalid)?;
+    let count: NonZeroU32 = count.trim().parse().map_err(|_| invalid())?;
+    match unit.trim() {
+        "s" | "sec" | "second" => Ok(Quota::per_second(count)),
+        "m" | "min" | "minute" => Ok(Quota::per_minute(count)),
+        "h" | "hour" => Ok(Quota::per_hour(count)),
+        _ => Err(invalid()),
+    }
+}
+
+/// A governor limiter with one bucket per key.
+pub struct KeyedLimiter {
+    limiter: DefaultKeyedRateLimiter<String>,
+}
+
+impl KeyedLimiter {
+    pub fn new(quota: Quota) -> Self {
+        Self { limiter: RateLimiter::keyed(quota) }
+    }
+
+    /// Takes one unit from `key`'s bucket, or says how long until one is free.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        if self.limiter.len() > RETAIN_THRESHOLD {
+            self.limiter.retain_recent();
+        }
+        self.limiter
+            .check_key(&key.to_string())
+            .map_err(|not_until| not_until.wait_time_from(DefaultClock::default().now()))
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Counts consecutive rejected logins per key and locks the key out for a
+/// period that doubles with every failure past the threshold.
+#[derive(Debug)]
+pub struct Lockout {
+    after: u32,
+    base: Duration,
+    max: Duration,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl Lockout {
+    pub fn new(after: u32, base: Duration, max: Duration) -> Self {
+        Self {
+            after,
+            base,
+            max,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Time left on `key`'s lockout, if any.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+        match failures.get(key).and_then(|f| f.locked_until) {
+            Some(until) if until > now => Err(until - now),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_failure(&self, key: &str) {
+        if self.after == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        // Forget keys that have been quiet for longer than the longest lockout
+        failures.retain(|_, f| now.duration_since(f.last) < self.max);
+
+        let entry = failures.entry(key.to_string()).or_insert(Failures { count: 0, last: now, locked_until: None });
+        entry.count += 1;
+        entry.last = now;
+        if entry.count >= self.after {
+            let doublings = (entry.count - self.after).min(16);
+            entry.locked_until = Some(now + self.base.saturating_mul(1 << doublings).min(self.max));
+        }
+    }
+
+    pub fn record_success(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+/// Brute-force protection for password logins: per-IP and per-username quotas
+/// plus a lockout after repeated rejections.
+pub struct LoginGuard {
+    per_ip: Option<KeyedLimiter>,
+    per_user: Option<KeyedLimiter>,
+    lockout: Lockout,
+}
+
+impl LoginGuard {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            per_ip: config.auth_per_ip.map(KeyedLimiter::new),
+            per_user: config.auth_per_user.map(KeyedLimiter::new),
+            lockout: Lockout::new(config.lockout_after, config.lockout_base, config.lockout_max),
+        }
+    }
+
+    /// Call before forwarding a login to Wazuh.
+    pub fn admit(&self, ip: Option<IpAddr>, username: &str) -> Result<(), NexusError> {
+        let keys = login_keys(ip, username);
+        for key in &keys {
+            self.lockout.check(key).map_err(|wait| rate_limited("Too many failed logins", wait))?;
+        }
+        if let (Some(limiter), Some(ip)) = (&self.per_ip, ip) {
+            limiter.check(&ip.to_string()).map_err(|wait| rate_limited("Too many logins from this address", wait))?;
+        }
+        if let Some(limiter) = &self.per_user {
+            limiter.check(username).map_err(|wait| rate_limited("Too many logins for this user", wait))?;
+        }
+        Ok(())
+    }
+
+    /// Call when Wazuh rejected the credentials (401).
+    pub fn record_failure(&self, ip: Option<IpAddr>, username: &str) {
+        for key in login_keys(ip, username) {
+            self.lockout.record_failure(&key);
+        }
+    }
+
+    /// Call when the login succeeded; clears the user's failure count. The
+    /// address keeps its count until it expires, so one valid account cannot
+    /// reset the lockout for guesses against others.
+    pub fn record_success(&self, _ip: Option<IpAddr>, username: &str) {
+        self.lockout.record_success(&format!("user:{}", username));
+    }
+
+    /// Records a login result: Wazuh's 401 is a failure, other errors (Wazuh
+    /// unreachable, ...) do not count either way.
+    pub fn record<T>(&self, ip: Option<IpAddr>, username: &str, result: &Result<T, NexusError>) {
+        match result {
+            Ok(_) => self.record_success(ip, username),
+            Err(NexusError::Upstream { status: 401, .. }) => self.record_failure(ip, username),
+            Err(_) => {}
+        }
+    }
+}
+
+fn login_keys(ip: Option<IpAddr>, username: &str) -> Vec<String> {
+    let mut keys = vec![format!("user:{}", username)];
+    if let Some(ip) = ip {
+        keys.push(format!("ip:{}", ip));
+    }
+    keys
+}
+
+fn rate_limited(reason: &str, wait: Duration) -> NexusError {
+    NexusError::RateLimited {
+        message: reason.to_string(),
+        // Round up so a client waiting `Retry-After` is never early
+        retry_after_secs: wait.as_secs() + u64::from(wait.subsec_nanos() > 0),
+    }
+}
+
+/// The login guard and the per-module quotas, built once from [`RateLimitConfig`].
+pub struct RateLimits {
+    pub logins: LoginGuard,
+    routes: HashMap<String, KeyedLimiter>,
+    trust_forwarded_for: bool,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            logins: LoginGuard::new(config),
+            routes: config.routes.iter().map(|(module, quota)| (module.clone(), KeyedLimiter::new(*quota))).collect(),
+            trust_forwarded_for: config.trust_forwarded_for,
+        }
+    }
+
+    /// The caller's address: the TCP peer, or the first `X-Forwarded-For` entry
+    /// when that is trusted.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: Option<&ConnectInfo<SocketAddr>>) -> Option<IpAddr> {
+        let forwarded = || {
+            headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|ip| ip.trim().parse().ok())
+        };
+        self.trust_forwarded_for
+            .then(forwarded)
+            .flatten()
+            .or_else(|| peer.map(|ConnectInfo(addr)| addr.ip()))
+    }
+}
+
+/// Extractor for the caller's address, see [`RateLimits::client_ip`]. `None`
+/// when the server was started without connect info (e.g. in tests).
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(state.limits.client_ip(&parts.headers, parts.extensions.get())))
+    }
+}
+
+/// Applies the `NEXUS_RATE_LIMITS` quota for `module`, if any, to every route
+/// of `router`. Callers are told apart by API key, else by IP.
+pub fn quota(state: &AppState, module: &'static str, router: Router<AppState>) -> Router<AppState> {
+    if !state.limits.routes.contains_key(module) {
+        return router;
+    }
+    router.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        move |State(state): State<AppState>, req: Request<Body>, next: Next<Body>| enforce_quota(state, module, req, next),
+    ))
+}
+
+async fn enforce_quota(state: AppState, module: &'static str, req: Request<Body>, next: Next<Body>) -> Response {
+    let caller = match req.extensions().get::<Arc<Principal>>() {
+        Some(principal) => format!("key:{}", principal.key_id),
+        None => state.limits
+            .client_ip(req.headers(), req.extensions().get())
+            .map_or_else(|| "anonymous".to_string(), |ip| format!("ip:{}", ip)),
+    };
+
+    if let Some(limiter) = state.limits.routes.get(module) {
+        if let Err(wait) = limiter.check(&caller) {
+            return rate_limited(&format!("Quota for '{}' exceeded", module), wait).into_response();
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quota() {
+        assert_eq!(parse_quota("20/min").unwrap(), Quota::per_minute(nonzero!(20u32)));
+        assert_eq!(parse_quota("5/s").unwrap(), Quota::per_second(nonzero!(5u32)));
+        assert_eq!(parse_quota("100 / hour").unwrap(), Quota::per_hour(nonzero!(100u32)));
+        assert_eq!(parse_optional_quota("off").unwrap(), None);
+        assert!(parse_quota("0/min").is_err());
+        assert!(parse_quota("20").is_err());
+        assert!(parse_quota("20/day").is_err());
+    }
+
+    #[test]
+    fn test_keyed_limiter_separates_keys() {
+        let limiter = KeyedLimiter::new(Quota::per_hour(nonzero!(2u32)));
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_ok());
+        let wait = limiter.check("a").unwrap_err();
+        assert!(wait > Duration::from_secs(60));
+        assert!(limiter.check("b").is_ok());
+    }
+
+    #[test]
+    fn test_lockout_grows_and_resets() {
+        let lockout = Lockout::new(2, Duration::from_secs(10), Duration::from_secs(25));
+
+        lockout.record_failure("user:a");
+        assert!(lockout.check("user:a").is_ok());
+
+        lockout.record_failure("user:a");
+        let first = lockout.check("user:a").unwrap_err();
+        assert!(first <= Duration::from_secs(10) && first > Duration::from_secs(9));
+
+        lockout.record_failure("user:a");
+        assert!(lockout.check("user:a").unwrap_err() > Duration::from_secs(19));
+
+        lockout.record_failure("user:a");
+        assert!(lockout.check("user:a").unwrap_err() <= Duration::from_secs(25), "Capped at the maximum");
+
+        lockout.record_success("user:a");
+        assert!(lockout.check("user:a").is_ok());
+    }
+
+    #[test]
+    fn test_lockout_does_not_overflow() {
+        let lockout = Lockout::new(1, Duration::from_secs(u64::MAX / 2), Duration::from_secs(3600));
+        for _ in 0..20 {
+            lockout.record_failure("user:a");
+        }
+        assert!(lockout.check("user:a").is_err());
+    }
+
+    #[test]
+    fn test_login_success_keeps_the_address_locked() {
+        let config = RateLimitConfig { lockout_after: 2, auth_per_ip: None, auth_per_user: None, ..Default::default() };
+        let guard = LoginGuard::new(&config);
+        let ip = Some(IpAddr::from([10, 0, 0, 1]));
+
+        guard.record_failure(ip, "alice");
+        guard.record_failure(ip, "bob");
+        guard.record_success(ip, "carol");
+        assert!(guard.admit(ip, "carol").is_err(), "The address stays locked");
+        assert!(guard.admit(None, "carol").is_ok());
+    }
+
+    #[test]
+    fn test_lockout_disabled() {
+        let lockout = Lockout::new(0, Duration::from_secs(10), Duration::from_secs(60));
+        for _ in 0..10 {
+            lockout.record_failure("user:a");
+        }
+        assert!(lockout.check("user:a").is_ok());
+    }
+}
@@ -0,0 +1,363 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use crate::client::WazuhClient;
+use super::config::AppConfig;
+use super::error::NexusError;
+use super::state::AppState;
+use super::upstreams::Upstream;
+
+/// Name of the cookie carrying the nexus session id.
+pub const SESSION_COOKIE: &str = "nexus_session";
+
+/// Every session id starts with this, so it can be told apart from a Wazuh JWT
+/// in an `Authorization: Bearer` header.
+pub const SESSION_PREFIX: &str = "nxs_";
+
+/// Server-side sessions: nexus holds the Wazuh JWT and credentials and clients
+/// only keep an opaque session id.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// Enables `POST /session` and session lookups on proxied routes.
+    pub enabled: bool,
+    /// Sessions unused for this long are forgotten.
+    pub idle_timeout: Duration,
+    /// Adds `Secure` to the session cookie; set it when nexus is served over HTTPS.
+    pub cookie_secure: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_timeout: Duration::from_secs(8 * 3600),
+            cookie_secure: false,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Reads `NEXUS_SESSIONS`, `NEXUS_SESSION_IDLE_TIMEOUT` (seconds) and
+    /// `NEXUS_SESSION_COOKIE_SECURE`.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self {
+            enabled: env_flag("NEXUS_SESSIONS")?,
+            cookie_secure: env_flag("NEXUS_SESSION_COOKIE_SECURE")?,
+            ..Self::default()
+        };
+        if let Ok(value) = env::var("NEXUS_SESSION_IDLE_TIMEOUT") {
+            let secs: u64 = value
+                .parse()
+                .map_err(|_| format!("NEXUS_SESSION_IDLE_TIMEOUT must be a number, got '{}'", value))?;
+            config.idle_timeout = Duration::from_secs(secs);
+        }
+        Ok(config)
+    }
+}
+
+/// A Wazuh login kept by nexus. The current JWT is replaced transparently when
+/// Wazuh rejects it.
+pub struct Session {
+    pub id: String,
+    /// Name of the upstream manager the session is bound to.
+    pub upstream: String,
+    pub username: String,
+    upstream_url: String,
+    password: String,
+    token: RwLock<String>,
+    // Serializes re-authentication so concurrent 401s log in only once.
+    refreshing: tokio::sync::Mutex<()>,
+    last_used: Mutex<Instant>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("upstream", &self.upstream)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    /// The Wazuh JWT currently held by the session.
+    pub fn token(&self) -> String {
+        self.token.read().unwrap().clone()
+    }
+
+    /// Logs in again unless another caller already replaced `stale_token`, and
+    /// returns the token to use from now on.
+    pub async fn refresh(&self, client: &WazuhClient, stale_token: &str) -> Result<String, NexusError> {
+        let _guard = self.refreshing.lock().await;
+        let current = self.token();
+        if current != stale_token {
+            return Ok(current);
+        }
+
+        println!("Wazuh token of {} on '{}' was rejected, re-authenticating", self.username, self.upstream);
+        let fresh = wazuh_login(client, &self.upstream_url, &self.username, &self.password).await?;
+        *self.token.write().unwrap() = fresh.clone();
+        Ok(fresh)
+    }
+
+    /// Revokes the session's JWT on Wazuh and drops what was cached under it.
+    /// Returns the number of purged cache entries.
+    pub async fn logout(&self, client: &WazuhClient) -> Result<usize, NexusError> {
+        let token = self.token();
+        let purged = client.invalidate_token(&token);
+        wazuh_logout(client, &self.upstream_url, &token).await?;
+        Ok(purged)
+    }
+
+    fn touch(&self) -> Duration {
+        let mut last_used = self.last_used.lock().unwrap();
+        let idle = last_used.elapsed();
+        *last_used = Instant::now();
+        idle
+    }
+}
+
+/// Runs `call` with the session's current token (or `token` without a session).
+/// When Wazuh answers 401 for a session, logs in again and retries once.
+pub async fn with_reauth<T, F, Fut>(client: &WazuhClient, session: Option<&Arc<Session>>, token: &str, call: F) -> Result<T, NexusError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T, NexusError>>,
+{
+    let Some(session) = session else {
+        return call(token.to_string()).await;
+    };
+
+    let token = session.token();
+    match call(token.clone()).await {
+        Err(NexusError::Upstream { status: 401, .. }) => {
+            let fresh = session.refresh(client, &token).await?;
+            call(fresh).await
+        }
+        other => other,
+    }
+}
+
+/// Logs in to the manager at `upstream_url` with basic auth and returns the JWT.
+pub async fn wazuh_login(client: &WazuhClient, upstream_url: &str, username: &str, password: &str) -> Result<String, NexusError> {
+    let auth_url = format!("{}/security/user/authenticate", upstream_url);
+    let response = client.get_with_auth(&auth_url, username, password).await?;
+    token_from(WazuhClient::json_result(response).await?)
+}
+
+/// Extracts the JWT from a Wazuh login answer.
+pub fn token_from(data: serde_json::Value) -> Result<String, NexusError> {
+    data["data"]["token"].as_str()
+        .map(String::from)
+        .ok_or_else(|| NexusError::Parse("No token in authentication response".to_string()))
+}
+
+/// Revokes `token` on the manager at `upstream_url`
+/// (`DELETE /security/user/authenticate`) and returns Wazuh's answer.
+pub async fn wazuh_logout(client: &WazuhClient, upstream_url: &str, token: &str) -> Result<serde_json::Value, NexusError> {
+    let auth_url = format!("{}/security/user/authenticate", upstream_url);
+    WazuhClient::json_result(client.delete(&auth_url, token).await?).await
+}
+
+#[derive(Debug)]
+pub struct SessionManager {
+    config: SessionConfig,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    // Service account session per upstream name, created on first use.
+    service: tokio::sync::Mutex<HashMap<String, Arc<Session>>>,
+}
+
+impl SessionManager {
+    pub fn new(config: SessionConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            service: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Logs `username` in to `upstream` and stores a new session for them.
+    pub async fn login(&self, client: &WazuhClient, upstream: &Upstream, username: &str, password: &str) -> Result<Arc<Session>, NexusError> {
+        let token = wazuh_login(client, &upstream.url, username, password).await?;
+        let session = Arc::new(new_session(new_session_id(), upstream, username, password, token));
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.last_used.lock().unwrap().elapsed() < self.config.idle_timeout);
+        sessions.insert(session.id.clone(), session.clone());
+        Ok(session)
+    }
+
+    /// Looks up a live session and marks it used.
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(id)?.clone();
+        if session.touch() >= self.config.idle_timeout {
+            sessions.remove(id);
+            return None;
+        }
+        Some(session)
+    }
+
+    /// Sessions that have not gone idle, without marking them used.
+    pub fn live(&self) -> Vec<Arc<Session>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.last_used.lock().unwrap().elapsed() < self.config.idle_timeout);
+        sessions.values().cloned().collect()
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().remove(id)
+    }
+
+    /// The service account session on the default upstream, logging in on first
+    /// use. Used by background jobs such as warm-up and WQL reports.
+    pub async fn service_session(&self, client: &WazuhClient, config: &AppConfig) -> Result<Arc<Session>, NexusError> {
+        let (_, username, password) = config.service_account().map_err(NexusError::Unauthorized)?;
+        let upstream = config.upstreams.default_upstream()
+            .ok_or_else(|| NexusError::UnknownUpstream("No Wazuh upstream is configured".to_string()))?;
+
+        let mut service = self.service.lock().await;
+        if let Some(session) = service.get(&upstream.name) {
+            return Ok(session.clone());
+        }
+
+        let token = wazuh_login(client, &upstream.url, username, password).await?;
+        let session = Arc::new(new_session(format!("service:{}", upstream.name), upstream, username, password, token));
+        service.insert(upstream.name.clone(), session.clone());
+        Ok(session)
+    }
+
+    /// The session named by the request's cookie or `Authorization: Bearer nxs_…`
+    /// header. `Ok(None)` when the request carries no session id.
+    pub fn from_headers(&self, headers: &HeaderMap) -> Result<Option<Arc<Session>>, NexusError> {
+        let Some(id) = session_id(headers) else {
+            return Ok(None);
+        };
+        if !self.config.enabled {
+            return Err(NexusError::Unauthorized("Sessions are disabled (NEXUS_SESSIONS)".to_string()));
+        }
+        self.get(&id)
+            .map(Some)
+            .ok_or_else(|| NexusError::Unauthorized("Session expired or unknown, log in again".to_string()))
+    }
+
+    /// `Set-Cookie` value for `session`, or one clearing the cookie for `None`.
+    pub fn cookie(&self, session: Option<&Session>) -> String {
+        let secure = if self.config.cookie_secure { "; Secure" } else { "" };
+        match session {
+            Some(session) => format!(
+                "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}",
+                SESSION_COOKIE, session.id, self.config.idle_timeout.as_secs(), secure
+            ),
+            None => format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0{}", SESSION_COOKIE, secure),
+        }
+    }
+}
+
+/// Extractor for routes that require a nexus session.
+pub struct CurrentSession(pub Arc<Session>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for CurrentSession {
+    type Rejection = NexusError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        state.sessions
+            .from_headers(&parts.headers)?
+            .map(CurrentSession)
+            .ok_or_else(|| NexusError::Unauthorized("No nexus session".to_string()))
+    }
+}
+
+fn new_session(id: String, upstream: &Upstream, username: &str, password: &str, token: String) -> Session {
+    Session {
+        id,
+        upstream: upstream.name.clone(),
+        username: username.to_string(),
+        upstream_url: upstream.url.clone(),
+        password: password.to_string(),
+        token: RwLock::new(token),
+        refreshing: tokio::sync::Mutex::new(()),
+        last_used: Mutex::new(Instant::now()),
+    }
+}
+
+fn new_session_id() -> String {
+    let bytes: [u8; 32] = rand::random();
+    format!("{}{}", SESSION_PREFIX, URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn session_id(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|v| v.starts_with(SESSION_PREFIX));
+
+    let cookie = || {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == SESSION_COOKIE)
+            .map(|(_, value)| value)
+    };
+
+    bearer.or_else(cookie).map(String::from)
+}
+
+fn env_flag(name: &str) -> Result<bool, String> {
+    match env::var(name) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(true),
+            "" | "0" | "false" | "no" => Ok(false),
+            _ => Err(format!("{} must be true or false, got '{}'", name, value)),
+        },
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_from_bearer_or_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "theme=dark; nexus_session=nxs_abc".parse().unwrap());
+        assert_eq!(session_id(&headers).as_deref(), Some("nxs_abc"));
+
+        headers.insert(header::AUTHORIZATION, "Bearer nxs_def".parse().unwrap());
+        assert_eq!(session_id(&headers).as_deref(), Some("nxs_def"));
+
+        // A Wazuh JWT in the header is not a session id
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer eyJhbGciOi".parse().unwrap());
+        assert_eq!(session_id(&headers), None);
+    }
+
+    #[test]
+    fn test_session_ids_are_unique_and_prefixed() {
+        let a = new_session_id();
+        let b = new_session_id();
+        assert_ne!(a, b);
+        assert!(a.starts_with(SESSION_PREFIX));
+        assert!(a.len() > 40);
+    }
+}
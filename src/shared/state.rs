@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use crate::client::WazuhClient;
+use crate::features::wql::WqlTransport;
+use super::access::KeyStore;
+use super::audit::AuditLog;
+use super::config::AppConfig;
+use super::error::NexusError;
+use super::rate_limit::RateLimits;
+use super::sessions::{Session, SessionManager};
+use super::watcher::AgentWatcher;
+
+/// State shared by every feature router.
+///
+/// `WazuhClient` is cheap to clone and its cache lives behind an `Arc`, so all
+/// requests served by this process see the same cache.
+#[derive(Clone)]
+pub struct AppState {
+    pub client: WazuhClient,
+    pub config: Arc<AppConfig>,
+    pub sessions: Arc<SessionManager>,
+    /// API keys and roles; `None` leaves every route open.
+    pub access: Option<Arc<KeyStore>>,
+    /// Login brute-force protection and per-module quotas.
+    pub limits: Arc<RateLimits>,
+    /// Record of every state-changing call sent to Wazuh.
+    pub audit: Arc<AuditLog>,
+    /// Agent status history filled by the background poller.
+    pub watcher: Arc<AgentWatcher>,
+    /// Client for the WQL gateway; `None` when `NEXUS_WQL_*` is not configured.
+    pub wql: Option<Arc<WqlTransport>>,
+}
+
+impl AppState {
+    pub fn new(client: WazuhClient, config: AppConfig) -> Self {
+        Self {
+            client,
+            sessions: Arc::new(SessionManager::new(config.sessions.clone())),
+            access: None,
+            limits: Arc::new(RateLimits::new(&config.rate_limits)),
+            audit: Arc::new(AuditLog::new(config.audit.retain)),
+            watcher: Arc::new(AgentWatcher::new(config.watcher.clone())),
+            wql: config.wql.clone().map(|wql| Arc::new(WqlTransport::new(wql))),
+            config: Arc::new(config),
+        }
+    }
+
+    /// The service account session (`WAZUH_USERNAME`/`WAZUH_PASSWORD` on the
+    /// default upstream), logging in on first use.
+    pub async fn service_session(&self) -> Result<Arc<Session>, NexusError> {
+        self.sessions.service_session(&self.client, &self.config).await
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_config(AppConfig::from_env()?)
+    }
+
+    /// Builds the shared client (cache, retries, one TLS client and breaker per
+    /// upstream) from `config` and opens the API key store and audit log file
+    /// when they are configured.
+    pub fn from_config(config: AppConfig) -> Result<Self, String> {
+        let mut client = WazuhClient::with_cache_config(&config.cache)
+            .with_resilience(config.retry.clone(), config.breaker.clone());
+        for upstream in config.upstreams.iter() {
+            client = client.with_upstream(&upstream.name, &upstream.url, &upstream.tls)?;
+        }
+        let access = match config.access.key_file.as_deref() {
+            Some(path) => Some(Arc::new(KeyStore::open(path)?)),
+            None => {
+                println!("Warning: NEXUS_API_KEYS_FILE is not set, routes are served without API keys");
+                None
+            }
+        };
+        let audit = Arc::new(AuditLog::open(&config.audit)?);
+        Ok(Self {
+            access,
+            audit,
+            ..Self::new(client, config)
+        })
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use super::common::{fetch_wazuh_json, WazuhRequest};
+use super::error::NexusError;
+use super::state::AppState;
+
+/// A customer sharing the manager with others, identified by the agent groups
+/// it owns. Defined in the key store file and attached to API keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tenant {
+    #[serde(skip)]
+    pub name: String,
+    pub groups: BTreeSet<String>,
+}
+
+impl Tenant {
+    pub fn owns(&self, group: &str) -> bool {
+        self.groups.contains(group)
+    }
+
+    pub fn check_group(&self, group: &str) -> Result<(), NexusError> {
+        if self.owns(group) {
+            Ok(())
+        } else {
+            Err(NexusError::Forbidden(format!("Group '{}' is outside the groups of '{}'", group, self.name)))
+        }
+    }
+
+    /// Wazuh `q` expression matching agents in any of the tenant's groups.
+    fn group_filter(&self) -> Result<String, NexusError> {
+        if self.groups.is_empty() {
+            return Err(NexusError::Forbidden(format!("'{}' owns no groups", self.name)));
+        }
+        let clauses: Vec<String> = self.groups.iter().map(|g| format!("group={}", g)).collect();
+        Ok(clauses.join(","))
+    }
+
+    /// Keeps the items of the listing `template` that belong to one of the
+    /// tenant's groups: agents by their `group`, groups by their `name`.
+    pub fn retain_own(&self, template: &str, items: &mut Vec<Value>) {
+        let Some(field) = checked_field(template) else {
+            return;
+        };
+        items.retain(|item| match &item[field] {
+            Value::Array(groups) => groups.iter().filter_map(Value::as_str).any(|g| self.owns(g)),
+            Value::String(group) => self.owns(group),
+            _ => false,
+        });
+    }
+}
+
+/// Agent listings that accept `q` and are narrowed to the tenant's groups.
+const FILTERED_LISTINGS: &[&str] = &["agents", "agents/outdated", "agents/stats/distinct", "agents/summary/os"];
+
+/// Listings whose items name their groups, so the answer can be checked
+/// against the tenant as well as narrowed by `q`; with the field to check.
+const CHECKED_LISTINGS: &[(&str, &str)] = &[("agents", "group"), ("groups", "name")];
+
+fn checked_field(template: &str) -> Option<&'static str> {
+    CHECKED_LISTINGS.iter().find(|(listing, _)| *listing == template).map(|(_, field)| *field)
+}
+
+/// Agent endpoints that cannot be narrowed to a set of groups.
+const UNSCOPED: &[&str] = &["agents/no_group", "agents/summary/status"];
+
+/// Applies the caller's group scope (its tenant, its roles' groups, or both)
+/// to a proxied call: agent and group ids must belong to it, and agent and
+/// group listings only return what it covers. A no-op for unscoped callers.
+pub async fn enforce(state: &AppState, request: &mut WazuhRequest, template: &str) -> Result<(), NexusError> {
+    let Some(tenant) = request.scope.clone() else {
+        return Ok(());
+    };
+
+    if let Some(group) = request.params.get("group_id") {
+        tenant.check_group(group)?;
+    }
+
+    if template.contains("{agent_id}") {
+        if let Some(agent_id) = request.params.get("agent_id").cloned() {
+            let groups = agent_groups(state, request, &agent_id).await?;
+            if !groups.iter().any(|g| tenant.owns(g)) {
+                return Err(NexusError::Forbidden(format!("Agent '{}' is outside the groups of '{}'", agent_id, tenant.name)));
+            }
+        }
+    }
+
+    if UNSCOPED.contains(&template) {
+        return Err(NexusError::Forbidden(format!("/{} is not available to '{}'", template, tenant.name)));
+    }
+    if FILTERED_LISTINGS.contains(&template) {
+        restrict_query(&mut request.query, &tenant)?;
+    }
+    if let Some(field) = checked_field(template) {
+        select_field(&mut request.query, field);
+    }
+    Ok(())
+}
+
+/// The scope an answer from `template` must be limited to, if any.
+pub fn listing_scope(request: &WazuhRequest, template: &str) -> Option<Arc<Tenant>> {
+    request.scope.clone().filter(|_| checked_field(template).is_some())
+}
+
+/// Drops items outside `scope` from a Wazuh envelope of the listing
+/// `template` and lowers `total_affected_items` to match.
+pub fn retain_in_envelope(scope: &Tenant, template: &str, envelope: &mut Value) {
+    let Value::Array(items) = &mut envelope["data"]["affected_items"] else {
+        return;
+    };
+    let before = items.len();
+    scope.retain_own(template, items);
+    let dropped = (before - items.len()) as u64;
+    if let Some(total) = envelope["data"]["total_affected_items"].as_u64() {
+        envelope["data"]["total_affected_items"] = Value::from(total.saturating_sub(dropped));
+    }
+}
+
+/// ANDs the tenant's group filter onto the caller's own `q`, if any.
+fn restrict_query(query: &mut BTreeMap<String, Value>, tenant: &Tenant) -> Result<(), NexusError> {
+    let filter = tenant.group_filter()?;
+    let q = match query.get("q") {
+        Some(Value::String(own)) if !own.trim().is_empty() => {
+            check_parentheses(own)?;
+            format!("({});({})", own, filter)
+        }
+        Some(Value::String(_)) | None => filter,
+        Some(_) => return Err(NexusError::BadParameter("Query parameter 'q' must be a string".to_string())),
+    };
+    query.insert("q".to_string(), Value::String(q));
+    Ok(())
+}
+
+/// Rejects a `q` that could close the group it is wrapped in, such as
+/// `id=001),(id!=x`, and so escape the tenant's filter.
+fn check_parentheses(q: &str) -> Result<(), NexusError> {
+    let mut depth = 0usize;
+    for c in q.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth.checked_sub(1).ok_or_else(|| {
+                    NexusError::BadParameter("Query parameter 'q' closes a parenthesis it did not open".to_string())
+                })?
+            }
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return Err(NexusError::BadParameter("Query parameter 'q' has unbalanced parentheses".to_string()));
+    }
+    Ok(())
+}
+
+/// Makes sure items come back with `field`, which the check after the call
+/// relies on.
+fn select_field(query: &mut BTreeMap<String, Value>, field: &str) {
+    if let Some(Value::String(select)) = query.get_mut("select") {
+        if !select.split(',').any(|selected| selected.trim() == field) {
+            select.push(',');
+            select.push_str(field);
+        }
+    }
+}
+
+/// Groups of `agent_id`, looked up with the caller's own credentials (so the
+/// answer is cached per token like any other call). Empty for unknown agents.
+async fn agent_groups(state: &AppState, request: &WazuhRequest, agent_id: &str) -> Result<Vec<String>, NexusError> {
+    let lookup = WazuhRequest {
+        upstream: request.upstream.clone(),
+        endpoint: request.endpoint.clone(),
+        token: request.token.clone(),
+        session: request.session.clone(),
+        tenant: None,
+        scope: None,
+        key_id: None,
+        params: HashMap::new(),
+        query: BTreeMap::from([
+            ("agents_list".to_string(), Value::String(agent_id.to_string())),
+            ("select".to_string(), Value::String("group".to_string())),
+        ]),
+        all_pages: false,
+        format: Default::default(),
+        dry_run: false,
+        body: None,
+    };
+    let data = fetch_wazuh_json(state, lookup, "agents", |url| url).await?;
+
+    Ok(data["data"]["affected_items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|item| item["id"].as_str().is_none_or(|id| id == agent_id))
+        .flat_map(|item| item["group"].as_array().cloned().unwrap_or_default())
+        .filter_map(|g| g.as_str().map(String::from))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tenant(groups: &[&str]) -> Tenant {
+        Tenant {
+            name: "acme".to_string(),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_restrict_query_ands_group_filter() {
+        let acme = tenant(&["acme-web", "acme-db"]);
+
+        let mut query = BTreeMap::new();
+        restrict_query(&mut query, &acme).unwrap();
+        assert_eq!(query["q"], "group=acme-db,group=acme-web");
+
+        let mut query = BTreeMap::from([("q".to_string(), json!("status=active,status=pending"))]);
+        restrict_query(&mut query, &acme).unwrap();
+        assert_eq!(query["q"], "(status=active,status=pending);(group=acme-db,group=acme-web)");
+    }
+
+    #[test]
+    fn test_tenant_without_groups_sees_nothing() {
+        let mut query = BTreeMap::new();
+        assert!(matches!(restrict_query(&mut query, &tenant(&[])), Err(NexusError::Forbidden(_))));
+        assert!(tenant(&[]).check_group("default").is_err());
+    }
+
+    #[test]
+    fn test_restrict_query_rejects_escaping_the_group() {
+        let acme = tenant(&["acme-web"]);
+        for q in ["id=001),(id!=x", "id=001)", "(id=001", ")(id=001"] {
+            let mut query = BTreeMap::from([("q".to_string(), json!(q))]);
+            assert!(matches!(restrict_query(&mut query, &acme), Err(NexusError::BadParameter(_))), "{}", q);
+        }
+
+        let mut query = BTreeMap::from([("q".to_string(), json!("(status=active,status=pending);os.platform=ubuntu"))]);
+        restrict_query(&mut query, &acme).unwrap();
+    }
+
+    #[test]
+    fn test_retain_in_envelope_drops_other_tenants_agents() {
+        let mut envelope = json!({"data": {
+            "affected_items": [
+                {"id": "001", "group": ["acme-web"]},
+                {"id": "002", "group": ["globex"]},
+                {"id": "003"}
+            ],
+            "total_affected_items": 3
+        }});
+        retain_in_envelope(&tenant(&["acme-web"]), "agents", &mut envelope);
+        assert_eq!(envelope["data"]["affected_items"], json!([{"id": "001", "group": ["acme-web"]}]));
+        assert_eq!(envelope["data"]["total_affected_items"], 1);
+    }
+
+    #[test]
+    fn test_select_field_keeps_group_in_agent_listings() {
+        let mut query = BTreeMap::from([("select".to_string(), json!("id,name"))]);
+        select_field(&mut query, "group");
+        assert_eq!(query["select"], "id,name,group");
+
+        select_field(&mut query, "group");
+        assert_eq!(query["select"], "id,name,group");
+    }
+
+    #[test]
+    fn test_group_listings_keep_owned_groups() {
+        let mut groups = vec![json!({"name": "acme-web"}), json!({"name": "globex"})];
+        tenant(&["acme-web"]).retain_own("groups", &mut groups);
+        assert_eq!(groups, vec![json!({"name": "acme-web"})]);
+    }
+}
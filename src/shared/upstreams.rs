@@ -0,0 +1,194 @@
+use reqwest::Url;
+use std::env;
+use crate::client::TlsConfig;
+use super::error::NexusError;
+
+/// Name given to the manager configured through the legacy `WAZUH_URL`.
+pub const DEFAULT_UPSTREAM: &str = "default";
+
+/// A Wazuh manager nexus is allowed to talk to.
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    pub name: String,
+    /// Base URL of the manager API without a trailing slash, e.g. `https://wazuh:55000`.
+    pub url: String,
+    pub tls: TlsConfig,
+}
+
+/// The only managers requests may reach. Callers pick one by name (or by its
+/// exact URL, for older clients); anything else is refused.
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamRegistry {
+    upstreams: Vec<Upstream>,
+    default: Option<String>,
+}
+
+impl UpstreamRegistry {
+    /// Builds a registry; the default is `default` or else the first upstream.
+    pub fn new(upstreams: Vec<Upstream>, default: Option<&str>) -> Result<Self, String> {
+        let mut registry = Self::default();
+        for upstream in upstreams {
+            registry.add(upstream)?;
+        }
+
+        registry.default = match default {
+            Some(name) if registry.get(name).is_none() => {
+                return Err(format!("Default upstream '{}' is not a configured upstream", name));
+            }
+            Some(name) => Some(name.to_string()),
+            None => registry.upstreams.first().map(|u| u.name.clone()),
+        };
+        Ok(registry)
+    }
+
+    /// A registry with a single manager named [`DEFAULT_UPSTREAM`].
+    pub fn single(url: &str, tls: TlsConfig) -> Result<Self, String> {
+        Self::new(vec![Upstream { name: DEFAULT_UPSTREAM.to_string(), url: url.to_string(), tls }], None)
+    }
+
+    /// Reads `NEXUS_UPSTREAMS="prod=https://wazuh:55000,lab=https://lab:55000"` and
+    /// `NEXUS_DEFAULT_UPSTREAM`, falling back to `WAZUH_URL` as the only manager.
+    /// TLS settings come from `NEXUS_UPSTREAM_<NAME>_TLS_*`.
+    pub fn from_env() -> Result<Self, String> {
+        let default = env::var("NEXUS_DEFAULT_UPSTREAM").ok().filter(|d| !d.is_empty());
+
+        let entries: Vec<(String, String)> = match env::var("NEXUS_UPSTREAMS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    entry.split_once('=')
+                        .map(|(name, url)| (name.trim().to_string(), url.trim().to_string()))
+                        .ok_or_else(|| format!("NEXUS_UPSTREAMS entry '{}' must look like name=url", entry))
+                })
+                .collect::<Result<_, _>>()?,
+            Err(_) => match env::var("WAZUH_URL") {
+                Ok(url) => vec![(DEFAULT_UPSTREAM.to_string(), url)],
+                Err(_) => Vec::new(),
+            },
+        };
+
+        let upstreams = entries
+            .into_iter()
+            .map(|(name, url)| {
+                let tls = TlsConfig::from_env(&env_prefix(&name))?;
+                Ok(Upstream { name, url, tls })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Self::new(upstreams, default.as_deref())
+    }
+
+    fn add(&mut self, mut upstream: Upstream) -> Result<(), String> {
+        if upstream.name.is_empty() || !upstream.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Upstream name '{}' may only contain letters, digits, '-' and '_'", upstream.name));
+        }
+        if self.get(&upstream.name).is_some() {
+            return Err(format!("Upstream '{}' is configured twice", upstream.name));
+        }
+
+        let url = Url::parse(&upstream.url)
+            .map_err(|e| format!("Upstream '{}' has an invalid URL '{}': {}", upstream.name, upstream.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") || url.query().is_some() {
+            return Err(format!("Upstream '{}' must be an http(s) URL without a query, got '{}'", upstream.name, upstream.url));
+        }
+
+        upstream.url = normalize(&upstream.url).to_string();
+        self.upstreams.push(upstream);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Upstream> {
+        self.upstreams.iter().find(|u| u.name == name)
+    }
+
+    pub fn default_upstream(&self) -> Option<&Upstream> {
+        self.default.as_deref().and_then(|name| self.get(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Upstream> {
+        self.upstreams.iter()
+    }
+
+    /// Picks the manager for a request: by `name`, else by the exact base URL in
+    /// `endpoint`, else the default. Both given must point at the same manager.
+    pub fn resolve(&self, name: Option<&str>, endpoint: Option<&str>) -> Result<&Upstream, NexusError> {
+        let by_name = name
+            .map(|name| self.get(name).ok_or_else(|| NexusError::UnknownUpstream(format!("No upstream named '{}'", name))))
+            .transpose()?;
+
+        let by_url = endpoint
+            .map(|endpoint| {
+                let endpoint = normalize(endpoint);
+                self.upstreams
+                    .iter()
+                    .find(|u| u.url == endpoint)
+                    .ok_or_else(|| NexusError::UnknownUpstream(format!("'{}' is not a configured upstream", endpoint)))
+            })
+            .transpose()?;
+
+        match (by_name, by_url) {
+            (Some(a), Some(b)) if a.name != b.name => Err(NexusError::UnknownUpstream(format!(
+                "Upstream '{}' does not match endpoint '{}'",
+                a.name, b.url
+            ))),
+            (Some(upstream), _) | (None, Some(upstream)) => Ok(upstream),
+            (None, None) => self.default_upstream()
+                .ok_or_else(|| NexusError::UnknownUpstream("No Wazuh upstream is configured".to_string())),
+        }
+    }
+}
+
+fn normalize(url: &str) -> &str {
+    url.trim().trim_end_matches('/')
+}
+
+/// `NEXUS_UPSTREAM_<NAME>` with the name upper-cased and `-` turned into `_`.
+pub fn env_prefix(name: &str) -> String {
+    format!("NEXUS_UPSTREAM_{}", name.to_ascii_uppercase().replace('-', "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstream(name: &str, url: &str) -> Upstream {
+        Upstream { name: name.to_string(), url: url.to_string(), tls: TlsConfig::default() }
+    }
+
+    fn registry() -> UpstreamRegistry {
+        UpstreamRegistry::new(
+            vec![upstream("prod", "https://wazuh:55000/"), upstream("lab", "https://lab:55000")],
+            Some("lab"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_by_name_url_and_default() {
+        let registry = registry();
+        assert_eq!(registry.resolve(Some("prod"), None).unwrap().name, "prod");
+        assert_eq!(registry.resolve(None, Some("https://wazuh:55000")).unwrap().name, "prod");
+        assert_eq!(registry.resolve(None, Some("https://wazuh:55000/")).unwrap().name, "prod");
+        assert_eq!(registry.resolve(None, None).unwrap().name, "lab");
+    }
+
+    #[test]
+    fn test_resolve_rejects_unknown_targets() {
+        let registry = registry();
+        assert!(registry.resolve(Some("staging"), None).is_err());
+        assert!(registry.resolve(None, Some("http://169.254.169.254")).is_err());
+        assert!(registry.resolve(None, Some("https://wazuh:55000/agents")).is_err());
+        assert!(registry.resolve(Some("lab"), Some("https://wazuh:55000")).is_err());
+        assert!(UpstreamRegistry::default().resolve(None, None).is_err());
+    }
+
+    #[test]
+    fn test_new_validates_entries() {
+        assert!(UpstreamRegistry::new(vec![upstream("a", "ftp://wazuh")], None).is_err());
+        assert!(UpstreamRegistry::new(vec![upstream("a b", "https://wazuh")], None).is_err());
+        assert!(UpstreamRegistry::new(vec![upstream("a", "https://x"), upstream("a", "https://y")], None).is_err());
+        assert!(UpstreamRegistry::new(vec![upstream("a", "https://x")], Some("b")).is_err());
+    }
+}
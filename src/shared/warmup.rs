@@ -0,0 +1,97 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use super::sessions::{with_reauth, Session};
+use super::state::AppState;
+
+/// Endpoints prefetched so callers do not wait on slow Wazuh queries such as
+/// `mitre/techniques`: once with the service account for internal jobs (WQL
+/// reports, the agent watcher) and once per live user session.
+#[derive(Debug, Clone, Default)]
+pub struct WarmupConfig {
+    /// Wazuh paths relative to `WAZUH_URL`, e.g. `manager/stats/weekly`.
+    pub paths: Vec<String>,
+    /// Re-run the warm-up on this interval; `None` warms up once at startup.
+    pub interval: Option<Duration>,
+}
+
+impl WarmupConfig {
+    /// Reads `NEXUS_WARMUP_PATHS` (comma separated) and `NEXUS_WARMUP_INTERVAL`
+    /// (seconds, `0` or unset for startup only).
+    pub fn from_env() -> Result<Self, String> {
+        let paths = env::var("NEXUS_WARMUP_PATHS")
+            .map(|v| {
+                v.split(',')
+                    .map(|p| p.trim().trim_start_matches('/').to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let interval = match env::var("NEXUS_WARMUP_INTERVAL") {
+            Ok(value) => {
+                let secs: u64 = value
+                    .parse()
+                    .map_err(|_| format!("NEXUS_WARMUP_INTERVAL must be a number, got '{}'", value))?;
+                Some(Duration::from_secs(secs)).filter(|d| !d.is_zero())
+            }
+            Err(_) => None,
+        };
+
+        Ok(Self { paths, interval })
+    }
+}
+
+/// Starts the warm-up task, or returns `None` when no paths are configured.
+///
+/// Entries are cached under the token they were fetched with, so each caller
+/// only hits what was fetched with its own token: the service account session
+/// serves WQL report jobs and other internal callers, and every live session
+/// from `POST /session` is warmed with its own token. They are deliberately
+/// not shared across tokens: a response fetched with the service account may
+/// include data a user's Wazuh role does not allow them to see. Requests that
+/// pass a raw Wazuh token are not known in advance and are not warmed. Each
+/// session logs in again by itself when Wazuh rejects its token.
+pub fn spawn(state: AppState) -> Option<JoinHandle<()>> {
+    if state.config.warmup.paths.is_empty() {
+        return None;
+    }
+
+    Some(tokio::spawn(async move {
+        loop {
+            match state.service_session().await {
+                Ok(session) => warm_up_once(&state, &session).await,
+                Err(e) => println!("Cache warm-up skipped for the service account: {}", e),
+            }
+            for session in state.sessions.live() {
+                warm_up_once(&state, &session).await;
+            }
+
+            match state.config.warmup.interval {
+                Some(interval) => tokio::time::sleep(interval).await,
+                None => break,
+            }
+        }
+    }))
+}
+
+async fn warm_up_once(state: &AppState, session: &Arc<Session>) {
+    let Some(base) = state.config.upstreams.get(&session.upstream).map(|u| u.url.as_str()) else {
+        return;
+    };
+
+    for path in &state.config.warmup.paths {
+        let url = format!("{}/{}", base, path);
+        let result = with_reauth(&state.client, Some(session), "", |token| {
+            let url = &url;
+            async move { state.client.prefetch(url, Some(&token)).await }
+        })
+        .await;
+
+        match result {
+            Ok(()) => println!("Warmed up cache for {} as {}", url, session.username),
+            Err(e) => println!("Cache warm-up failed for {} as {}: {}", url, session.username, e),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use super::common::{collect_wazuh_items, WazuhRequest};
+use super::error::NexusError;
+use super::state::AppState;
+use super::tenants::Tenant;
+
+/// Background polling of agent statuses on the default upstream.
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    /// Poll interval; the watcher does not run when `None`.
+    pub interval: Option<Duration>,
+    /// A `never_connected` agent registered longer ago than this is reported once.
+    pub never_connected_after: Duration,
+    /// Events kept for `/events/agents`.
+    pub history: usize,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            never_connected_after: Duration::from_secs(24 * 3600),
+            history: 10_000,
+        }
+    }
+}
+
+impl WatcherConfig {
+    /// Reads `NEXUS_AGENT_WATCH_INTERVAL` (seconds, unset or `0` disables),
+    /// `NEXUS_AGENT_NEVER_CONNECTED_AFTER` (seconds) and `NEXUS_AGENT_EVENT_HISTORY`.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Some(secs) = env_number("NEXUS_AGENT_WATCH_INTERVAL")? {
+            config.interval = Some(Duration::from_secs(secs)).filter(|d| !d.is_zero());
+        }
+        if let Some(secs) = env_number("NEXUS_AGENT_NEVER_CONNECTED_AFTER")? {
+            config.never_connected_after = Duration::from_secs(secs);
+        }
+        if let Some(events) = env_number("NEXUS_AGENT_EVENT_HISTORY")? {
+            config.history = events as usize;
+        }
+        Ok(config)
+    }
+}
+
+fn env_number(name: &str) -> Result<Option<u64>, String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.parse().map_err(|_| format!("{} must be a number, got '{}'", name, value)))
+        .transpose()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentEventKind {
+    /// `status` changed between two polls, e.g. `active` → `disconnected`.
+    StatusChanged,
+    /// The agent showed up after the first poll.
+    Registered,
+    /// The agent is no longer listed.
+    Removed,
+    /// Still `never_connected` past `never_connected_after`.
+    NeverConnected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentEvent {
+    /// Increasing sequence number, also the SSE event id.
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    pub upstream: String,
+    pub agent_id: String,
+    pub agent_name: Option<String>,
+    pub groups: Vec<String>,
+    pub kind: AgentEventKind,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl AgentEvent {
+    /// Whether a caller confined to `tenant` may see this event.
+    pub fn visible_to(&self, tenant: Option<&Tenant>) -> bool {
+        tenant.is_none_or(|t| self.groups.iter().any(|g| t.owns(g)))
+    }
+}
+
+/// Which events `/events/agents` returns. Times are RFC 3339.
+#[derive(Debug, Default, Deserialize)]
+pub struct EventFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub agent_id: Option<String>,
+    pub kind: Option<AgentEventKind>,
+    /// Only events after this sequence number, e.g. a resuming SSE client's `Last-Event-ID`.
+    pub after_id: Option<u64>,
+    /// Newest events returned from the history; 500 when omitted.
+    pub limit: Option<usize>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &AgentEvent) -> bool {
+        self.since.is_none_or(|since| event.at >= since)
+            && self.until.is_none_or(|until| event.at <= until)
+            && self.agent_id.as_ref().is_none_or(|id| *id == event.agent_id)
+            && self.kind.is_none_or(|kind| kind == event.kind)
+            && self.after_id.is_none_or(|after| event.id > after)
+    }
+}
+
+/// What the previous poll saw of one agent.
+struct Seen {
+    name: Option<String>,
+    groups: Vec<String>,
+    status: Option<String>,
+    never_connected_reported: bool,
+}
+
+struct History {
+    // `None` until the first poll, which only records a baseline.
+    snapshot: Option<HashMap<String, Seen>>,
+    events: VecDeque<AgentEvent>,
+    next_id: u64,
+}
+
+/// Compares successive agent listings and keeps the resulting events.
+pub struct AgentWatcher {
+    config: WatcherConfig,
+    history: Mutex<History>,
+    live: broadcast::Sender<AgentEvent>,
+}
+
+impl AgentWatcher {
+    pub fn new(config: WatcherConfig) -> Self {
+        let (live, _) = broadcast::channel(256);
+        Self {
+            config,
+            history: Mutex::new(History {
+                snapshot: None,
+                events: VecDeque::new(),
+                next_id: 1,
+            }),
+            live,
+        }
+    }
+
+    /// Diffs `agents` (items of `GET /agents`) against the previous poll,
+    /// stores and broadcasts the events, and returns them.
+    pub fn observe(&self, upstream: &str, agents: &[Value], now: DateTime<Utc>) -> Vec<AgentEvent> {
+        let mut history = self.history.lock().unwrap();
+        let first_poll = history.snapshot.is_none();
+        let mut previous = history.snapshot.take().unwrap_or_default();
+        let mut current = HashMap::new();
+        let mut events = Vec::new();
+
+        let mut event = |id: &str, seen: &Seen, kind, from: Option<String>, to: Option<String>| {
+            events.push(AgentEvent {
+                id: 0,
+                at: now,
+                upstream: upstream.to_string(),
+                agent_id: id.to_string(),
+                agent_name: seen.name.clone(),
+                groups: seen.groups.clone(),
+                kind,
+                from,
+                to,
+            });
+        };
+
+        for agent in agents {
+            let Some(id) = agent["id"].as_str() else { continue };
+            let before = previous.remove(id);
+            let mut seen = Seen {
+                name: agent["name"].as_str().map(String::from),
+                groups: groups_of(agent),
+                status: agent["status"].as_str().map(String::from),
+                never_connected_reported: false,
+            };
+
+            match &before {
+                None if !first_poll => event(id, &seen, AgentEventKind::Registered, None, seen.status.clone()),
+                Some(before) if before.status != seen.status => {
+                    event(id, &seen, AgentEventKind::StatusChanged, before.status.clone(), seen.status.clone())
+                }
+                _ => {}
+            }
+
+            seen.never_connected_reported = before.is_some_and(|b| b.never_connected_reported && b.status == seen.status);
+            if seen.status.as_deref() == Some("never_connected") && !seen.never_connected_reported && self.registered_long_ago(agent, now) {
+                event(id, &seen, AgentEventKind::NeverConnected, None, seen.status.clone());
+                seen.never_connected_reported = true;
+            }
+
+            current.insert(id.to_string(), seen);
+        }
+
+        for (id, before) in previous {
+            event(&id, &before, AgentEventKind::Removed, before.status.clone(), None);
+        }
+
+        history.snapshot = Some(current);
+        for event in &mut events {
+            event.id = history.next_id;
+            history.next_id += 1;
+            history.events.push_back(event.clone());
+            // Nobody listening is fine
+            let _ = self.live.send(event.clone());
+        }
+        while history.events.len() > self.config.history {
+            history.events.pop_front();
+        }
+        events
+    }
+
+    fn registered_long_ago(&self, agent: &Value, now: DateTime<Utc>) -> bool {
+        let Some(added) = agent["dateAdd"].as_str().and_then(|d| DateTime::parse_from_rfc3339(d).ok()) else {
+            return false;
+        };
+        let age = now.signed_duration_since(added.with_timezone(&Utc));
+        age.to_std().is_ok_and(|age| age >= self.config.never_connected_after)
+    }
+
+    /// Stored events matching `filter`, oldest first.
+    pub fn events(&self, filter: &EventFilter, tenant: Option<&Tenant>) -> Vec<AgentEvent> {
+        let history = self.history.lock().unwrap();
+        let matching: Vec<AgentEvent> = history.events
+            .iter()
+            .filter(|e| filter.matches(e) && e.visible_to(tenant))
+            .cloned()
+            .collect();
+        let skip = matching.len().saturating_sub(filter.limit.unwrap_or(500));
+        matching.into_iter().skip(skip).collect()
+    }
+
+    /// Events from now on, for streaming.
+    pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
+        self.live.subscribe()
+    }
+}
+
+fn groups_of(agent: &Value) -> Vec<String> {
+    agent["group"]
+        .as_array()
+        .map(|groups| groups.iter().filter_map(|g| g.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+/// Starts the poller, or returns `None` when `NEXUS_AGENT_WATCH_INTERVAL` is unset.
+pub fn spawn(state: AppState) -> Option<JoinHandle<()>> {
+    let interval = state.config.watcher.interval?;
+
+    Some(tokio::spawn(async move {
+        loop {
+            if let Err(e) = poll_once(&state).await {
+                println!("Agent status poll failed: {}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+/// Lists every agent on the default upstream with the service account and
+/// feeds the listing to the watcher.
+pub async fn poll_once(state: &AppState) -> Result<Vec<AgentEvent>, NexusError> {
+    let session = state.service_session().await?;
+    let request = WazuhRequest {
+        upstream: Some(session.upstream.clone()),
+        token: session.token(),
+        session: Some(session.clone()),
+        query: [("select", "id,name,status,group,dateAdd")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+            .collect(),
+        ..Default::default()
+    };
+
+    let agents = collect_wazuh_items(state, request, "agents", false).await?;
+    let events = state.watcher.observe(&session.upstream, &agents, Utc::now());
+    if !events.is_empty() {
+        println!("Agent watcher recorded {} events", events.len());
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap()
+    }
+
+    fn agent(id: &str, status: &str) -> Value {
+        json!({ "id": id, "name": format!("host-{}", id), "status": status, "group": ["web"], "dateAdd": "2024-03-01T00:00:00Z" })
+    }
+
+    fn watcher() -> AgentWatcher {
+        AgentWatcher::new(WatcherConfig {
+            never_connected_after: Duration::from_secs(6 * 3600),
+            ..WatcherConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_first_poll_is_a_baseline() {
+        let watcher = watcher();
+        assert!(watcher.observe("default", &[agent("001", "active")], at(1)).is_empty());
+
+        let events = watcher.observe("default", &[agent("001", "disconnected"), agent("002", "pending")], at(2));
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, AgentEventKind::StatusChanged);
+        assert_eq!(events[0].from.as_deref(), Some("active"));
+        assert_eq!(events[0].to.as_deref(), Some("disconnected"));
+        assert_eq!(events[1].kind, AgentEventKind::Registered);
+        assert_eq!((events[0].id, events[1].id), (1, 2));
+    }
+
+    #[test]
+    fn test_removed_agents() {
+        let watcher = watcher();
+        watcher.observe("default", &[agent("001", "active"), agent("002", "active")], at(1));
+
+        let events = watcher.observe("default", &[agent("001", "active")], at(2));
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AgentEventKind::Removed);
+        assert_eq!(events[0].agent_id, "002");
+        assert_eq!(events[0].groups, vec!["web"], "Removed agents stay visible to their tenant");
+    }
+
+    #[test]
+    fn test_never_connected_is_reported_once_when_aged() {
+        let watcher = watcher();
+        assert!(watcher.observe("default", &[agent("003", "never_connected")], at(1)).is_empty());
+
+        let events = watcher.observe("default", &[agent("003", "never_connected")], at(7));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AgentEventKind::NeverConnected);
+
+        assert!(watcher.observe("default", &[agent("003", "never_connected")], at(8)).is_empty());
+    }
+
+    #[test]
+    fn test_history_filters_and_limits() {
+        let watcher = watcher();
+        watcher.observe("default", &[agent("001", "active")], at(1));
+        watcher.observe("default", &[agent("001", "disconnected")], at(2));
+        watcher.observe("default", &[agent("001", "active")], at(3));
+        watcher.observe("default", &[agent("001", "disconnected")], at(4));
+
+        let since = EventFilter { since: Some(at(3)), ..Default::default() };
+        assert_eq!(watcher.events(&since, None).len(), 2);
+        let newest = watcher.events(&EventFilter { limit: Some(1), ..Default::default() }, None);
+        assert_eq!(newest.len(), 1);
+        assert_eq!(newest[0].at, at(4));
+
+        let other_tenant = Tenant { name: "globex".to_string(), groups: ["globex".to_string()].into() };
+        assert!(watcher.events(&EventFilter::default(), Some(&other_tenant)).is_empty());
+    }
+}
@@ -0,0 +1,619 @@
+use native_tls::{Certificate, TlsConnector as NativeTlsConnector};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_native_tls::TlsConnector;
+
+/// Responses used to be capped at 10MB; framed gateways can be given more room.
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Connections kept to the gateway when `pool_size` is not set.
+const DEFAULT_POOL_SIZE: usize = 8;
+
+/// Retries are backed off by `2^n` ms plus up to 1s of jitter, so 16 retries
+/// already wait about two and a half minutes in total (besides the time spent
+/// on each attempt), and a larger `n` would eventually overflow the backoff.
+const MAX_RETRIES_LIMIT: u32 = 16;
+
+/// Signing scheme spoken with the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WqlProtocol {
+    /// `SHA256(data || key)` with a single key pair, as spoken by older gateways.
+    V1,
+    /// HMAC-SHA256 with a `key_id` on every request and response.
+    V2,
+    /// Tries v2 and falls back to v1 when the gateway answers in v1. Without a
+    /// `client_key_id` only v1 is spoken.
+    #[default]
+    Auto,
+}
+
+impl WqlProtocol {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "v1" | "1" => Some(Self::V1),
+            "v2" | "2" => Some(Self::V2),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// How requests and responses are delimited on the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WqlFraming {
+    /// One JSON request per connection; the response ends when the gateway
+    /// closes the socket.
+    #[default]
+    Legacy,
+    /// Length-prefixed frames, so a connection and its gateway session carry
+    /// many request/response pairs.
+    Framed,
+}
+
+/// Compression applied to the request frames nexus sends. Responses may use
+/// any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WqlCompression {
+    #[default]
+    None,
+    Gzip,
+}
+
+/// A response verification key; v2 responses name the key they were signed with.
+#[derive(Clone)]
+pub struct WqlKey {
+    /// Unset for a key only used with v1.
+    pub id: Option<String>,
+    pub secret: String,
+}
+
+impl fmt::Debug for WqlKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WqlKey").field("id", &self.id).field("secret", &"<redacted>").finish()
+    }
+}
+
+/// Connection to the WQL gateway behind `/wql/:group` reports.
+#[derive(Clone)]
+pub struct WqlConfig {
+    /// `host:port` of the gateway; host names are resolved on every connection.
+    pub address: String,
+    /// Name sent as SNI and checked against the gateway certificate. Defaults
+    /// to the host part of `address`.
+    pub server_name: String,
+    /// Skip certificate verification, for gateways with self-signed certificates.
+    pub insecure: bool,
+    /// PEM certificate trusted in addition to the system roots.
+    pub ca_file: Option<PathBuf>,
+    pub protocol: WqlProtocol,
+    pub client_id: String,
+    /// Shared secret signing our requests.
+    pub client_key: String,
+    /// Sent as `key_id` in v2 so the gateway can pick the key during rotation.
+    pub client_key_id: Option<String>,
+    /// Keys the gateway may sign its responses with; several are active while
+    /// a key is being rotated.
+    pub server_keys: Vec<WqlKey>,
+    pub connect_timeout: Duration,
+    /// Longest wait for the next chunk of a response.
+    pub read_timeout: Duration,
+    /// Longest time to write one request.
+    pub request_timeout: Duration,
+    /// Attempts after the first one, each on a new connection.
+    pub max_retries: u32,
+    /// Largest accepted difference between a response timestamp and our clock.
+    pub max_skew: Duration,
+    pub framing: WqlFraming,
+    pub compression: WqlCompression,
+    /// Largest response accepted, after decompression.
+    pub max_response_bytes: usize,
+    /// Most connections open at once, shared by all report jobs; further
+    /// requests wait for one to be returned. Also how many agents a report
+    /// queries at once. Connections are only reused with `Framed`; with
+    /// `Legacy` each request still opens its own.
+    pub pool_size: usize,
+    /// Idle connections older than this are closed instead of reused.
+    pub pool_idle_timeout: Duration,
+    /// Connections are closed once this old, even when busy, so gateway
+    /// sessions and TLS keys are renewed.
+    pub pool_max_lifetime: Duration,
+    /// Longest wait for a free connection when the pool is exhausted.
+    pub pool_acquire_timeout: Duration,
+}
+
+impl fmt::Debug for WqlConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WqlConfig")
+            .field("address", &self.address)
+            .field("server_name", &self.server_name)
+            .field("insecure", &self.insecure)
+            .field("ca_file", &self.ca_file)
+            .field("protocol", &self.protocol)
+            .field("client_id", &self.client_id)
+            .field("client_key", &"<redacted>")
+            .field("client_key_id", &self.client_key_id)
+            .field("server_keys", &self.server_keys)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("max_retries", &self.max_retries)
+            .field("max_skew", &self.max_skew)
+            .field("framing", &self.framing)
+            .field("compression", &self.compression)
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("pool_size", &self.pool_size)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("pool_max_lifetime", &self.pool_max_lifetime)
+            .field("pool_acquire_timeout", &self.pool_acquire_timeout)
+            .finish()
+    }
+}
+
+/// Raw settings as found in the `NEXUS_WQL_CONFIG` file, before environment
+/// overrides and validation. Timeouts are in seconds; `server_keys` maps key
+/// ids to secrets.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WqlSettings {
+    pub address: Option<String>,
+    pub server_name: Option<String>,
+    pub tls_insecure: Option<bool>,
+    pub tls_ca_file: Option<PathBuf>,
+    pub protocol: Option<WqlProtocol>,
+    pub client_id: Option<String>,
+    pub client_key: Option<String>,
+    pub client_key_file: Option<PathBuf>,
+    pub client_key_id: Option<String>,
+    pub server_key: Option<String>,
+    pub server_key_file: Option<PathBuf>,
+    pub server_key_id: Option<String>,
+    pub server_keys: Option<BTreeMap<String, String>>,
+    pub connect_timeout_secs: Option<u64>,
+    pub read_timeout_secs: Option<u64>,
+    pub request_timeout_secs: Option<u64>,
+    pub max_retries: Option<u32>,
+    pub max_skew_secs: Option<u64>,
+    pub framing: Option<WqlFraming>,
+    pub compression: Option<WqlCompression>,
+    pub max_response_bytes: Option<usize>,
+    pub pool_size: Option<usize>,
+    pub pool_idle_timeout_secs: Option<u64>,
+    pub pool_max_lifetime_secs: Option<u64>,
+    pub pool_acquire_timeout_secs: Option<u64>,
+}
+
+impl WqlSettings {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read WQL config {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid WQL config {}: {}", path.display(), e))
+    }
+
+    /// Overrides the file values with `NEXUS_WQL_ADDR`, `_SERVER_NAME`,
+    /// `_TLS_INSECURE`, `_TLS_CA_FILE`, `_PROTOCOL`, `_CLIENT_ID`,
+    /// `_CLIENT_KEY(_FILE)`, `_CLIENT_KEY_ID`, `_SERVER_KEY(_FILE)`,
+    /// `_SERVER_KEY_ID`, `_SERVER_KEYS` (`id=secret,id=secret`),
+    /// `_CONNECT_TIMEOUT`, `_READ_TIMEOUT`, `_REQUEST_TIMEOUT`, `_MAX_RETRIES` and
+    /// `_MAX_SKEW` (seconds), `_FRAMING` (`legacy` or `framed`), `_COMPRESSION`
+    /// (`none` or `gzip`), `_MAX_RESPONSE_BYTES`, `_POOL_SIZE` and
+    /// `_POOL_IDLE_TIMEOUT`, `_POOL_MAX_LIFETIME`, `_POOL_ACQUIRE_TIMEOUT` (seconds).
+    fn merge_env(&mut self) -> Result<(), String> {
+        let var = |suffix: &str| {
+            let name = format!("NEXUS_WQL_{}", suffix);
+            env::var(&name).ok().filter(|v| !v.is_empty()).map(|v| (name, v))
+        };
+        let number = |suffix: &str| {
+            var(suffix)
+                .map(|(name, value)| value.parse::<u64>().map_err(|_| format!("{} must be a number, got '{}'", name, value)))
+                .transpose()
+        };
+
+        if let Some((_, v)) = var("ADDR") { self.address = Some(v); }
+        if let Some((_, v)) = var("SERVER_NAME") { self.server_name = Some(v); }
+        if let Some((name, v)) = var("TLS_INSECURE") {
+            self.tls_insecure = Some(match v.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => true,
+                "0" | "false" | "no" => false,
+                _ => return Err(format!("{} must be true or false, got '{}'", name, v)),
+            });
+        }
+        if let Some((_, v)) = var("TLS_CA_FILE") { self.tls_ca_file = Some(PathBuf::from(v)); }
+        if let Some((name, v)) = var("PROTOCOL") {
+            self.protocol = Some(WqlProtocol::parse(&v)
+                .ok_or_else(|| format!("{} must be v1, v2 or auto, got '{}'", name, v))?);
+        }
+        if let Some((_, v)) = var("CLIENT_ID") { self.client_id = Some(v); }
+        if let Some((_, v)) = var("CLIENT_KEY") { self.client_key = Some(v); }
+        if let Some((_, v)) = var("CLIENT_KEY_FILE") { self.client_key_file = Some(PathBuf::from(v)); }
+        if let Some((_, v)) = var("CLIENT_KEY_ID") { self.client_key_id = Some(v); }
+        if let Some((_, v)) = var("SERVER_KEY") { self.server_key = Some(v); }
+        if let Some((_, v)) = var("SERVER_KEY_FILE") { self.server_key_file = Some(PathBuf::from(v)); }
+        if let Some((_, v)) = var("SERVER_KEY_ID") { self.server_key_id = Some(v); }
+        if let Some((name, v)) = var("SERVER_KEYS") {
+            let keys = v
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    entry.split_once('=')
+                        .map(|(id, secret)| (id.trim().to_string(), secret.trim().to_string()))
+                        .ok_or_else(|| format!("{} entries must look like id=secret", name))
+                })
+                .collect::<Result<_, _>>()?;
+            self.server_keys = Some(keys);
+        }
+        if let Some(secs) = number("CONNECT_TIMEOUT")? { self.connect_timeout_secs = Some(secs); }
+        if let Some(secs) = number("READ_TIMEOUT")? { self.read_timeout_secs = Some(secs); }
+        if let Some(secs) = number("REQUEST_TIMEOUT")? { self.request_timeout_secs = Some(secs); }
+        if let Some(secs) = number("MAX_SKEW")? { self.max_skew_secs = Some(secs); }
+        if let Some((name, v)) = var("FRAMING") {
+            self.framing = Some(match v.trim().to_ascii_lowercase().as_str() {
+                "legacy" => WqlFraming::Legacy,
+                "framed" => WqlFraming::Framed,
+                _ => return Err(format!("{} must be legacy or framed, got '{}'", name, v)),
+            });
+        }
+        if let Some((name, v)) = var("COMPRESSION") {
+            self.compression = Some(match v.trim().to_ascii_lowercase().as_str() {
+                "none" => WqlCompression::None,
+                "gzip" => WqlCompression::Gzip,
+                _ => return Err(format!("{} must be none or gzip, got '{}'", name, v)),
+            });
+        }
+        if let Some(bytes) = number("MAX_RESPONSE_BYTES")? {
+            self.max_response_bytes = Some(usize::try_from(bytes).unwrap_or(usize::MAX));
+        }
+        if let Some(size) = number("POOL_SIZE")? {
+            self.pool_size = Some(usize::try_from(size).unwrap_or(usize::MAX));
+        }
+        if let Some(secs) = number("POOL_IDLE_TIMEOUT")? { self.pool_idle_timeout_secs = Some(secs); }
+        if let Some(secs) = number("POOL_MAX_LIFETIME")? { self.pool_max_lifetime_secs = Some(secs); }
+        if let Some(secs) = number("POOL_ACQUIRE_TIMEOUT")? { self.pool_acquire_timeout_secs = Some(secs); }
+        if let Some(retries) = number("MAX_RETRIES")? {
+            self.max_retries = Some(u32::try_from(retries).unwrap_or(u32::MAX));
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.address.is_none()
+            && self.client_id.is_none()
+            && self.client_key.is_none()
+            && self.client_key_file.is_none()
+            && self.server_key.is_none()
+            && self.server_key_file.is_none()
+            && self.server_keys.is_none()
+    }
+}
+
+impl WqlConfig {
+    /// Reads the optional JSON file named by `NEXUS_WQL_CONFIG`, then the
+    /// `NEXUS_WQL_*` variables on top of it. WQL reports are disabled when
+    /// neither names a gateway or credentials.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let mut settings = match env::var("NEXUS_WQL_CONFIG").ok().filter(|p| !p.is_empty()) {
+            Some(path) => WqlSettings::from_file(Path::new(&path))?,
+            None => WqlSettings::default(),
+        };
+        settings.merge_env()?;
+        Self::from_settings(settings)
+    }
+
+    /// Validates `settings` and loads the key files. `None` when nothing is configured.
+    pub fn from_settings(settings: WqlSettings) -> Result<Option<Self>, String> {
+        if settings.is_empty() {
+            return Ok(None);
+        }
+
+        let address = settings.address
+            .ok_or("WQL gateway address (NEXUS_WQL_ADDR) is not set")?;
+        let host = split_address(&address)?;
+        let server_name = match settings.server_name {
+            Some(name) if name.trim().is_empty() => return Err("WQL server_name must not be empty".to_string()),
+            Some(name) => name,
+            None => host.to_string(),
+        };
+
+        let client_id = settings.client_id
+            .filter(|id| !id.trim().is_empty())
+            .ok_or("WQL client id (NEXUS_WQL_CLIENT_ID) is not set")?;
+        let client_key = secret("client key", "NEXUS_WQL_CLIENT_KEY", settings.client_key, settings.client_key_file)?
+            .ok_or("WQL client key (NEXUS_WQL_CLIENT_KEY or NEXUS_WQL_CLIENT_KEY_FILE) is not set")?;
+        let client_key_id = key_id("client", settings.client_key_id)?;
+
+        let mut server_keys = Vec::new();
+        let server_key_id = key_id("server", settings.server_key_id)?;
+        match secret("server key", "NEXUS_WQL_SERVER_KEY", settings.server_key, settings.server_key_file)? {
+            Some(secret) => server_keys.push(WqlKey { id: server_key_id, secret }),
+            None if server_key_id.is_some() => {
+                return Err("NEXUS_WQL_SERVER_KEY_ID is set without NEXUS_WQL_SERVER_KEY".to_string());
+            }
+            None => {}
+        }
+        for (id, secret) in settings.server_keys.unwrap_or_default() {
+            let id = key_id("server", Some(id))?;
+            if server_keys.iter().any(|k| k.id == id) {
+                return Err(format!("WQL server key id '{}' is configured twice", id.unwrap_or_default()));
+            }
+            if secret.is_empty() {
+                return Err(format!("WQL server key '{}' must not be empty", id.unwrap_or_default()));
+            }
+            server_keys.push(WqlKey { id, secret });
+        }
+        if server_keys.is_empty() {
+            return Err("WQL server key (NEXUS_WQL_SERVER_KEY, NEXUS_WQL_SERVER_KEY_FILE or NEXUS_WQL_SERVER_KEYS) is not set".to_string());
+        }
+
+        let protocol = settings.protocol.unwrap_or_default();
+        if protocol == WqlProtocol::V2 {
+            if client_key_id.is_none() {
+                return Err("WQL protocol v2 needs a client key id (NEXUS_WQL_CLIENT_KEY_ID)".to_string());
+            }
+            if server_keys.iter().all(|k| k.id.is_none()) {
+                return Err("WQL protocol v2 needs a server key with an id (NEXUS_WQL_SERVER_KEY_ID or NEXUS_WQL_SERVER_KEYS)".to_string());
+            }
+        }
+
+        let insecure = settings.tls_insecure.unwrap_or(false);
+        if insecure && settings.tls_ca_file.is_some() {
+            return Err("NEXUS_WQL_TLS_INSECURE cannot be combined with NEXUS_WQL_TLS_CA_FILE".to_string());
+        }
+
+        let max_retries = settings.max_retries.unwrap_or(5);
+        if max_retries > MAX_RETRIES_LIMIT {
+            return Err(format!("WQL max_retries must be at most {}, got {}", MAX_RETRIES_LIMIT, max_retries));
+        }
+
+        let config = Self {
+            address,
+            server_name,
+            insecure,
+            ca_file: settings.tls_ca_file,
+            protocol,
+            client_id,
+            client_key,
+            client_key_id,
+            server_keys,
+            connect_timeout: timeout("connect", settings.connect_timeout_secs, 30)?,
+            read_timeout: timeout("read", settings.read_timeout_secs, 60)?,
+            request_timeout: timeout("request", settings.request_timeout_secs, 300)?,
+            max_retries,
+            max_skew: match settings.max_skew_secs.unwrap_or(300) {
+                0 => return Err("WQL max_skew must be at least one second".to_string()),
+                secs => Duration::from_secs(secs),
+            },
+            framing: settings.framing.unwrap_or_default(),
+            compression: settings.compression.unwrap_or_default(),
+            max_response_bytes: settings.max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES),
+            pool_size: match settings.pool_size.unwrap_or(DEFAULT_POOL_SIZE) {
+                0 => return Err("WQL pool_size must be at least 1".to_string()),
+                size => size,
+            },
+            pool_idle_timeout: timeout("pool idle", settings.pool_idle_timeout_secs, 90)?,
+            pool_max_lifetime: match settings.pool_max_lifetime_secs.unwrap_or(600) {
+                0 => return Err("WQL pool_max_lifetime must be at least one second".to_string()),
+                secs => Duration::from_secs(secs),
+            },
+            pool_acquire_timeout: timeout("pool acquire", settings.pool_acquire_timeout_secs, 60)?,
+        };
+        if config.max_response_bytes == 0 || config.max_response_bytes > u32::MAX as usize {
+            return Err(format!(
+                "WQL max_response_bytes must be between 1 and {} (the largest frame), got {}",
+                u32::MAX, config.max_response_bytes
+            ));
+        }
+        if config.framing == WqlFraming::Legacy && config.compression != WqlCompression::None {
+            return Err("NEXUS_WQL_COMPRESSION needs NEXUS_WQL_FRAMING=framed".to_string());
+        }
+        // v1 responses carry no request nonce, so on a connection that keeps its
+        // session two identical answers look like a replay
+        if config.framing == WqlFraming::Framed && (config.protocol == WqlProtocol::V1 || config.client_key_id.is_none()) {
+            return Err("NEXUS_WQL_FRAMING=framed needs protocol v2 (NEXUS_WQL_CLIENT_KEY_ID), not v1".to_string());
+        }
+        // Surfaces an unreadable CA file at startup instead of on the first report
+        config.tls_connector()?;
+        Ok(Some(config))
+    }
+
+    pub fn tls_connector(&self) -> Result<TlsConnector, String> {
+        let mut builder = NativeTlsConnector::builder();
+        if self.insecure {
+            builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(path) = &self.ca_file {
+            let pem = fs::read(path)
+                .map_err(|e| format!("Cannot read WQL CA file {}: {}", path.display(), e))?;
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|e| format!("Invalid WQL CA file {}: {}", path.display(), e))?;
+            builder.add_root_certificate(certificate);
+        }
+        builder.build()
+            .map(TlsConnector::from)
+            .map_err(|e| format!("Cannot build the WQL TLS connector: {}", e))
+    }
+}
+
+/// Returns the host of `host:port` (without IPv6 brackets).
+fn split_address(address: &str) -> Result<&str, String> {
+    let invalid = || format!("WQL address must look like host:port, got '{}'", address);
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 && !host.is_empty() && !host.contains(char::is_whitespace) => Ok(host),
+        _ => Err(invalid()),
+    }
+}
+
+/// A key given inline or as a file (trimmed), but not both.
+fn secret(what: &str, var: &str, inline: Option<String>, file: Option<PathBuf>) -> Result<Option<String>, String> {
+    let key = match (inline, file) {
+        (Some(_), Some(_)) => return Err(format!("Set only one of {} and {}_FILE", var, var)),
+        (Some(key), None) => key,
+        (None, Some(path)) => fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read WQL {} file {}: {}", what, path.display(), e))?
+            .trim()
+            .to_string(),
+        (None, None) => return Ok(None),
+    };
+    if key.is_empty() {
+        return Err(format!("WQL {} must not be empty", what));
+    }
+    Ok(Some(key))
+}
+
+/// Key ids travel in JSON and logs, so they are kept to a printable token.
+fn key_id(whose: &str, id: Option<String>) -> Result<Option<String>, String> {
+    match id {
+        Some(id) if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c)) => Err(format!(
+            "WQL {} key id '{}' may only contain letters, digits, '-', '_', '.' and ':'",
+            whose, id
+        )),
+        id => Ok(id),
+    }
+}
+
+fn timeout(what: &str, secs: Option<u64>, default: u64) -> Result<Duration, String> {
+    match secs.unwrap_or(default) {
+        0 => Err(format!("WQL {} timeout must be at least one second", what)),
+        secs => Ok(Duration::from_secs(secs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn settings() -> WqlSettings {
+        WqlSettings {
+            address: Some("gateway.internal:8080".to_string()),
+            client_id: Some("client1".to_string()),
+            client_key: Some("client-secret".to_string()),
+            server_key: Some("server-secret".to_string()),
+            ..WqlSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_unconfigured_transport_is_disabled() {
+        assert!(WqlConfig::from_settings(WqlSettings::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_defaults_and_server_name() {
+        let config = WqlConfig::from_settings(settings()).unwrap().unwrap();
+        assert_eq!(config.server_name, "gateway.internal");
+        assert_eq!(config.connect_timeout, Duration::from_secs(30));
+        assert_eq!(config.read_timeout, Duration::from_secs(60));
+        assert_eq!(config.request_timeout, Duration::from_secs(300));
+        assert_eq!(config.max_retries, 5);
+        assert_eq!(config.max_skew, Duration::from_secs(300));
+        assert_eq!(config.framing, WqlFraming::Legacy);
+        assert_eq!(config.max_response_bytes, 10 * 1024 * 1024);
+        assert_eq!(config.pool_size, 8);
+        assert_eq!(config.pool_idle_timeout, Duration::from_secs(90));
+        assert_eq!(config.pool_max_lifetime, Duration::from_secs(600));
+        assert!(!config.insecure);
+
+        let ipv6 = WqlConfig::from_settings(WqlSettings { address: Some("[::1]:8080".to_string()), ..settings() })
+            .unwrap()
+            .unwrap();
+        assert_eq!(ipv6.server_name, "::1");
+
+        let named = WqlConfig::from_settings(WqlSettings { server_name: Some("wql.example".to_string()), ..settings() })
+            .unwrap()
+            .unwrap();
+        assert_eq!(named.server_name, "wql.example");
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let cases = [
+            (WqlSettings { address: None, ..settings() }, "NEXUS_WQL_ADDR"),
+            (WqlSettings { address: Some("gateway".to_string()), ..settings() }, "host:port"),
+            (WqlSettings { address: Some("gateway:http".to_string()), ..settings() }, "host:port"),
+            (WqlSettings { client_id: None, ..settings() }, "NEXUS_WQL_CLIENT_ID"),
+            (WqlSettings { server_key: None, ..settings() }, "NEXUS_WQL_SERVER_KEY"),
+            (WqlSettings { client_key: Some(String::new()), ..settings() }, "must not be empty"),
+            (WqlSettings { client_key_file: Some(PathBuf::from("/nonexistent")), ..settings() }, "Set only one"),
+            (WqlSettings { read_timeout_secs: Some(0), ..settings() }, "read timeout"),
+            (WqlSettings { max_retries: Some(100), ..settings() }, "max_retries"),
+            (WqlSettings { max_skew_secs: Some(0), ..settings() }, "max_skew"),
+            (WqlSettings { max_response_bytes: Some(0), ..settings() }, "max_response_bytes"),
+            (WqlSettings { compression: Some(WqlCompression::Gzip), ..settings() }, "FRAMING=framed"),
+            (WqlSettings { framing: Some(WqlFraming::Framed), ..settings() }, "needs protocol v2"),
+            (
+                WqlSettings {
+                    framing: Some(WqlFraming::Framed),
+                    protocol: Some(WqlProtocol::V1),
+                    client_key_id: Some("c1".to_string()),
+                    ..settings()
+                },
+                "needs protocol v2",
+            ),
+            (WqlSettings { pool_size: Some(0), ..settings() }, "pool_size"),
+            (WqlSettings { pool_idle_timeout_secs: Some(0), ..settings() }, "pool idle timeout"),
+            (WqlSettings { pool_max_lifetime_secs: Some(0), ..settings() }, "pool_max_lifetime"),
+            (
+                WqlSettings { tls_insecure: Some(true), tls_ca_file: Some(PathBuf::from("ca.pem")), ..settings() },
+                "cannot be combined",
+            ),
+            (WqlSettings { tls_ca_file: Some(PathBuf::from("/nonexistent/ca.pem")), ..settings() }, "CA file"),
+            (WqlSettings { protocol: Some(WqlProtocol::V2), ..settings() }, "NEXUS_WQL_CLIENT_KEY_ID"),
+            (
+                WqlSettings { protocol: Some(WqlProtocol::V2), client_key_id: Some("c1".to_string()), ..settings() },
+                "server key with an id",
+            ),
+            (WqlSettings { client_key_id: Some("bad id".to_string()), ..settings() }, "may only contain"),
+            (WqlSettings { server_key: None, server_key_id: Some("s1".to_string()), ..settings() }, "without NEXUS_WQL_SERVER_KEY"),
+            (
+                WqlSettings {
+                    server_key_id: Some("s1".to_string()),
+                    server_keys: Some(BTreeMap::from([("s1".to_string(), "other".to_string())])),
+                    ..settings()
+                },
+                "configured twice",
+            ),
+        ];
+        for (settings, expected) in cases {
+            let err = WqlConfig::from_settings(settings).unwrap_err();
+            assert!(err.contains(expected), "'{}' should mention '{}'", err, expected);
+        }
+    }
+
+    #[test]
+    fn test_keys_and_settings_from_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("client.key");
+        fs::write(&key_path, "rotated-secret\n").unwrap();
+
+        let config_path = dir.path().join("wql.json");
+        let mut file = fs::File::create(&config_path).unwrap();
+        write!(
+            file,
+            r#"{{"address": "10.0.0.5:9443", "server_name": "wql-gateway", "client_id": "staging",
+                "client_key_file": {:?}, "server_key": "server-secret", "max_retries": 2}}"#,
+            key_path
+        )
+        .unwrap();
+
+        let config = WqlConfig::from_settings(WqlSettings::from_file(&config_path).unwrap()).unwrap().unwrap();
+        assert_eq!(config.client_key, "rotated-secret");
+        assert_eq!(config.server_name, "wql-gateway");
+        assert_eq!(config.max_retries, 2);
+        assert!(!format!("{:?}", config).contains("rotated-secret"));
+
+        fs::write(&config_path, r#"{"adress": "10.0.0.5:9443"}"#).unwrap();
+        let err = WqlSettings::from_file(&config_path).unwrap_err();
+        assert!(err.contains("unknown field"), "{}", err);
+    }
+}
@@ -0,0 +1,191 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use crate::create_router;
+use crate::shared::access::API_KEY_HEADER;
+use crate::shared::admin::ADMIN_TOKEN_HEADER;
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json, MOCK_ADMIN_TOKEN};
+
+/// State with a key store holding an `inventory` role (read-only agents,
+/// syscollector and groups, limited to the `web` group) and an `auditor` role
+/// (everything). Agent `001` is in `web`, `002` in `finance`.
+fn keyed_state(upstream: &MockUpstream) -> (AppState, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.json");
+    let roles = json!({
+        "roles": {
+            "inventory": { "modules": { "agents": "read", "syscollector": "read", "groups": "read" }, "groups": ["web"] },
+            "auditor": { "modules": { "*": "read" } }
+        }
+    });
+    std::fs::write(&path, roles.to_string()).unwrap();
+    upstream.agent_in_groups("001", &["web"]);
+    upstream.agent_in_groups("002", &["finance"]);
+
+    let state = upstream.app_state_configured(|config| config.access.key_file = Some(path));
+    (state, dir)
+}
+
+fn issue(state: &AppState, id: &str, role: &str) -> String {
+    state.access.as_ref().unwrap().issue(id, vec![role.to_string()], None).unwrap()
+}
+
+async fn post_with_key(app: &Router, path: &str, key: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(path)
+        .header("content-type", "application/json")
+        .header(API_KEY_HEADER, key)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    call(app, request).await
+}
+
+#[tokio::test]
+async fn test_routes_are_open_without_key_store() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = post_json(&app, "/rules", json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_missing_or_unknown_key_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let app = create_router(state);
+
+    let (status, error) = post_json(&app, "/syscollector/001/os", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["kind"], "unauthorized");
+
+    let (status, _) = post_with_key(&app, "/syscollector/001/os", "nxk_guess", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_role_grants_access_per_module() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let key = issue(&state, "grafana", "inventory");
+    let app = create_router(state);
+
+    let (status, _) = post_with_key(&app, "/syscollector/001/os", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, error) = post_with_key(&app, "/wql/web", &key, json!({ "report_type": "daily" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(error["kind"], "forbidden");
+
+    let (status, _) = post_with_key(&app, "/rules", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(upstream.hits("/rules"), 0);
+}
+
+#[tokio::test]
+async fn test_role_limits_group_routes() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let key = issue(&state, "web-team", "inventory");
+    let app = create_router(state);
+
+    let (status, _) = post_with_key(&app, "/groups/web/agents", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_with_key(&app, "/groups/finance/agents", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(upstream.hits("/groups/finance/agents"), 0);
+}
+
+#[tokio::test]
+async fn test_role_limits_agent_routes() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let key = issue(&state, "web-team", "inventory");
+    let app = create_router(state);
+
+    let (status, _) = post_with_key(&app, "/syscollector/001/os", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, error) = post_with_key(&app, "/syscollector/002/os", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(error["kind"], "forbidden");
+    assert_eq!(upstream.hits("/syscollector/002/os"), 0);
+}
+
+#[tokio::test]
+async fn test_role_limits_agent_and_group_listings() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let key = issue(&state, "web-team", "inventory");
+    let app = create_router(state);
+
+    let (status, listing) = post_with_key(&app, "/agents", &key, json!({ "token": "t", "query": { "agents_list": "001,002" } })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(upstream.requests().iter().any(|p| p.starts_with("/agents?") && p.contains("q=group%3Dweb")));
+    let ids: Vec<&str> = listing["data"]["affected_items"].as_array().unwrap().iter().filter_map(|a| a["id"].as_str()).collect();
+    assert_eq!(ids, vec!["001"], "The mock ignores q, so 002 is dropped afterwards");
+
+    let groups = json!({ "data": { "affected_items": [{ "name": "web" }, { "name": "finance" }], "total_affected_items": 2 }, "error": 0 });
+    upstream.respond_with("/groups", StatusCode::OK, &groups.to_string());
+    let (status, listing) = post_with_key(&app, "/groups", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listing["data"]["affected_items"], json!([{ "name": "web" }]));
+    assert_eq!(listing["data"]["total_affected_items"], 1);
+}
+
+#[tokio::test]
+async fn test_wildcard_role_and_exempt_routes() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let key = issue(&state, "auditor", "auditor");
+    let app = create_router(state);
+
+    let (status, _) = post_with_key(&app, "/groups/finance/agents", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_issues_and_revokes_keys() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = keyed_state(&upstream);
+    let app = create_router(state);
+    let admin = |method: Method, path: &str, body: Option<Value>| {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(ADMIN_TOKEN_HEADER, MOCK_ADMIN_TOKEN)
+            .header("content-type", "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+            .unwrap()
+    };
+
+    let (status, issued) = call(&app, admin(Method::POST, "/admin/keys", Some(json!({ "id": "grafana", "roles": ["inventory"] })))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let key = issued["key"].as_str().unwrap().to_string();
+
+    let (status, _) = call(&app, admin(Method::POST, "/admin/keys", Some(json!({ "id": "other", "roles": ["root"] })))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, listing) = call(&app, admin(Method::GET, "/admin/keys", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listing["keys"][0]["id"], "grafana");
+    assert!(!listing.to_string().contains(&key), "Secrets are never listed");
+
+    let (status, _) = post_with_key(&app, "/syscollector/001/os", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = call(&app, admin(Method::DELETE, "/admin/keys/grafana", None)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = post_with_key(&app, "/syscollector/001/os", &key, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
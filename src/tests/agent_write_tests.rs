@@ -0,0 +1,240 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tempfile::TempDir;
+
+use crate::client::RetryConfig;
+use crate::create_router;
+use crate::shared::access::API_KEY_HEADER;
+use crate::shared::admin::ADMIN_TOKEN_HEADER;
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json, MOCK_ADMIN_TOKEN};
+
+async fn send(app: &Router, method: Method, path: &str, key: Option<&str>, body: Value) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(path).header("content-type", "application/json");
+    if let Some(key) = key {
+        request = request.header(API_KEY_HEADER, key);
+    }
+    call(app, request.body(Body::from(body.to_string())).unwrap()).await
+}
+
+async fn audit_log(app: &Router) -> Vec<Value> {
+    let request = Request::get("/admin/audit")
+        .header(ADMIN_TOKEN_HEADER, MOCK_ADMIN_TOKEN)
+        .body(Body::empty())
+        .unwrap();
+    let (status, listing) = call(app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    listing["records"].as_array().cloned().unwrap_or_default()
+}
+
+/// Key store with a read-only `viewer`, a `operator` allowed to change agents
+/// and a tenant `acme` owning the `acme-web` group.
+fn keyed_state(upstream: &MockUpstream) -> (AppState, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.json");
+    let file = json!({
+        "roles": {
+            "viewer": { "modules": { "agents": "read" } },
+            "operator": { "modules": { "agents": "write" } }
+        },
+        "tenants": { "acme": { "groups": ["acme-web"] } }
+    });
+    std::fs::write(&path, file.to_string()).unwrap();
+
+    let state = upstream.app_state_configured(|config| config.access.key_file = Some(path));
+    (state, dir)
+}
+
+#[tokio::test]
+async fn test_bulk_restart_targets_resolved_agents() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 4);
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = send(&app, Method::PUT, "/agents/restart", None, json!({ "token": "t", "query": { "status": "active" } })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(upstream.requests().iter().any(|p| p.starts_with("/agents?") && p.contains("status=active")), "Filters select the agents");
+    assert_eq!(upstream.writes(), vec!["PUT /agents/restart?agents_list=001%2C002%2C003"], "The manager (000) is never a target");
+}
+
+#[tokio::test]
+async fn test_dry_run_previews_without_changing_anything() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 3);
+    let app = create_router(upstream.app_state());
+
+    let (status, preview) = send(&app, Method::PUT, "/agents/upgrade", None, json!({
+        "token": "t",
+        "dry_run": true,
+        "query": { "upgrade_version": "4.7.0" }
+    })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["dry_run"], true);
+    assert_eq!(preview["data"]["total_affected_items"], 2);
+    assert!(upstream.writes().is_empty());
+
+    let records = audit_log(&app).await;
+    assert_eq!(records[0]["action"], "PUT agents/upgrade");
+    assert_eq!(records[0]["dry_run"], true);
+    assert_eq!(records[0]["agents"], json!(["001", "002"]));
+}
+
+#[tokio::test]
+async fn test_upgrade_options_are_forwarded_but_not_used_as_filters() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 2);
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = send(&app, Method::PUT, "/agents/upgrade", None, json!({
+        "token": "t",
+        "query": { "upgrade_version": "4.7.0", "force": true }
+    })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(upstream.requests().iter().all(|p| !p.starts_with("/agents?") || !p.contains("upgrade_version")));
+    assert_eq!(upstream.writes(), vec!["PUT /agents/upgrade?agents_list=001&force=true&upgrade_version=4.7.0"]);
+}
+
+#[tokio::test]
+async fn test_delete_needs_status_and_defaults_older_than() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 2);
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = send(&app, Method::DELETE, "/agents", None, json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, Method::DELETE, "/agents", None, json!({
+        "token": "t",
+        "query": { "status": "never_connected", "purge": true }
+    })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(upstream.requests().iter().any(|p| p.starts_with("/agents?") && p.contains("older_than=7d") && p.contains("status=never_connected")));
+    assert_eq!(upstream.writes(), vec!["DELETE /agents?agents_list=001&older_than=7d&purge=true&status=never_connected"]);
+}
+
+#[tokio::test]
+async fn test_nothing_is_sent_when_no_agent_matches() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 1); // only the manager
+    let app = create_router(upstream.app_state());
+
+    let (status, body) = send(&app, Method::PUT, "/agents/reconnect", None, json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["total_affected_items"], 0);
+    assert!(upstream.writes().is_empty(), "An empty agents_list would target every agent");
+}
+
+#[tokio::test]
+async fn test_writes_are_not_retried_after_reaching_wazuh() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 2);
+    upstream.respond_once("/agents/restart", StatusCode::SERVICE_UNAVAILABLE, r#"{"title": "Service Unavailable"}"#);
+    let state = upstream.app_state_configured(|config| {
+        config.retry = RetryConfig {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            max_retry_after: Duration::from_secs(2),
+        };
+    });
+    let app = create_router(state);
+
+    let (status, _) = send(&app, Method::PUT, "/agents/restart", None, json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(upstream.writes().len(), 1);
+    let records = audit_log(&app).await;
+    assert_eq!(records[0]["status"], 503);
+    assert!(records[0]["actor"].as_str().unwrap().starts_with("token:"));
+}
+
+#[tokio::test]
+async fn test_enrollment_forwards_body() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = post_json(&app, "/agents/enroll", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "A name is required");
+
+    let (status, _) = post_json(&app, "/agents/enroll", json!({
+        "token": "t",
+        "body": { "name": "web-01", "ip": "10.0.0.5" }
+    })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.writes(), vec!["POST /agents"]);
+    let sent: Value = serde_json::from_str(&upstream.bodies("/agents")[0]).unwrap();
+    assert_eq!(sent, json!({ "name": "web-01", "ip": "10.0.0.5" }));
+}
+
+#[tokio::test]
+async fn test_write_routes_need_write_access() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 2);
+    let (state, _dir) = keyed_state(&upstream);
+    let keys = state.access.clone().unwrap();
+    let viewer = keys.issue("viewer", vec!["viewer".to_string()], None).unwrap();
+    let operator = keys.issue("operator", vec!["operator".to_string()], None).unwrap();
+    let app = create_router(state);
+
+    let (status, _) = send(&app, Method::POST, "/agents", Some(&viewer), json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK, "Listing stays a read");
+    let (status, _) = send(&app, Method::PUT, "/agents/restart", Some(&viewer), json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(upstream.writes().is_empty());
+
+    let (status, _) = send(&app, Method::PUT, "/agents/001/restart", Some(&operator), json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.writes(), vec!["PUT /agents/001/restart"]);
+    assert_eq!(audit_log(&app).await[0]["actor"], "key:operator");
+}
+
+#[tokio::test]
+async fn test_tenant_keys_only_change_their_agents() {
+    let upstream = MockUpstream::start().await;
+    upstream.agent_in_groups("001", &["acme-web"]);
+    upstream.agent_in_groups("002", &["globex"]);
+    let (state, _dir) = keyed_state(&upstream);
+    let acme = state.access.as_ref().unwrap().issue("acme", vec!["operator".to_string()], Some("acme".to_string())).unwrap();
+    let app = create_router(state);
+
+    let (status, _) = send(&app, Method::PUT, "/agents/002/restart", Some(&acme), json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_json(&app, "/agents/enroll", json!({ "token": "t", "body": { "name": "x" } })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "No key at all");
+    let (status, _) = send(&app, Method::POST, "/agents/enroll", Some(&acme), json!({ "token": "t", "body": { "name": "x" } })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(upstream.writes().is_empty());
+
+    let (status, _) = send(&app, Method::PUT, "/agents/restart", Some(&acme), json!({ "token": "t", "query": { "agents_list": "001,002" } })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(upstream.requests().iter().any(|p| p.starts_with("/agents?") && p.contains("q=group%3Dacme-web")));
+}
+
+#[tokio::test]
+async fn test_tenant_bulk_writes_skip_agents_outside_the_tenant() {
+    let upstream = MockUpstream::start().await;
+    upstream.agent_in_groups("001", &["acme-web"]);
+    upstream.agent_in_groups("002", &["globex"]);
+    let (state, _dir) = keyed_state(&upstream);
+    let acme = state.access.as_ref().unwrap().issue("acme", vec!["operator".to_string()], Some("acme".to_string())).unwrap();
+    let app = create_router(state);
+
+    let (status, _) = send(&app, Method::PUT, "/agents/restart", Some(&acme), json!({ "token": "t", "query": { "q": "id=002),(id!=x" } })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(upstream.writes().is_empty());
+
+    // The mock ignores `q`, like a filter that still let 002 through
+    let (status, _) = send(&app, Method::PUT, "/agents/restart", Some(&acme), json!({ "token": "t", "query": { "agents_list": "001,002" } })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.writes(), vec!["PUT /agents/restart?agents_list=001"]);
+}
@@ -3,7 +3,7 @@ use crate::tests::core::{
     common::test_agent_id,
     test_helpers::batch_test_endpoints,
 };
-use crate::{endpoints, agent_endpoints, agent_config_endpoints, agent_stats_endpoints};
+use crate::{endpoints, agent_endpoints, agent_config_endpoints, agent_stats_endpoints, query_endpoints};
 
 const MODULE_NAME: &str = "agents";
 
@@ -51,3 +51,21 @@ async fn test_agents_endpoints() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_agents_query_parameters() -> Result<(), Box<dyn std::error::Error>> {
+    let framework = TestFramework::new("agents_query").await?;
+
+    let query_endpoints = query_endpoints!(framework,
+        ("/agents", serde_json::json!({ "limit": 1, "offset": 0, "select": ["id", "name", "status"] })),
+        ("/agents", serde_json::json!({ "q": "status=active", "sort": "-dateAdd" })),
+        ("/agents/summary/status", serde_json::json!({ "agents_list": [test_agent_id()] }))
+    );
+
+    for endpoint in query_endpoints {
+        let response = framework.test_endpoint(endpoint).await?;
+        assert!(response.get("data").is_some(), "Wazuh should accept the forwarded query: {}", response);
+    }
+
+    Ok(())
+}
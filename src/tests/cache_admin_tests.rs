@@ -0,0 +1,119 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use serde_json::{json, Value};
+
+use crate::create_router;
+use crate::shared::admin::ADMIN_TOKEN_HEADER;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json, MOCK_ADMIN_TOKEN};
+
+fn admin_request(method: Method, path: &str, body: Option<Value>) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(path)
+        .header(ADMIN_TOKEN_HEADER, MOCK_ADMIN_TOKEN)
+        .header("content-type", "application/json")
+        .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+        .unwrap()
+}
+
+async fn warm_cache(app: &axum::Router, upstream: &MockUpstream) {
+    for (path, agent_id) in [("/syscollector/001/os", "001"), ("/syscollector/002/os", "002")] {
+        let body = json!({ "endpoint": upstream.url(), "token": "secret-jwt", "params": { "agent_id": agent_id } });
+        post_json(app, path, body).await;
+    }
+    post_json(app, "/rules", json!({ "endpoint": upstream.url(), "token": "secret-jwt" })).await;
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_admin_token() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = call(&app, Request::get("/admin/cache").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let wrong = Request::delete("/admin/cache")
+        .header(ADMIN_TOKEN_HEADER, "guess")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _) = call(&app, wrong).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // A prefix of the real token is as wrong as any other guess
+    let prefix = Request::get("/admin/cache")
+        .header(ADMIN_TOKEN_HEADER, &MOCK_ADMIN_TOKEN[..MOCK_ADMIN_TOKEN.len() - 1])
+        .body(Body::empty())
+        .unwrap();
+    let (status, _) = call(&app, prefix).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_routes_disabled_without_configured_token() {
+    let upstream = MockUpstream::start().await;
+    let mut state = upstream.app_state();
+    let mut config = (*state.config).clone();
+    config.admin_token = None;
+    state.config = std::sync::Arc::new(config);
+    let app = create_router(state);
+
+    let (status, _) = call(&app, admin_request(Method::GET, "/admin/cache", None)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_list_entries_redacts_tokens() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    warm_cache(&app, &upstream).await;
+
+    let (status, listing) = call(&app, admin_request(Method::GET, "/admin/cache", None)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let entries = listing["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(!listing.to_string().contains("secret-jwt"), "Tokens must never be listed");
+    for entry in entries {
+        assert_eq!(entry["token_fingerprint"].as_str().unwrap().len(), 8);
+        assert!(entry["size_bytes"].as_u64().unwrap() > 0);
+        assert!(entry.get("age_secs").is_some());
+    }
+}
+
+#[tokio::test]
+async fn test_invalidate_by_path_prefix() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    warm_cache(&app, &upstream).await;
+
+    let request = admin_request(Method::POST, "/admin/cache/invalidate", Some(json!({ "path": "/syscollector/001" })));
+    let (status, result) = call(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["removed"], 1);
+
+    // The invalidated entry is fetched again, the others are still cached.
+    warm_cache(&app, &upstream).await;
+    assert_eq!(upstream.hits("/syscollector/001/os"), 2);
+    assert_eq!(upstream.hits("/syscollector/002/os"), 1);
+    assert_eq!(upstream.hits("/rules"), 1);
+}
+
+#[tokio::test]
+async fn test_invalidate_by_url_prefix_and_flush() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    warm_cache(&app, &upstream).await;
+
+    let prefix = format!("{}/syscollector", upstream.url());
+    let request = admin_request(Method::POST, "/admin/cache/invalidate", Some(json!({ "prefix": prefix })));
+    let (_, result) = call(&app, request).await;
+    assert_eq!(result["removed"], 2);
+
+    let (_, result) = call(&app, admin_request(Method::DELETE, "/admin/cache", None)).await;
+    assert_eq!(result["removed"], 1);
+
+    let request = admin_request(Method::POST, "/admin/cache/invalidate", Some(json!({})));
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
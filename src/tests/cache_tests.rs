@@ -0,0 +1,77 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use serde_json::json;
+
+use crate::create_router;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json};
+
+#[tokio::test]
+async fn test_second_identical_call_is_served_from_cache() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "token-a" });
+
+    let (status, first) = post_json(&app, "/agents/summary/status", body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, second) = post_json(&app, "/agents/summary/status", body).await;
+
+    assert_eq!(first, second, "Cached response should match original response");
+    assert_eq!(upstream.hits("/agents/summary/status"), 1, "Second call should not reach Wazuh");
+}
+
+#[tokio::test]
+async fn test_cache_is_shared_across_feature_routers() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let agent = json!({ "endpoint": upstream.url(), "token": "token-a", "params": { "agent_id": "001" } });
+    post_json(&app, "/syscollector/001/os", agent.clone()).await;
+    post_json(&app, "/syscollector/001/os", agent).await;
+
+    let groups = json!({ "endpoint": upstream.url(), "token": "token-a" });
+    post_json(&app, "/groups", groups.clone()).await;
+    post_json(&app, "/groups", groups).await;
+
+    assert_eq!(upstream.hits("/syscollector/001/os"), 1);
+    assert_eq!(upstream.hits("/groups"), 1);
+}
+
+#[tokio::test]
+async fn test_cache_is_keyed_by_token() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    post_json(&app, "/agents", json!({ "endpoint": upstream.url(), "token": "token-a" })).await;
+    post_json(&app, "/agents", json!({ "endpoint": upstream.url(), "token": "token-b" })).await;
+
+    assert_eq!(upstream.hits("/agents"), 2, "Different tokens must not share cache entries");
+}
+
+#[tokio::test]
+async fn test_uncached_paths_always_reach_wazuh() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "token-a" });
+
+    post_json(&app, "/manager/logs", body.clone()).await;
+    post_json(&app, "/manager/logs", body).await;
+
+    assert_eq!(upstream.hits("/manager/logs"), 2, "manager/logs must never be cached");
+}
+
+#[tokio::test]
+async fn test_cache_stats_endpoint_reports_hits_and_misses() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "token-a" });
+
+    post_json(&app, "/rules", body.clone()).await;
+    post_json(&app, "/rules", body).await;
+
+    let (_, stats) = call(&app, Request::get("/cache/stats").body(Body::empty()).unwrap()).await;
+
+    assert_eq!(stats["hits"], 1);
+    assert_eq!(stats["misses"], 1);
+    assert_eq!(stats["entries"], 1);
+}
@@ -0,0 +1,70 @@
+use futures::future::join_all;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::client::WazuhClient;
+use crate::create_router;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+const CONCURRENT_CALLS: usize = 25;
+
+#[tokio::test]
+async fn test_concurrent_identical_calls_share_one_upstream_request() {
+    let upstream = MockUpstream::start_with_delay(Duration::from_millis(200)).await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "token-a" });
+
+    let calls = (0..CONCURRENT_CALLS).map(|_| post_json(&app, "/agents/summary/status", body.clone()));
+    let responses = join_all(calls).await;
+
+    assert_eq!(upstream.hits("/agents/summary/status"), 1, "Only one request should reach Wazuh");
+    for (_, response) in &responses {
+        assert_eq!(response, &responses[0].1, "Every caller should receive the same result");
+    }
+}
+
+#[tokio::test]
+async fn test_different_keys_are_not_coalesced() {
+    let upstream = MockUpstream::start_with_delay(Duration::from_millis(100)).await;
+    let app = create_router(upstream.app_state());
+
+    let calls = ["token-a", "token-b", "token-c"].map(|token| {
+        post_json(&app, "/groups", json!({ "endpoint": upstream.url(), "token": token }))
+    });
+    join_all(calls).await;
+
+    assert_eq!(upstream.hits("/groups"), 3);
+}
+
+#[tokio::test]
+async fn test_coalesced_waiters_are_counted() {
+    let upstream = MockUpstream::start_with_delay(Duration::from_millis(200)).await;
+    let client = WazuhClient::new();
+    let url = format!("{}/groups", upstream.url());
+
+    let calls = (0..CONCURRENT_CALLS).map(|_| client.get_cached(&url, Some("token-a")));
+    let results = join_all(calls).await;
+    assert!(results.iter().all(|r| r.is_ok()));
+
+    let stats = client.cache_stats();
+    assert_eq!(upstream.hits("/groups"), 1);
+    assert_eq!(stats.coalesced, (CONCURRENT_CALLS - 1) as u64);
+
+    // Once the flight has landed, the next call is a plain cache hit.
+    client.get_cached(&url, Some("token-a")).await.unwrap();
+    assert_eq!(upstream.hits("/groups"), 1);
+    assert_eq!(client.cache_stats().coalesced, (CONCURRENT_CALLS - 1) as u64);
+}
+
+#[tokio::test]
+async fn test_failed_flight_is_not_remembered() {
+    let client = WazuhClient::new();
+    // Nothing listens on this port, so every attempt fails.
+    let url = "http://127.0.0.1:9/groups";
+
+    assert!(client.get_cached(url, Some("token-a")).await.is_err());
+    assert!(client.get_cached(url, Some("token-a")).await.is_err());
+    assert_eq!(client.cache_stats().coalesced, 0);
+    assert_eq!(client.cache_stats().entries, 0);
+}
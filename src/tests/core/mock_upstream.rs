@@ -0,0 +1,311 @@
+//! A local stand-in for the Wazuh API that records every request it receives,
+//! so tests can assert on how many calls actually left the proxy.
+
+use axum::{
+    Router,
+    Json,
+    body::Body,
+    extract::State,
+    body::Bytes,
+    http::{header, HeaderMap, Method, Request, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use tower::ServiceExt;
+use serde_json::{json, Value};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::shared::{AppConfig, AppState};
+use crate::shared::upstreams::UpstreamRegistry;
+
+/// Token returned by the mock for the first `/security/user/authenticate`;
+/// later logins get `{MOCK_TOKEN}-{n}`.
+pub const MOCK_TOKEN: &str = "mock-service-token";
+
+/// Admin credential configured by [`MockUpstream::app_state`].
+pub const MOCK_ADMIN_TOKEN: &str = "mock-admin-token";
+
+/// A scripted answer: status, optional `Retry-After` seconds and raw body.
+type Reply = (StatusCode, Option<u64>, String);
+
+#[derive(Clone, Default)]
+struct Recorder {
+    requests: Arc<Mutex<Vec<String>>>,
+    // Requests other than GET as "METHOD path?query", in arrival order.
+    writes: Arc<Mutex<Vec<String>>>,
+    // Non-empty request bodies as (path, body), in arrival order.
+    bodies: Arc<Mutex<Vec<(String, String)>>>,
+    // Scripted (status, raw body) answers keyed by path, overriding the default reply.
+    overrides: Arc<Mutex<HashMap<String, (StatusCode, String)>>>,
+    // One-shot answers keyed by path, used up before overrides and the default reply.
+    queued: Arc<Mutex<HashMap<String, VecDeque<Reply>>>>,
+    // Bearer tokens answered with 401, to simulate expired Wazuh sessions.
+    revoked: Arc<Mutex<HashSet<String>>>,
+    logins: Arc<Mutex<usize>>,
+    // Agent id -> groups, answered for `agents?agents_list=...` lookups.
+    agent_groups: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    // Paged datasets keyed by path: (items stored, total reported to the caller).
+    datasets: Arc<Mutex<HashMap<String, (usize, usize)>>>,
+    delay: Duration,
+}
+
+pub struct MockUpstream {
+    addr: SocketAddr,
+    recorder: Recorder,
+}
+
+impl MockUpstream {
+    pub async fn start() -> Self {
+        Self::start_with_delay(Duration::ZERO).await
+    }
+
+    /// Starts a mock that waits `delay` before answering, to keep requests in flight.
+    pub async fn start_with_delay(delay: Duration) -> Self {
+        let recorder = Recorder {
+            delay,
+            ..Default::default()
+        };
+        let app = Router::new()
+            .fallback(respond)
+            .with_state(recorder.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock upstream");
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .expect("Failed to start mock upstream")
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        Self { addr, recorder }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Every path (with query string) received so far, in arrival order.
+    pub fn requests(&self) -> Vec<String> {
+        self.recorder.requests.lock().unwrap().clone()
+    }
+
+    /// Every request other than `GET`, as `"METHOD /path?query"`, in arrival order.
+    pub fn writes(&self) -> Vec<String> {
+        self.recorder.writes.lock().unwrap().clone()
+    }
+
+    /// Bodies sent to `path` (without query), in arrival order.
+    pub fn bodies(&self, path: &str) -> Vec<String> {
+        self.recorder.bodies.lock().unwrap().iter().filter(|(p, _)| p == path).map(|(_, b)| b.clone()).collect()
+    }
+
+    /// Makes every later request for `path` answer with `status` and the raw `body`.
+    pub fn respond_with(&self, path: &str, status: StatusCode, body: &str) {
+        self.recorder.overrides.lock().unwrap().insert(path.to_string(), (status, body.to_string()));
+    }
+
+    /// Goes back to the default successful reply for `path`.
+    pub fn reset(&self, path: &str) {
+        self.recorder.overrides.lock().unwrap().remove(path);
+    }
+
+    /// Makes the next request for `path` answer with `status` and `body`, once.
+    /// Calls queue up in order.
+    pub fn respond_once(&self, path: &str, status: StatusCode, body: &str) {
+        self.queue(path, (status, None, body.to_string()));
+    }
+
+    /// Makes the next request for `path` answer 429 with `Retry-After: {secs}`, once.
+    pub fn rate_limit_once(&self, path: &str, retry_after_secs: u64) {
+        self.queue(path, (StatusCode::TOO_MANY_REQUESTS, Some(retry_after_secs), r#"{"title": "Too Many Requests"}"#.to_string()));
+    }
+
+    fn queue(&self, path: &str, reply: Reply) {
+        self.recorder.queued.lock().unwrap().entry(path.to_string()).or_default().push_back(reply);
+    }
+
+    /// Answers 401 to every later request carrying `token`, like an expired JWT.
+    /// `DELETE /security/user/authenticate` revokes the caller's token the same way.
+    pub fn revoke_token(&self, token: &str) {
+        self.recorder.revoked.lock().unwrap().insert(token.to_string());
+    }
+
+    /// Registers an agent and its groups, answered for `agents?agents_list=<id>`.
+    pub fn agent_in_groups(&self, agent_id: &str, groups: &[&str]) {
+        let groups = groups.iter().map(|g| g.to_string()).collect();
+        self.recorder.agent_groups.lock().unwrap().insert(agent_id.to_string(), groups);
+    }
+
+    /// Serves `count` items at `path`, paged by the `offset`/`limit` query.
+    pub fn serve_items(&self, path: &str, count: usize) {
+        self.serve_items_reporting(path, count, count);
+    }
+
+    /// Like [`serve_items`](Self::serve_items) but claims `reported` in `total_affected_items`.
+    pub fn serve_items_reporting(&self, path: &str, count: usize, reported: usize) {
+        self.recorder.datasets.lock().unwrap().insert(path.to_string(), (count, reported));
+    }
+
+    pub fn hits(&self, path: &str) -> usize {
+        self.requests().iter().filter(|p| p.as_str() == path).count()
+    }
+
+    /// Application state whose default Wazuh manager is this mock.
+    pub fn app_state(&self) -> AppState {
+        self.app_state_configured(|_| {})
+    }
+
+    /// Application state with the mock's credentials but the given managers.
+    pub fn app_state_with(&self, upstreams: UpstreamRegistry) -> AppState {
+        self.app_state_configured(|config| config.upstreams = upstreams)
+    }
+
+    /// Application state for this mock after `configure` adjusted the
+    /// configuration, e.g. retry or breaker settings.
+    pub fn app_state_configured(&self, configure: impl FnOnce(&mut AppConfig)) -> AppState {
+        let mut config = AppConfig {
+            upstreams: UpstreamRegistry::single(&self.url(), Default::default()).unwrap(),
+            wazuh_username: Some("wazuh".to_string()),
+            wazuh_password: Some("wazuh".to_string()),
+            admin_token: Some(MOCK_ADMIN_TOKEN.to_string()),
+            ..Default::default()
+        };
+        configure(&mut config);
+        AppState::from_config(config).expect("Invalid mock configuration")
+    }
+}
+
+async fn respond(State(recorder): State<Recorder>, method: Method, headers: HeaderMap, uri: Uri, body: Bytes) -> Response {
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/").to_string();
+    let hit = {
+        let mut requests = recorder.requests.lock().unwrap();
+        requests.push(path.clone());
+        requests.len()
+    };
+    if method != Method::GET {
+        recorder.writes.lock().unwrap().push(format!("{} {}", method, path));
+    }
+    if !body.is_empty() {
+        let body = String::from_utf8_lossy(&body).into_owned();
+        recorder.bodies.lock().unwrap().push((uri.path().to_string(), body));
+    }
+
+    if !recorder.delay.is_zero() {
+        tokio::time::sleep(recorder.delay).await;
+    }
+
+    let queued = recorder.queued.lock().unwrap().get_mut(uri.path()).and_then(VecDeque::pop_front);
+    if let Some((status, retry_after, body)) = queued {
+        let mut response = (status, [(header::CONTENT_TYPE, "application/json")], body).into_response();
+        if let Some(secs) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, secs.into());
+        }
+        return response;
+    }
+
+    let scripted = recorder.overrides.lock().unwrap().get(uri.path()).cloned();
+    if let Some((status, body)) = scripted {
+        return (status, [(header::CONTENT_TYPE, "application/json")], body).into_response();
+    }
+
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if method == Method::DELETE && uri.path() == "/security/user/authenticate" {
+        if let Some(token) = bearer {
+            recorder.revoked.lock().unwrap().insert(token.to_string());
+        }
+        return Json(json!({ "message": "User wazuh was successfully logged out", "error": 0 })).into_response();
+    }
+
+    if path.starts_with("/security/user/authenticate") {
+        let token = {
+            let mut logins = recorder.logins.lock().unwrap();
+            *logins += 1;
+            match *logins {
+                1 => MOCK_TOKEN.to_string(),
+                n => format!("{}-{}", MOCK_TOKEN, n),
+            }
+        };
+        return Json(json!({ "data": { "token": token }, "error": 0 })).into_response();
+    }
+
+    if bearer.is_some_and(|token| recorder.revoked.lock().unwrap().contains(token)) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "title": "Unauthorized", "error": 401 }))).into_response();
+    }
+
+    let known = recorder.agent_groups.lock().unwrap().clone();
+    if let Some(agents_list) = query_param(&uri, "agents_list").filter(|_| uri.path() == "/agents" && !known.is_empty()) {
+        let items: Vec<Value> = agents_list
+            .split(',')
+            .filter_map(|id| known.get(id).map(|groups| json!({ "id": id, "group": groups })))
+            .collect();
+        return Json(json!({
+            "data": { "affected_items": items, "total_affected_items": items.len(), "total_failed_items": 0, "failed_items": [] },
+            "error": 0
+        })).into_response();
+    }
+
+    let dataset = recorder.datasets.lock().unwrap().get(uri.path()).copied();
+    if let Some((count, reported)) = dataset {
+        return Json(page_of(&uri, count, reported)).into_response();
+    }
+
+    Json(json!({
+        "data": {
+            "affected_items": [{ "path": path, "hit": hit }],
+            "total_affected_items": 1,
+            "total_failed_items": 0,
+            "failed_items": []
+        },
+        "message": "Mock response",
+        "error": 0
+    })).into_response()
+}
+
+fn query_param(uri: &Uri, name: &str) -> Option<String> {
+    let url = reqwest::Url::parse(&format!("http://mock{}", uri)).ok()?;
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn page_of(uri: &Uri, count: usize, reported: usize) -> Value {
+    let param = |name: &str| query_param(uri, name).and_then(|value| value.parse::<usize>().ok());
+    let offset = param("offset").unwrap_or(0);
+    let limit = param("limit").unwrap_or(500);
+    let items: Vec<Value> = (offset..count.min(offset + limit))
+        .map(|id| json!({ "id": format!("{:03}", id) }))
+        .collect();
+
+    json!({
+        "data": {
+            "affected_items": items,
+            "total_affected_items": reported,
+            "total_failed_items": 0,
+            "failed_items": []
+        },
+        "message": "Mock response",
+        "error": 0
+    })
+}
+
+/// Sends `request` through `app` and decodes the JSON body (`Null` when empty).
+pub async fn call(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, body)
+}
+
+pub async fn post_json(app: &Router, path: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(path)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    call(app, request).await
+}
@@ -0,0 +1,326 @@
+//! A TLS stand-in for the WQL gateway: reads signed `AuthRequest`s, checks their
+//! signatures and answers with signed `Response`s.
+//! Signing is implemented here independently of `features::wql` so the tests
+//! catch mistakes on both ends. A replaying gateway answers every request with
+//! the first response it sent, like an attacker replaying a captured frame; a
+//! framed gateway speaks length-prefixed frames and keeps one session per
+//! connection, rejecting requests that name another session; a hanging-up one
+//! closes each connection after its first answer; a downgrading one answers
+//! in v1 only after its first answer, as if it had been swapped for a v1
+//! gateway.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+use crate::features::wql::{AuthRequest, Response};
+use crate::shared::wql::{WqlConfig, WqlSettings};
+use super::tls_upstream::{acceptor, fixture};
+
+pub const CLIENT_KEY: &str = "client-secret";
+pub const SERVER_KEY: &str = "server-secret";
+
+/// How the gateway signs its answers.
+#[derive(Debug, Clone)]
+pub enum Speaks {
+    /// Only `SHA256(data || key)`; v2 requests are turned down.
+    V1,
+    /// Answers v2 requests with the given server key id (v1 requests in v1).
+    V2 { key_id: String },
+}
+
+/// What the gateway does besides answering each request.
+#[derive(Debug, Clone, Copy, Default)]
+struct Behaviour {
+    /// Answer every request with the first response sent.
+    replay: bool,
+    /// Length-prefixed frames and several requests per connection.
+    framed: bool,
+    /// Close framed connections after one answer, as a restarting gateway would.
+    hang_up: bool,
+    /// Speak v1 once the first response was sent.
+    downgrade: bool,
+}
+
+struct Shared {
+    speaks: Speaks,
+    behaviour: Behaviour,
+    first: Mutex<Option<Response>>,
+    requests: Mutex<Vec<AuthRequest>>,
+    gzip_requests: AtomicUsize,
+    connections: AtomicUsize,
+}
+
+pub struct MockWqlGateway {
+    port: u16,
+    shared: Arc<Shared>,
+}
+
+impl MockWqlGateway {
+    pub async fn start(speaks: Speaks) -> Self {
+        Self::serve(speaks, Behaviour::default()).await
+    }
+
+    pub async fn replaying(speaks: Speaks) -> Self {
+        Self::serve(speaks, Behaviour { replay: true, ..Behaviour::default() }).await
+    }
+
+    pub async fn framed(speaks: Speaks) -> Self {
+        Self::serve(speaks, Behaviour { framed: true, ..Behaviour::default() }).await
+    }
+
+    pub async fn framed_replaying(speaks: Speaks) -> Self {
+        Self::serve(speaks, Behaviour { framed: true, replay: true, ..Behaviour::default() }).await
+    }
+
+    pub async fn framed_hanging_up(speaks: Speaks) -> Self {
+        Self::serve(speaks, Behaviour { framed: true, hang_up: true, ..Behaviour::default() }).await
+    }
+
+    pub async fn downgrading(speaks: Speaks) -> Self {
+        Self::serve(speaks, Behaviour { downgrade: true, ..Behaviour::default() }).await
+    }
+
+    async fn serve(speaks: Speaks, behaviour: Behaviour) -> Self {
+        let acceptor = acceptor(false);
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind WQL gateway");
+        let port = listener.local_addr().unwrap().port();
+        let shared = Arc::new(Shared {
+            speaks,
+            behaviour,
+            first: Mutex::new(None),
+            requests: Mutex::new(Vec::new()),
+            gzip_requests: AtomicUsize::new(0),
+            connections: AtomicUsize::new(0),
+        });
+
+        let gateway = shared.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (acceptor, gateway) = (acceptor.clone(), gateway.clone());
+                tokio::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        gateway.connections.fetch_add(1, Ordering::SeqCst);
+                        serve_connection(stream, &gateway).await;
+                    }
+                });
+            }
+        });
+
+        Self { port, shared }
+    }
+
+    /// Settings reaching this gateway through the fixture CA, with no retries.
+    pub fn settings(&self) -> WqlSettings {
+        WqlSettings {
+            address: Some(format!("127.0.0.1:{}", self.port)),
+            server_name: Some("localhost".to_string()),
+            tls_ca_file: Some(fixture("ca.pem")),
+            client_id: Some("client1".to_string()),
+            client_key: Some(CLIENT_KEY.to_string()),
+            server_key: Some(SERVER_KEY.to_string()),
+            max_retries: Some(0),
+            ..WqlSettings::default()
+        }
+    }
+
+    pub fn config(&self, settings: WqlSettings) -> WqlConfig {
+        WqlConfig::from_settings(settings).unwrap().unwrap()
+    }
+
+    /// `version` of every request received so far (`None` for v1).
+    pub fn versions(&self) -> Vec<Option<u8>> {
+        self.shared.requests.lock().unwrap().iter().map(|r| r.version).collect()
+    }
+
+    /// `session_id` of every request received so far.
+    pub fn sessions(&self) -> Vec<Option<String>> {
+        self.shared.requests.lock().unwrap().iter().map(|r| r.session_id.clone()).collect()
+    }
+
+    /// TLS connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.load(Ordering::SeqCst)
+    }
+
+    /// Framed requests that arrived gzip-compressed.
+    pub fn gzip_requests(&self) -> usize {
+        self.shared.gzip_requests.load(Ordering::SeqCst)
+    }
+}
+
+/// Answers requests until the client goes away; legacy connections carry one.
+async fn serve_connection<S: AsyncReadExt + AsyncWriteExt + Unpin>(mut stream: S, gateway: &Shared) {
+    // The session this connection authenticates into
+    let session = Uuid::new_v4().to_string();
+
+    loop {
+        let request = if gateway.behaviour.framed {
+            match read_frame(&mut stream).await {
+                Some((request, gzip)) => {
+                    if gzip {
+                        gateway.gzip_requests.fetch_add(1, Ordering::SeqCst);
+                    }
+                    request
+                }
+                None => return,
+            }
+        } else {
+            match read_request(&mut stream).await {
+                Some(request) => request,
+                None => return,
+            }
+        };
+
+        let response = {
+            let mut first = gateway.first.lock().unwrap();
+            match first.as_ref() {
+                Some(captured) if gateway.behaviour.replay => captured.clone(),
+                Some(_) if gateway.behaviour.downgrade => answer(&Speaks::V1, &request, &session),
+                _ => first.insert(answer(&gateway.speaks, &request, &session)).clone(),
+            }
+        };
+        gateway.requests.lock().unwrap().push(request);
+
+        let json = serde_json::to_vec(&response).unwrap();
+        if gateway.behaviour.framed {
+            let mut frame = b"WQLF\x01\x01\x00\x00".to_vec();
+            frame.extend_from_slice(&(json.len() as u32).to_be_bytes());
+            frame.extend_from_slice(&json);
+            if stream.write_all(&frame).await.is_err() {
+                return;
+            }
+            if gateway.behaviour.hang_up {
+                let _ = stream.shutdown().await;
+                return;
+            }
+        } else {
+            let _ = stream.write_all(&json).await;
+            let _ = stream.shutdown().await;
+            return;
+        }
+    }
+}
+
+/// Reads one frame (see `features::wql::framing`) and whether it was gzipped.
+async fn read_frame<S: AsyncReadExt + Unpin>(stream: &mut S) -> Option<(AuthRequest, bool)> {
+    let mut header = [0u8; 12];
+    stream.read_exact(&mut header).await.ok()?;
+    assert_eq!(&header[..6], b"WQLF\x01\x01", "Unexpected frame header");
+    let length = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload).await.ok()?;
+    let gzip = header[6] == 1;
+    if gzip {
+        let mut json = Vec::new();
+        flate2::read::GzDecoder::new(payload.as_slice()).read_to_end(&mut json).unwrap();
+        payload = json;
+    }
+    Some((serde_json::from_slice(&payload).expect("Invalid request frame"), gzip))
+}
+
+/// Reads until the bytes form one JSON request; the client does not close its side.
+async fn read_request<S: AsyncReadExt + Unpin>(stream: &mut S) -> Option<AuthRequest> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = stream.read(&mut buffer).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buffer[..n]);
+        if let Ok(request) = serde_json::from_slice(&data) {
+            return Some(request);
+        }
+    }
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac_b64(key: &str, input: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+    mac.update(input.as_bytes());
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+fn v1_b64(input: &str, key: &str) -> String {
+    BASE64.encode(Sha256::digest(format!("{}{}", input, key).as_bytes()))
+}
+
+fn request_is_valid(request: &AuthRequest) -> bool {
+    match request.version {
+        Some(2) => {
+            let input = format!(
+                "v2\n{}\n{}\n{}\n{}\n{}",
+                request.client_id,
+                request.key_id.as_deref().unwrap_or_default(),
+                request.timestamp,
+                request.nonce,
+                hex_sha256(request.wql_query.as_bytes()),
+            );
+            hmac_b64(CLIENT_KEY, &input) == request.signature
+        }
+        _ => {
+            let input = format!("{}:{}:{}", request.client_id, request.timestamp, request.nonce);
+            v1_b64(&input, CLIENT_KEY) == request.signature
+        }
+    }
+}
+
+fn answer(speaks: &Speaks, request: &AuthRequest, session: &str) -> Response {
+    let v2_key = match speaks {
+        Speaks::V2 { key_id } if request.version == Some(2) => Some(key_id.clone()),
+        _ => None,
+    };
+    let accepted = request_is_valid(request)
+        && (request.version.is_none() || v2_key.is_some())
+        && request.session_id.as_deref().is_none_or(|id| id == session);
+    let data = if accepted {
+        json!({"hits": {"total": {"value": 3}}, "query": request.wql_query}).to_string()
+    } else {
+        json!({"error": "authentication failed"}).to_string()
+    };
+
+    let mut response = Response {
+        version: None,
+        key_id: None,
+        nonce: None,
+        status: accepted,
+        data,
+        session_id: session.to_string(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        signature: String::new(),
+    };
+    match v2_key {
+        Some(key_id) => {
+            response.version = Some(2);
+            response.key_id = Some(key_id.clone());
+            response.nonce = Some(request.nonce.clone());
+            let input = format!(
+                "v2\n{}\n{}\n{}\n{}\n{}\n{}",
+                key_id,
+                request.nonce,
+                response.status,
+                response.session_id,
+                response.timestamp,
+                hex_sha256(response.data.as_bytes()),
+            );
+            response.signature = hmac_b64(SERVER_KEY, &input);
+        }
+        None => {
+            response.signature = v1_b64(&serde_json::to_string(&response).unwrap(), SERVER_KEY);
+        }
+    }
+    response
+}
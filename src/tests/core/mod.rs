@@ -2,10 +2,14 @@ pub mod common;
 pub mod test_framework;
 pub mod test_utils;
 pub mod macros;
+pub mod mock_upstream;
+pub mod mock_wql_gateway;
+pub mod tls_upstream;
 
 // Re-export commonly used items
 pub use test_framework::TestFramework;
 pub use test_utils::TestEndpoint;
+pub use mock_upstream::MockUpstream;
 
 // Helper functions for test organization
 pub mod test_helpers {
@@ -70,7 +74,7 @@ pub mod test_helpers {
         expected_fields: &[&str],
     ) -> Result<(), Box<dyn Error>> {
         for field in expected_fields {
-            if !response.get(field).is_some() {
+            if response.get(field).is_none() {
                 return Err(format!("Missing expected field: {}", field).into());
             }
         }
@@ -86,6 +86,16 @@ impl TestFramework {
         TestEndpoint::new(path, Some(param_desc), Some(request))
     }
 
+    /// An endpoint whose request carries Wazuh query parameters (`limit`, `select`, `q`, ...).
+    pub fn create_endpoint_with_query(&self, path: &str, query: Value) -> TestEndpoint {
+        let mut request = self.base_request.clone();
+        let param_desc = format!("query: {}", query);
+        if let Value::Object(ref mut map) = request {
+            map.insert("query".to_string(), query);
+        }
+        TestEndpoint::new(path, Some(&param_desc), Some(request))
+    }
+
     pub fn create_agent_endpoint(&self, path_template: &str, agent_id: &str) -> TestEndpoint {
         let path = path_template.replace("{agent_id}", agent_id);
         self.create_endpoint_with_params(
@@ -187,6 +197,18 @@ macro_rules! param_endpoints {
     }};
 }
 
+// 查詢參數端點宏
+#[macro_export]
+macro_rules! query_endpoints {
+    ($framework:expr, $(($path:expr, $query:expr)),* $(,)?) => {{
+        vec![
+            $(
+                $framework.create_endpoint_with_query($path, $query),
+            )*
+        ]
+    }};
+}
+
 // Agent配置端點宏
 #[macro_export]
 macro_rules! agent_config_endpoints {
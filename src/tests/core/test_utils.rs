@@ -35,7 +35,7 @@ pub async fn test_endpoint(
     println!("\nTesting {} {}", endpoint.method, endpoint.path);
     
     let response = client
-        .post(&format!("{}{}", base_url, endpoint.path))
+        .post(format!("{}{}", base_url, endpoint.path))
         .headers(headers.clone())
         .json(&endpoint.request_body.clone().unwrap_or(serde_json::json!({})))
         .send()
@@ -56,7 +56,7 @@ pub async fn test_endpoint(
 }
 
 fn get_filename_with_params(path: &str, request_body: &Option<Value>) -> String {
-    let mut filename = path.replace('/', "_").replace(':', "_").trim_start_matches('_').to_string();
+    let mut filename = path.replace(['/', ':'], "_").trim_start_matches('_').to_string();
     
     // Replace parameter placeholders with actual values
     if let Some(body) = request_body {
@@ -67,6 +67,14 @@ fn get_filename_with_params(path: &str, request_body: &Option<Value>) -> String
                     filename = filename.replace(&placeholder, value_str);
                 }
             }
+
+            // Keep one result file per query instead of overwriting the plain endpoint's
+            if let Some(query) = obj.get("query").and_then(|q| q.as_object()) {
+                for (key, value) in query {
+                    let value = value.to_string().replace(|c: char| !c.is_ascii_alphanumeric(), "");
+                    filename.push_str(&format!("__{}_{}", key.replace('.', "_"), value));
+                }
+            }
         }
     }
     
@@ -81,7 +89,7 @@ fn generate_rust_types(json_value: &Value, module_name: &str, endpoint_path: &st
     // Generate a unique type name based on module and endpoint
     let type_name = format!("{}_{}",
         module_name,
-        endpoint_path.replace('/', "_").replace(':', "_").trim_start_matches('_')
+        endpoint_path.replace(['/', ':'], "_").trim_start_matches('_')
     ).replace(".", "_").replace("-", "_");
 
     // Create output path
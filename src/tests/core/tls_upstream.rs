@@ -0,0 +1,90 @@
+//! A minimal HTTPS stand-in for Wazuh, serving the certificates under
+//! `tests/fixtures/tls` (regenerate them with `generate.sh`).
+
+use hyper::{service::service_fn, Body, Response};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::server::{AllowAnyAuthenticatedClient, NoClientAuth};
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// Path of a file in the TLS fixture directory.
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/fixtures/tls").join(name)
+}
+
+pub struct TlsUpstream {
+    port: u16,
+}
+
+impl TlsUpstream {
+    /// Serves `server.pem`; with `require_client_cert` only clients holding a
+    /// certificate signed by `ca.pem` complete the handshake.
+    pub async fn start(require_client_cert: bool) -> Self {
+        let acceptor = acceptor(require_client_cert);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind TLS upstream");
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // Failed handshakes are what several tests expect; just drop them.
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        let service = service_fn(|_| async {
+                            Ok::<_, Infallible>(Response::new(Body::from(r#"{"data": {"tls": true}, "error": 0}"#)))
+                        });
+                        let _ = hyper::server::conn::Http::new().serve_connection(stream, service).await;
+                    }
+                });
+            }
+        });
+
+        Self { port }
+    }
+
+    /// Base URL using the name on the server certificate.
+    pub fn url(&self) -> String {
+        format!("https://localhost:{}", self.port)
+    }
+}
+
+/// TLS acceptor presenting `server.pem` (valid for `localhost`, signed by `ca.pem`).
+pub fn acceptor(require_client_cert: bool) -> TlsAcceptor {
+    let certs = read_pem(&fixture("server.pem"))
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    let key = PrivateKey(read_pem(&fixture("server-key.pem")).remove(0));
+
+    let verifier = if require_client_cert {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(&read_pem(&fixture("ca.pem")));
+        AllowAnyAuthenticatedClient::new(roots).boxed()
+    } else {
+        NoClientAuth::boxed()
+    };
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .expect("Invalid TLS fixtures");
+    TlsAcceptor::from(Arc::new(config))
+}
+
+/// DER contents of every PEM block (certificates or PKCS#8 keys) in `path`.
+fn read_pem(path: &PathBuf) -> Vec<Vec<u8>> {
+    let pem = std::fs::read(path).expect("Missing TLS fixture");
+    rustls_pemfile::read_all(&mut pem.as_slice())
+        .expect("Invalid PEM fixture")
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(der) | rustls_pemfile::Item::PKCS8Key(der) => Some(der),
+            _ => None,
+        })
+        .collect()
+}
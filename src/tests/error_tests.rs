@@ -0,0 +1,107 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::create_router;
+use crate::shared::upstreams::UpstreamRegistry;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+const UNAUTHORIZED: &str = r#"{"title": "Unauthorized", "detail": "The server could not verify that you are authorized to access the URL requested", "error": 401}"#;
+
+#[tokio::test]
+async fn test_upstream_401_is_propagated_and_not_cached() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "expired" });
+
+    upstream.respond_with("/agents", StatusCode::UNAUTHORIZED, UNAUTHORIZED);
+    let (status, error) = post_json(&app, "/agents", body.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["kind"], "upstream");
+    assert_eq!(error["upstream_status"], 401);
+    assert_eq!(error["upstream"]["title"], "Unauthorized");
+
+    // Once Wazuh recovers, the next call must reach it instead of replaying the error.
+    upstream.reset("/agents");
+    let (status, _) = post_json(&app, "/agents", body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/agents"), 2);
+}
+
+#[tokio::test]
+async fn test_upstream_5xx_is_propagated_and_not_cached() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "token-a" });
+
+    upstream.respond_with("/manager/stats/weekly", StatusCode::INTERNAL_SERVER_ERROR, r#"{"title": "Internal Error"}"#);
+    let (status, _) = post_json(&app, "/manager/stats/weekly", body.clone()).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    let (status, _) = post_json(&app, "/manager/stats/weekly", body).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    assert_eq!(upstream.hits("/manager/stats/weekly"), 2);
+}
+
+#[tokio::test]
+async fn test_unparseable_body_is_bad_gateway() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    upstream.respond_with("/rules", StatusCode::OK, "<html>proxy error</html>");
+    let (status, error) = post_json(&app, "/rules", json!({ "endpoint": upstream.url(), "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(error["kind"], "parse");
+}
+
+#[tokio::test]
+async fn test_transport_failure_is_bad_gateway() {
+    let upstream = MockUpstream::start().await;
+    // Nothing listens on the discard port.
+    let registry = UpstreamRegistry::single("http://127.0.0.1:9", Default::default()).unwrap();
+    let app = create_router(upstream.app_state_with(registry));
+
+    let (status, error) = post_json(&app, "/rules", json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(error["kind"], "transport");
+}
+
+#[tokio::test]
+async fn test_missing_path_parameter_is_bad_request() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    // Routes fill their own placeholders from the path, so call the shared handler
+    // with a template whose parameter nobody supplies.
+    let request = serde_json::from_value(json!({ "endpoint": upstream.url(), "token": "t" })).unwrap();
+
+    let error = crate::shared::common::handle_wazuh_request(&state, request, "sca/{agent_id}", |url| url)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+    assert!(matches!(error, crate::shared::NexusError::BadParameter(_)));
+    assert!(upstream.requests().is_empty(), "Nothing should be sent upstream");
+}
+
+#[tokio::test]
+async fn test_auth_propagates_upstream_status() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "username": "wazuh", "password": "wazuh" });
+
+    let (status, ok) = post_json(&app, "/auth", body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(ok["token"].is_string());
+
+    upstream.respond_with(
+        "/security/user/authenticate",
+        StatusCode::SERVICE_UNAVAILABLE,
+        r#"{"title": "Service Unavailable", "detail": "Wazuh API is restarting"}"#,
+    );
+    let (status, error) = post_json(&app, "/auth", body).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(error["token"].is_null());
+    assert_eq!(error["error"], "Wazuh returned 503: Wazuh API is restarting");
+}
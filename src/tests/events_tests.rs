@@ -0,0 +1,138 @@
+use axum::body::{Body, BoxBody, HttpBody};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tower::ServiceExt;
+
+use crate::create_router;
+use crate::shared::watcher::poll_once;
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::call;
+
+fn list_agents(upstream: &MockUpstream, agents: Value) {
+    let total = agents.as_array().unwrap().len();
+    let body = json!({ "data": { "affected_items": agents, "total_affected_items": total }, "error": 0 });
+    upstream.respond_with("/agents", StatusCode::OK, &body.to_string());
+}
+
+/// Polls `001` as active and then as disconnected.
+async fn disconnect(upstream: &MockUpstream, state: &AppState) {
+    list_agents(upstream, json!([{ "id": "001", "name": "web-01", "status": "active", "group": ["web"] }]));
+    assert!(poll_once(state).await.unwrap().is_empty(), "The first poll is a baseline");
+    list_agents(upstream, json!([{ "id": "001", "name": "web-01", "status": "disconnected", "group": ["web"] }]));
+    assert_eq!(poll_once(state).await.unwrap().len(), 1);
+}
+
+/// GET with a Wazuh token, which the mock accepts.
+async fn get(app: &Router, path: &str) -> (StatusCode, Value) {
+    call(app, Request::get(path).header("authorization", "Bearer t").body(Body::empty()).unwrap()).await
+}
+
+#[tokio::test]
+async fn test_status_changes_are_listed() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    disconnect(&upstream, &state).await;
+    let app = create_router(state);
+
+    let (status, listing) = get(&app, "/events/agents").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let event = &listing["events"][0];
+    assert_eq!(event["kind"], "status_changed");
+    assert_eq!(event["agent_id"], "001");
+    assert_eq!(event["from"], "active");
+    assert_eq!(event["to"], "disconnected");
+    assert!(upstream.requests().iter().any(|p| p.starts_with("/agents?") && p.contains("select=")));
+}
+
+#[tokio::test]
+async fn test_time_and_kind_filters() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    disconnect(&upstream, &state).await;
+    let app = create_router(state);
+
+    let (_, listing) = get(&app, "/events/agents?since=2000-01-01T00:00:00Z&kind=status_changed").await;
+    assert_eq!(listing["events"].as_array().unwrap().len(), 1);
+    let (_, listing) = get(&app, "/events/agents?since=2999-01-01T00:00:00Z").await;
+    assert!(listing["events"].as_array().unwrap().is_empty());
+    let (_, listing) = get(&app, "/events/agents?until=2000-01-01T00:00:00Z").await;
+    assert!(listing["events"].as_array().unwrap().is_empty());
+    let (_, listing) = get(&app, "/events/agents?kind=removed").await;
+    assert!(listing["events"].as_array().unwrap().is_empty());
+
+    let (status, _) = get(&app, "/events/agents?since=yesterday").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Reads the next SSE frame, waiting at most two seconds.
+async fn next_frame(body: &mut BoxBody) -> String {
+    let chunk = tokio::time::timeout(Duration::from_secs(2), body.data())
+        .await
+        .expect("No event within 2s")
+        .unwrap()
+        .unwrap();
+    String::from_utf8(chunk.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_stream_pushes_new_events() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    let app = create_router(state.clone());
+
+    let response = app
+        .oneshot(Request::get("/events/agents/stream").header("authorization", "Bearer t").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body();
+
+    disconnect(&upstream, &state).await;
+
+    let frame = next_frame(&mut body).await;
+    assert!(frame.contains("event:agent"), "{}", frame);
+    assert!(frame.contains("id:1\n"), "{}", frame);
+    assert!(frame.contains("\"to\":\"disconnected\""), "{}", frame);
+}
+
+#[tokio::test]
+async fn test_stream_replays_after_last_event_id() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    disconnect(&upstream, &state).await;
+    let app = create_router(state);
+
+    let request = Request::get("/events/agents/stream")
+        .header("authorization", "Bearer t")
+        .header("last-event-id", "0")
+        .body(Body::empty())
+        .unwrap();
+    let mut body = app.oneshot(request).await.unwrap().into_body();
+
+    let frame = next_frame(&mut body).await;
+    assert!(frame.contains("id:1\n"), "{}", frame);
+}
+
+#[tokio::test]
+async fn test_events_need_credentials() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    disconnect(&upstream, &state).await;
+    let app = create_router(state);
+
+    for path in ["/events/agents", "/events/agents/stream"] {
+        let (status, error) = call(&app, Request::get(path).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", path);
+        assert_eq!(error["kind"], "unauthorized");
+    }
+
+    upstream.respond_once("/security/users/me", StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized", "error": 401}"#);
+    let request = Request::get("/events/agents").header("authorization", "Bearer stale").body(Body::empty()).unwrap();
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "A token Wazuh rejects");
+}
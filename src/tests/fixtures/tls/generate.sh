@@ -0,0 +1,31 @@
+#!/bin/sh
+# Regenerates the self-signed fixtures used by tls_tests.rs.
+# ca.pem signs server.pem (localhost / 127.0.0.1) and client.pem (mTLS);
+# other-ca.pem is an unrelated CA that must not be trusted.
+set -e
+cd "$(dirname "$0")"
+
+ec_key() {
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1"
+}
+
+ec_key ca-key.pem
+openssl req -x509 -new -key ca-key.pem -sha256 -days 36500 -subj "/CN=nexus test CA" -out ca.pem
+
+ec_key other-ca-key.pem
+openssl req -x509 -new -key other-ca-key.pem -sha256 -days 36500 -subj "/CN=nexus other CA" -out other-ca.pem
+
+ec_key server-key.pem
+openssl req -new -key server-key.pem -subj "/CN=localhost" -out server.csr
+printf "subjectAltName=DNS:localhost,IP:127.0.0.1\nextendedKeyUsage=serverAuth\n" > server.ext
+openssl x509 -req -in server.csr -CA ca.pem -CAkey ca-key.pem -CAcreateserial -sha256 -days 36500 -extfile server.ext -out server.pem
+
+ec_key client-key.pem
+openssl req -new -key client-key.pem -subj "/CN=nexus" -out client.csr
+printf "extendedKeyUsage=clientAuth\n" > client.ext
+openssl x509 -req -in client.csr -CA ca.pem -CAkey ca-key.pem -CAcreateserial -sha256 -days 36500 -extfile client.ext -out client.pem
+
+# SPKI pin of the server key, as expected in NEXUS_UPSTREAM_<NAME>_TLS_SPKI_PINS
+openssl pkey -in server-key.pem -pubout -outform DER | openssl dgst -sha256 -binary | openssl base64 > server.pin
+
+rm -f ca-key.pem other-ca-key.pem server.csr server.ext client.csr client.ext ca.srl
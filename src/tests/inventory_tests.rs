@@ -0,0 +1,141 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+use crate::create_router;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+fn items(items: Value) -> String {
+    let total = items.as_array().map_or(0, Vec::len);
+    json!({ "data": { "affected_items": items, "total_affected_items": total }, "error": 0 }).to_string()
+}
+
+/// Agent `001` with full syscollector data; the manager (`000`) has none.
+fn describe_agent(upstream: &MockUpstream) {
+    upstream.respond_with("/agents", StatusCode::OK, &items(json!([
+        { "id": "000", "name": "manager", "status": "active", "group": [] },
+        { "id": "001", "name": "web-01", "status": "active", "ip": "10.0.0.5", "group": ["web", "default"], "version": "Wazuh v4.7.0" }
+    ])));
+    upstream.respond_with("/syscollector/001/os", StatusCode::OK, &items(json!([{
+        "os": { "name": "Ubuntu", "version": "22.04.3 LTS", "platform": "ubuntu" },
+        "architecture": "x86_64", "hostname": "web-01", "release": "5.15.0-91-generic"
+    }])));
+    upstream.respond_with("/syscollector/001/hardware", StatusCode::OK, &items(json!([{
+        "cpu": { "name": "Intel(R) Xeon(R), 2.20GHz", "cores": 4 }, "ram": { "total": 8144216 }, "board_serial": "0"
+    }])));
+    upstream.respond_with("/syscollector/001/netaddr", StatusCode::OK, &items(json!([
+        { "iface": "eth0", "proto": "ipv4", "address": "10.0.0.5", "netmask": "255.255.255.0" },
+        { "iface": "eth0", "proto": "ipv6", "address": "fe80::1", "netmask": "ffff:ffff:ffff:ffff::" }
+    ])));
+    for section in ["os", "hardware", "netaddr"] {
+        upstream.respond_with(&format!("/syscollector/000/{}", section), StatusCode::OK, &items(json!([])));
+    }
+}
+
+#[tokio::test]
+async fn test_inventory_joins_agent_sections() {
+    let upstream = MockUpstream::start().await;
+    describe_agent(&upstream);
+    let app = create_router(upstream.app_state());
+
+    let (status, inventory) = post_json(&app, "/inventory", json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(inventory["total"], 2);
+    let agent = &inventory["items"][1];
+    assert_eq!(agent["id"], "001");
+    assert_eq!(agent["groups"], json!(["web", "default"]));
+    assert_eq!(agent["os"]["name"], "Ubuntu");
+    assert_eq!(agent["os"]["architecture"], "x86_64");
+    assert_eq!(agent["hardware"]["cpu_cores"], 4);
+    assert_eq!(agent["hardware"]["ram_total_kb"], 8144216);
+    assert_eq!(agent["addresses"].as_array().unwrap().len(), 2);
+    assert_eq!(inventory["items"][0]["os"], Value::Null, "No data is not an error");
+    assert_eq!(inventory["items"][0].get("errors"), None);
+}
+
+#[tokio::test]
+async fn test_failed_section_is_reported_per_agent() {
+    let upstream = MockUpstream::start().await;
+    describe_agent(&upstream);
+    upstream.respond_with("/syscollector/001/hardware", StatusCode::NOT_FOUND, r#"{"title": "Not Found", "detail": "Agent does not exist"}"#);
+    let app = create_router(upstream.app_state());
+
+    let (status, inventory) = post_json(&app, "/inventory", json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let agent = &inventory["items"][1];
+    assert_eq!(agent["os"]["name"], "Ubuntu");
+    assert_eq!(agent["hardware"], Value::Null);
+    assert!(agent["errors"][0].as_str().unwrap().starts_with("hardware:"), "{}", agent);
+}
+
+#[tokio::test]
+async fn test_inventory_as_csv() {
+    let upstream = MockUpstream::start().await;
+    describe_agent(&upstream);
+    let app = create_router(upstream.app_state());
+
+    let request = Request::post("/inventory")
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "token": "t", "format": "csv" }).to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let csv = String::from_utf8(body.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,name,status,ip,groups,"));
+    assert!(lines[2].starts_with("001,web-01,active,10.0.0.5,web;default,Wazuh v4.7.0,"), "{}", lines[2]);
+    assert!(lines[2].contains(",\"Intel(R) Xeon(R), 2.20GHz\",4,8144216,"), "{}", lines[2]);
+    assert!(lines[2].contains(",eth0:10.0.0.5;eth0:fe80::1,"), "{}", lines[2]);
+}
+
+#[tokio::test]
+async fn test_inventory_group_filters() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 0);
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = post_json(&app, "/inventory", json!({ "token": "t", "query": { "group": "web" } })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_json(&app, "/inventory", json!({ "token": "t", "query": { "group": ["web", "db"] } })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let requests = upstream.requests();
+    assert!(requests.iter().any(|p| p.contains("group=web&limit=")), "{:?}", requests);
+    assert!(requests.iter().any(|p| p.contains("q=group%3Dweb%2Cgroup%3Ddb")), "{:?}", requests);
+}
+
+#[tokio::test]
+async fn test_fan_out_is_bounded() {
+    let upstream = MockUpstream::start_with_delay(Duration::from_millis(50)).await;
+    upstream.serve_items("/agents", 8);
+    let state = upstream.app_state_configured(|config| config.inventory_concurrency = Some(2));
+    let app = create_router(state);
+
+    let started = Instant::now();
+    let (status, inventory) = post_json(&app, "/inventory", json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(inventory["total"], 8);
+    assert_eq!(upstream.requests().iter().filter(|p| p.starts_with("/syscollector/")).count(), 24);
+    // One listing call plus 8 agents, 2 at a time, each taking one round of three parallel calls
+    assert!(started.elapsed() >= Duration::from_millis(250), "{:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn test_csv_is_refused_on_proxied_routes() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = post_json(&app, "/agents", json!({ "token": "t", "format": "csv" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,144 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+
+use crate::create_router;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json, MOCK_TOKEN};
+
+async fn delete_json(app: &Router, path: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::delete(path)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    call(app, request).await
+}
+
+#[tokio::test]
+async fn test_logout_revokes_token_and_purges_its_cache() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    let app = create_router(state.clone());
+
+    post_json(&app, "/rules", json!({ "token": "alice-jwt" })).await;
+    post_json(&app, "/decoders", json!({ "token": "alice-jwt" })).await;
+    post_json(&app, "/rules", json!({ "token": "bob-jwt" })).await;
+
+    let (status, body) = delete_json(&app, "/auth", json!({ "token": "alice-jwt" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["purged_cache_entries"], 2);
+    assert_eq!(upstream.hits("/security/user/authenticate"), 1);
+
+    // The revoked token is no longer answered from the cache
+    let (status, _) = post_json(&app, "/rules", json!({ "token": "alice-jwt" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Other callers keep their entries
+    post_json(&app, "/rules", json!({ "token": "bob-jwt" })).await;
+    assert_eq!(upstream.hits("/rules"), 3);
+    assert_eq!(state.client.cache_entries().len(), 1);
+}
+
+#[tokio::test]
+async fn test_logout_purges_even_when_wazuh_rejects_token() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state();
+    let app = create_router(state.clone());
+    post_json(&app, "/rules", json!({ "token": "old-jwt" })).await;
+    upstream.respond_once("/security/user/authenticate", StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+
+    let (status, _) = delete_json(&app, "/auth", json!({ "token": "old-jwt" })).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(state.client.cache_entries().is_empty());
+}
+
+#[tokio::test]
+async fn test_session_logout_revokes_session_token() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state_configured(|config| config.sessions.enabled = true);
+    let app = create_router(state.clone());
+
+    let (_, login) = post_json(&app, "/session", json!({ "username": "wazuh", "password": "wazuh" })).await;
+    let bearer = format!("Bearer {}", login["session"].as_str().unwrap());
+    let rules = Request::post("/rules")
+        .header("content-type", "application/json")
+        .header(header::AUTHORIZATION, &bearer)
+        .body(Body::from("{}"))
+        .unwrap();
+    call(&app, rules).await;
+    assert_eq!(state.client.cache_entries().len(), 1);
+
+    // Sessions are ended through /session, not /auth
+    let request = Request::delete("/auth")
+        .header("content-type", "application/json")
+        .header(header::AUTHORIZATION, &bearer)
+        .body(Body::from("{}"))
+        .unwrap();
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let request = Request::delete("/session").header(header::AUTHORIZATION, &bearer).body(Body::empty()).unwrap();
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(state.client.cache_entries().is_empty());
+
+    let (status, _) = post_json(&app, "/rules", json!({ "token": MOCK_TOKEN })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "Wazuh no longer accepts the session's JWT");
+}
+
+#[tokio::test]
+async fn test_run_as_forwards_authorization_context() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let context = json!({ "department": "soc", "tenant": "acme" });
+
+    let (status, body) = post_json(&app, "/auth/run_as", json!({
+        "username": "wazuh", "password": "wazuh", "auth_context": context
+    })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["token"], MOCK_TOKEN);
+    let sent: Vec<Value> = upstream.bodies("/security/user/authenticate/run_as")
+        .iter()
+        .map(|b| serde_json::from_str(b).unwrap())
+        .collect();
+    assert_eq!(sent, vec![context]);
+}
+
+#[tokio::test]
+async fn test_run_as_invalid_credentials() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_once("/security/user/authenticate/run_as", StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+    let app = create_router(upstream.app_state());
+
+    let (status, body) = post_json(&app, "/auth/run_as", json!({
+        "username": "wazuh", "password": "nope", "auth_context": {}
+    })).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["kind"], "unauthorized");
+}
+
+#[tokio::test]
+async fn test_introspect_decodes_claims_without_calling_wazuh() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let claims = json!({ "sub": "analyst", "exp": 4_102_444_800_i64, "run_as": true, "rbac_roles": [3, 7] });
+    let token = format!("e30.{}.sig", URL_SAFE_NO_PAD.encode(claims.to_string()));
+
+    let (status, info) = post_json(&app, "/auth/introspect", json!({ "token": token })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(info["username"], "analyst");
+    assert_eq!(info["expired"], false);
+    assert_eq!(info["run_as"], true);
+    assert_eq!(info["rbac_roles"], json!([3, 7]));
+    assert_eq!(info["expires_at"], "2100-01-01T00:00:00Z");
+
+    let (status, error) = post_json(&app, "/auth/introspect", json!({ "token": "not-a-jwt" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["kind"], "bad_parameter");
+    assert!(upstream.requests().is_empty());
+}
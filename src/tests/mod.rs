@@ -1,15 +1,35 @@
 pub mod core;
 
+pub mod access_tests;
+pub mod agent_write_tests;
 pub mod agents_tests;
 pub mod agent_specific_tests;  // New module for agent-specific endpoints
 pub mod auth_tests;
+pub mod cache_admin_tests;
+pub mod cache_tests;
+pub mod coalescing_tests;
 pub mod decoders_tests;
+pub mod error_tests;
+pub mod events_tests;
 pub mod groups_tests;
 pub mod groups_with_agents_tests;
+pub mod inventory_tests;
 pub mod lists_tests;
+pub mod logout_tests;
 pub mod manager_tests;
 pub mod mitre_tests;
+pub mod pagination_tests;
+pub mod path_params_tests;
+pub mod query_tests;
+pub mod rate_limit_tests;
+pub mod refresh_tests;
+pub mod resilience_tests;
 pub mod rules_tests;
 pub mod security_tests;
+pub mod session_tests;
 pub mod syscollector_tests;
 pub mod tasks_tests;
+pub mod tenant_tests;
+pub mod tls_tests;
+pub mod upstream_tests;
+pub mod wql_tests;
@@ -0,0 +1,128 @@
+use axum::{body::Body, http::{header, Request, StatusCode}};
+use serde_json::{json, Value};
+
+use crate::create_router;
+use crate::shared::pagination::PaginationConfig;
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+fn state_with_pages(upstream: &MockUpstream, page_size: u64, max_items: u64) -> AppState {
+    let mut state = upstream.app_state();
+    let mut config = (*state.config).clone();
+    config.pagination = PaginationConfig { page_size, max_items };
+    state.config = std::sync::Arc::new(config);
+    state
+}
+
+#[tokio::test]
+async fn test_all_pages_merges_affected_items() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 25);
+    let app = create_router(state_with_pages(&upstream, 10, 100));
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "all_pages": true, "query": { "q": "status=active" } });
+
+    let (status, response) = post_json(&app, "/agents", body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let items = response["data"]["affected_items"].as_array().unwrap();
+    assert_eq!(items.len(), 25);
+    assert_eq!(items[24]["id"], "024");
+    assert_eq!(response["data"]["total_affected_items"], 25);
+    assert_eq!(
+        upstream.requests(),
+        vec![
+            "/agents?limit=10&offset=0&q=status%3Dactive",
+            "/agents?limit=10&offset=10&q=status%3Dactive",
+            "/agents?limit=10&offset=20&q=status%3Dactive",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_all_pages_enforces_ceiling() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/agents", 50);
+    let app = create_router(state_with_pages(&upstream, 10, 20));
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "all_pages": true });
+
+    let (status, response) = post_json(&app, "/agents", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(response["error"].as_str().unwrap().contains("50 items"));
+    assert_eq!(upstream.requests().len(), 1, "Only the first page should be fetched");
+}
+
+#[tokio::test]
+async fn test_all_pages_detects_count_mismatch() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items_reporting("/agents", 15, 30);
+    let app = create_router(state_with_pages(&upstream, 10, 100));
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "all_pages": true });
+
+    let (status, response) = post_json(&app, "/agents", body).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(response["kind"], "pagination");
+}
+
+#[tokio::test]
+async fn test_all_pages_streams_ndjson() {
+    let upstream = MockUpstream::start().await;
+    upstream.serve_items("/rules", 23);
+    let app = create_router(state_with_pages(&upstream, 10, 100));
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "all_pages": true, "format": "ndjson" });
+    let request = Request::post("/rules")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let lines: Vec<Value> = std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 23);
+    assert_eq!(lines[0]["id"], "000");
+    assert_eq!(upstream.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_all_pages_rejects_unpaged_endpoint() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "all_pages": true });
+
+    let (status, _) = post_json(&app, "/agents/summary/status", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_all_pages_rejects_explicit_offset() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "all_pages": true, "query": { "offset": 10 } });
+
+    let (status, _) = post_json(&app, "/agents", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_ndjson_requires_all_pages() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "format": "ndjson" });
+
+    let (status, _) = post_json(&app, "/agents", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,74 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::create_router;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+#[tokio::test]
+async fn test_path_params_fill_upstream_path() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t" });
+
+    let (status, _) = post_json(&app, "/agents/001/stats/logcollector", body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.requests(), vec!["/agents/001/stats/logcollector"]);
+}
+
+#[tokio::test]
+async fn test_conflicting_body_param_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "params": { "agent_id": "002" } });
+
+    let (status, response) = post_json(&app, "/syscollector/001/os", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(response["error"].as_str().unwrap().contains("agent_id"));
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_traversal_in_path_param_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t" });
+
+    let (status, _) = post_json(&app, "/syscheck/..%2Fsecurity%2Fusers", body.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_json(&app, "/syscheck/..", body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_traversal_in_body_param_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({
+        "endpoint": upstream.url(),
+        "token": "t",
+        "params": { "agent_id": "../../security/users" }
+    });
+
+    let (status, _) = post_json(&app, "/syscheck/..%2F..%2Fsecurity%2Fusers", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_path_params_are_percent_encoded() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t" });
+
+    let (status, _) = post_json(&app, "/sca/001/checks/cis%20debian%3F", body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.requests(), vec!["/sca/001/checks/cis%20debian%3F"]);
+}
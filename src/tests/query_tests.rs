@@ -0,0 +1,54 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::create_router;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+#[tokio::test]
+async fn test_query_is_forwarded_url_encoded() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({
+        "endpoint": upstream.url(),
+        "token": "t",
+        "params": { "agent_id": "001" },
+        "query": { "limit": 50, "offset": 100, "search": "open ssl", "select": ["name", "version"] }
+    });
+
+    let (status, _) = post_json(&app, "/syscollector/001/packages", body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        upstream.requests(),
+        vec!["/syscollector/001/packages?limit=50&offset=100&search=open+ssl&select=name%2Cversion"]
+    );
+}
+
+#[tokio::test]
+async fn test_query_is_part_of_cache_key() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let page = |offset: u32| json!({ "endpoint": upstream.url(), "token": "t", "query": { "offset": offset, "limit": 10 } });
+
+    let (_, first) = post_json(&app, "/rules", page(0)).await;
+    let (_, second) = post_json(&app, "/rules", page(10)).await;
+    let (_, first_again) = post_json(&app, "/rules", page(0)).await;
+
+    assert_ne!(first, second, "Different pages must not share a cache entry");
+    assert_eq!(first, first_again);
+    assert_eq!(upstream.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_unsupported_query_parameter_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": upstream.url(), "token": "t", "query": { "limit": 10 } });
+
+    let (status, error) = post_json(&app, "/manager/info", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["error"].as_str().unwrap().contains("limit"));
+    assert!(upstream.requests().is_empty());
+}
@@ -0,0 +1,183 @@
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use governor::Quota;
+use nonzero_ext::nonzero;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+use crate::create_router;
+use crate::shared::rate_limit::RateLimitConfig;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+const AUTH_PATH: &str = "/security/user/authenticate";
+
+fn limits(configure: impl FnOnce(&mut RateLimitConfig)) -> RateLimitConfig {
+    let mut config = RateLimitConfig {
+        auth_per_ip: None,
+        auth_per_user: None,
+        lockout_after: 0,
+        ..Default::default()
+    };
+    configure(&mut config);
+    config
+}
+
+/// Posts a login from `peer`, returning the status and `Retry-After` header.
+async fn login_from(app: &Router, peer: &str, username: &str) -> (StatusCode, Option<u64>) {
+    let mut request = Request::post("/auth")
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "username": username, "password": "guess" }).to_string()))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let retry_after = response.headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    (response.status(), retry_after)
+}
+
+#[tokio::test]
+async fn test_repeated_401s_lock_the_user_out() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_with(AUTH_PATH, StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.rate_limits = limits(|l| l.lockout_after = 3);
+    }));
+
+    for _ in 0..3 {
+        let (status, _) = login_from(&app, "10.0.0.1:5000", "admin").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    let (status, retry_after) = login_from(&app, "10.0.0.1:5000", "admin").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after, Some(30));
+    assert_eq!(upstream.hits(AUTH_PATH), 3, "Locked out guesses never reach Wazuh");
+
+    // The username is locked from every address, and the address for every username
+    let (status, _) = login_from(&app, "10.0.0.2:5000", "admin").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let (status, _) = login_from(&app, "10.0.0.1:5000", "other").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let (status, _) = login_from(&app, "10.0.0.2:5000", "other").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_successful_login_resets_failures() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.rate_limits = limits(|l| l.lockout_after = 2);
+    }));
+
+    for i in 1..=3 {
+        let addr = format!("10.0.0.{}:5000", i);
+        upstream.respond_once(AUTH_PATH, StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+        let (status, _) = login_from(&app, &addr, "admin").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = login_from(&app, &addr, "admin").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_successful_login_keeps_the_address_locked() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.rate_limits = limits(|l| l.lockout_after = 2);
+    }));
+
+    upstream.respond_once(AUTH_PATH, StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "alice").await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "mallory").await.0, StatusCode::OK);
+
+    // A valid account does not wipe the guesses made from the same address
+    upstream.respond_once(AUTH_PATH, StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "bob").await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "mallory").await.0, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_logins_are_limited_per_user_and_per_ip() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.rate_limits = limits(|l| {
+            l.auth_per_user = Some(Quota::per_hour(nonzero!(2u32)));
+            l.auth_per_ip = Some(Quota::per_hour(nonzero!(2u32)));
+        });
+    }));
+
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "alice").await.0, StatusCode::OK);
+    assert_eq!(login_from(&app, "10.0.0.2:5000", "alice").await.0, StatusCode::OK);
+    let (status, retry_after) = login_from(&app, "10.0.0.3:5000", "alice").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(retry_after.unwrap() > 60);
+
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "bob").await.0, StatusCode::OK);
+    assert_eq!(login_from(&app, "10.0.0.1:5000", "carol").await.0, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(login_from(&app, "10.0.0.4:5000", "carol").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_session_login_is_limited_too() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_with(AUTH_PATH, StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.sessions.enabled = true;
+        config.rate_limits = limits(|l| l.lockout_after = 1);
+    }));
+    let credentials = json!({ "username": "wazuh", "password": "guess" });
+
+    let (status, _) = post_json(&app, "/session", credentials.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, error) = post_json(&app, "/session", credentials).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error["kind"], "rate_limited");
+}
+
+#[tokio::test]
+async fn test_module_quota() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.rate_limits = limits(|l| {
+            l.routes.insert("rules".to_string(), Quota::per_hour(nonzero!(2u32)));
+        });
+    }));
+    let body: Value = json!({ "token": "t" });
+
+    assert_eq!(post_json(&app, "/rules", body.clone()).await.0, StatusCode::OK);
+    assert_eq!(post_json(&app, "/rules/groups", body.clone()).await.0, StatusCode::OK);
+    let (status, error) = post_json(&app, "/rules", body.clone()).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error["kind"], "rate_limited");
+
+    assert_eq!(post_json(&app, "/decoders", body).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_forwarded_for_is_only_used_when_trusted() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state_configured(|config| {
+        config.rate_limits = limits(|l| {
+            l.auth_per_ip = Some(Quota::per_hour(nonzero!(1u32)));
+            l.trust_forwarded_for = true;
+        });
+    }));
+
+    for (forwarded, expected) in [("203.0.113.1", StatusCode::OK), ("203.0.113.2, 10.0.0.1", StatusCode::OK), ("203.0.113.1", StatusCode::TOO_MANY_REQUESTS)] {
+        let request = Request::post("/auth")
+            .header("content-type", "application/json")
+            .header("x-forwarded-for", forwarded)
+            .body(Body::from(json!({ "username": forwarded, "password": "p" }).to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), expected, "{}", forwarded);
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use crate::client::{CacheConfig, TtlPolicy, WazuhClient};
+use crate::shared::warmup::{self, WarmupConfig};
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::MOCK_TOKEN;
+
+fn short_lived_client(stale_window: Duration) -> WazuhClient {
+    WazuhClient::with_cache_config(&CacheConfig {
+        ttl: TtlPolicy::new(Duration::from_millis(100)),
+        stale_window,
+        ..Default::default()
+    })
+}
+
+async fn wait_for_hits(upstream: &MockUpstream, path: &str, expected: usize) {
+    for _ in 0..50 {
+        if upstream.hits(path) >= expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Expected {} hits on {}, got {}", expected, path, upstream.hits(path));
+}
+
+#[tokio::test]
+async fn test_expired_entry_is_served_stale_and_refreshed() {
+    let upstream = MockUpstream::start().await;
+    let client = short_lived_client(Duration::from_secs(60));
+    let url = format!("{}/manager/stats/weekly", upstream.url());
+
+    let first = client.get_cached(&url, Some("token-a")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    // Expired: the old value comes back immediately and a refresh starts.
+    let stale = client.get_cached(&url, Some("token-a")).await.unwrap();
+    assert_eq!(stale, first);
+    wait_for_hits(&upstream, "/manager/stats/weekly", 2).await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let refreshed = client.get_cached(&url, Some("token-a")).await.unwrap();
+    assert_ne!(refreshed, first, "The refreshed value should replace the stale one");
+    assert_eq!(upstream.hits("/manager/stats/weekly"), 2);
+
+    let stats = client.cache_stats();
+    assert_eq!(stats.stale_hits, 1);
+    assert_eq!(stats.refreshes, 1);
+}
+
+#[tokio::test]
+async fn test_no_stale_window_waits_for_upstream() {
+    let upstream = MockUpstream::start().await;
+    let client = short_lived_client(Duration::ZERO);
+    let url = format!("{}/mitre/techniques", upstream.url());
+
+    let first = client.get_cached(&url, Some("token-a")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let second = client.get_cached(&url, Some("token-a")).await.unwrap();
+
+    assert_ne!(first, second);
+    assert_eq!(client.cache_stats().stale_hits, 0);
+}
+
+#[tokio::test]
+async fn test_warmup_prefetches_configured_paths() {
+    let upstream = MockUpstream::start().await;
+    let mut config = (*upstream.app_state().config).clone();
+    config.warmup = WarmupConfig {
+        paths: vec!["mitre/techniques".to_string(), "manager/stats/weekly".to_string()],
+        interval: None,
+    };
+    let state = AppState::new(WazuhClient::new(), config);
+
+    warmup::spawn(state.clone()).expect("Warm-up should start").await.unwrap();
+    assert_eq!(upstream.hits("/mitre/techniques"), 1);
+    assert_eq!(upstream.hits("/manager/stats/weekly"), 1);
+
+    // Requests made with the service token are now cache hits.
+    let url = format!("{}/mitre/techniques", upstream.url());
+    state.client.get_cached(&url, Some(MOCK_TOKEN)).await.unwrap();
+    assert_eq!(upstream.hits("/mitre/techniques"), 1);
+}
+
+#[tokio::test]
+async fn test_warmup_repeats_on_interval() {
+    let upstream = MockUpstream::start().await;
+    let mut config = (*upstream.app_state().config).clone();
+    config.warmup = WarmupConfig {
+        paths: vec!["groups".to_string()],
+        interval: Some(Duration::from_millis(50)),
+    };
+    let state = AppState::new(short_lived_client(Duration::ZERO), config);
+
+    let handle = warmup::spawn(state).unwrap();
+    wait_for_hits(&upstream, "/groups", 3).await;
+    handle.abort();
+
+    // One login serves every cycle while the token is young.
+    assert_eq!(upstream.hits("/security/user/authenticate"), 1);
+}
+
+#[tokio::test]
+async fn test_warmup_disabled_without_paths() {
+    let upstream = MockUpstream::start().await;
+    assert!(warmup::spawn(upstream.app_state()).is_none());
+}
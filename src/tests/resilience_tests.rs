@@ -0,0 +1,147 @@
+use axum::{body::Body, http::{Request, StatusCode}};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::client::{BreakerConfig, RetryConfig};
+use crate::create_router;
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json};
+
+const UNAVAILABLE: &str = r#"{"title": "Service Unavailable"}"#;
+
+fn fast_retries(max_retries: u32) -> RetryConfig {
+    RetryConfig {
+        max_retries,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(5),
+        max_retry_after: Duration::from_secs(2),
+    }
+}
+
+fn state(upstream: &MockUpstream, retry: RetryConfig, breaker: BreakerConfig) -> AppState {
+    upstream.app_state_configured(|config| {
+        config.retry = retry;
+        config.breaker = breaker;
+    })
+}
+
+fn body(upstream: &MockUpstream) -> serde_json::Value {
+    json!({ "endpoint": upstream.url(), "token": "t" })
+}
+
+#[tokio::test]
+async fn test_transient_5xx_is_retried() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_once("/rules", StatusCode::SERVICE_UNAVAILABLE, UNAVAILABLE);
+    upstream.respond_once("/rules", StatusCode::BAD_GATEWAY, UNAVAILABLE);
+    let app = create_router(state(&upstream, fast_retries(2), BreakerConfig::default()));
+
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/rules"), 3);
+}
+
+#[tokio::test]
+async fn test_retries_are_bounded() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_with("/rules", StatusCode::SERVICE_UNAVAILABLE, UNAVAILABLE);
+    let app = create_router(state(&upstream, fast_retries(2), BreakerConfig::default()));
+
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(upstream.hits("/rules"), 3);
+}
+
+#[tokio::test]
+async fn test_internal_server_error_is_not_retried() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_with("/rules", StatusCode::INTERNAL_SERVER_ERROR, r#"{"title": "Internal Error"}"#);
+    let app = create_router(state(&upstream, fast_retries(2), BreakerConfig::default()));
+
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(upstream.hits("/rules"), 1);
+}
+
+#[tokio::test]
+async fn test_rate_limit_waits_for_retry_after() {
+    let upstream = MockUpstream::start().await;
+    upstream.rate_limit_once("/rules", 1);
+    let app = create_router(state(&upstream, fast_retries(1), BreakerConfig::default()));
+
+    let started = Instant::now();
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_secs(1), "Retry-After must be honoured");
+    assert_eq!(upstream.hits("/rules"), 2);
+}
+
+#[tokio::test]
+async fn test_long_retry_after_is_returned_to_caller() {
+    let upstream = MockUpstream::start().await;
+    upstream.rate_limit_once("/rules", 120);
+    let app = create_router(state(&upstream, fast_retries(2), BreakerConfig::default()));
+
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(upstream.hits("/rules"), 1);
+}
+
+#[tokio::test]
+async fn test_breaker_opens_and_fails_fast() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_with("/rules", StatusCode::SERVICE_UNAVAILABLE, UNAVAILABLE);
+    let breaker = BreakerConfig { failure_threshold: 2, open_for: Duration::from_secs(60) };
+    let app = create_router(state(&upstream, fast_retries(0), breaker));
+
+    post_json(&app, "/rules", body(&upstream)).await;
+    post_json(&app, "/rules", body(&upstream)).await;
+    let (status, error) = post_json(&app, "/rules", body(&upstream)).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(error["kind"], "circuit_open");
+    assert_eq!(upstream.hits("/rules"), 2, "An open breaker must not reach Wazuh");
+
+    let (status, health) = call(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(health["status"], "degraded");
+    assert_eq!(health["upstreams"][0]["breaker"]["state"], "open");
+}
+
+#[tokio::test]
+async fn test_breaker_closes_after_successful_probe() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_once("/rules", StatusCode::SERVICE_UNAVAILABLE, UNAVAILABLE);
+    let breaker = BreakerConfig { failure_threshold: 1, open_for: Duration::ZERO };
+    let app = create_router(state(&upstream, fast_retries(0), breaker));
+
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let (status, _) = post_json(&app, "/rules", body(&upstream)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, health) = call(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["upstreams"][0]["breaker"]["state"], "closed");
+}
+
+#[tokio::test]
+async fn test_run_as_login_is_not_retried() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_once("/security/user/authenticate/run_as", StatusCode::SERVICE_UNAVAILABLE, UNAVAILABLE);
+    let app = create_router(state(&upstream, fast_retries(2), BreakerConfig::default()));
+
+    let (status, _) = post_json(&app, "/auth/run_as", json!({
+        "username": "wazuh", "password": "wazuh", "auth_context": {}
+    })).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(upstream.hits("/security/user/authenticate/run_as"), 1, "A login may have been issued; it must not be sent twice");
+}
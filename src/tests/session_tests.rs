@@ -0,0 +1,206 @@
+use axum::{body::Body, http::{header, Request, StatusCode}, Router};
+use serde_json::{json, Value};
+
+use crate::create_router;
+use crate::shared::warmup::WarmupConfig;
+use crate::shared::{warmup, AppState};
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::{call, post_json, MOCK_TOKEN};
+
+fn sessions_enabled(upstream: &MockUpstream) -> AppState {
+    upstream.app_state_configured(|config| config.sessions.enabled = true)
+}
+
+async fn login(app: &Router) -> (String, String) {
+    let request = Request::post("/session")
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "username": "wazuh", "password": "wazuh" }).to_string()))
+        .unwrap();
+    let response = tower::ServiceExt::oneshot(app.clone(), request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap().to_string();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    (body["session"].as_str().unwrap().to_string(), cookie)
+}
+
+async fn post_with(app: &Router, path: &str, header_name: header::HeaderName, value: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(path)
+        .header("content-type", "application/json")
+        .header(header_name, value)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    call(app, request).await
+}
+
+#[tokio::test]
+async fn test_sessions_are_disabled_by_default() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, error) = post_json(&app, "/session", json!({ "username": "wazuh", "password": "wazuh" })).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["kind"], "unauthorized");
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_session_cookie_replaces_token() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+
+    let (session, cookie) = login(&app).await;
+    assert!(session.starts_with("nxs_"));
+    assert!(cookie.starts_with(&format!("nexus_session={};", session)));
+    assert!(cookie.contains("HttpOnly"));
+
+    let (status, _) = post_with(&app, "/rules", header::COOKIE, &format!("nexus_session={}", session), json!({})).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/rules"), 1);
+}
+
+#[tokio::test]
+async fn test_session_bearer_header_is_accepted() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+    let (session, _) = login(&app).await;
+
+    let (status, _) = post_with(&app, "/rules", header::AUTHORIZATION, &format!("Bearer {}", session), json!({})).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_expired_wazuh_token_is_renewed_once() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+    let (session, _) = login(&app).await;
+    upstream.revoke_token(MOCK_TOKEN);
+
+    let bearer = format!("Bearer {}", session);
+    let (status, _) = post_with(&app, "/rules", header::AUTHORIZATION, &bearer, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/security/user/authenticate"), 2);
+    assert_eq!(upstream.hits("/rules"), 2, "The rejected call is retried once");
+
+    // The renewed token is kept for later calls
+    let (status, _) = post_with(&app, "/agents", header::AUTHORIZATION, &bearer, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/security/user/authenticate"), 2);
+}
+
+#[tokio::test]
+async fn test_plain_token_401_is_not_retried() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+    upstream.revoke_token("expired");
+
+    let (status, _) = post_json(&app, "/rules", json!({ "token": "expired" })).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(upstream.hits("/rules"), 1);
+    assert_eq!(upstream.hits("/security/user/authenticate"), 0);
+}
+
+#[tokio::test]
+async fn test_missing_token_and_session_is_unauthorized() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+
+    let (status, error) = post_json(&app, "/rules", json!({})).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["kind"], "unauthorized");
+}
+
+#[tokio::test]
+async fn test_unknown_session_is_unauthorized() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+
+    let (status, _) = post_with(&app, "/rules", header::COOKIE, "nexus_session=nxs_forged", json!({})).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_token_and_session_together_are_rejected() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+    let (session, _) = login(&app).await;
+
+    let (status, _) = post_with(&app, "/rules", header::AUTHORIZATION, &format!("Bearer {}", session), json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_logout_ends_session() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(sessions_enabled(&upstream));
+    let (session, _) = login(&app).await;
+    let bearer = format!("Bearer {}", session);
+
+    let request = Request::get("/session").header(header::AUTHORIZATION, &bearer).body(Body::empty()).unwrap();
+    let (status, info) = call(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(info["username"], "wazuh");
+    assert!(info["session"].is_null(), "The id is only returned at login");
+
+    let request = Request::delete("/session").header(header::AUTHORIZATION, &bearer).body(Body::empty()).unwrap();
+    let (status, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = post_with(&app, "/rules", header::AUTHORIZATION, &bearer, json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_invalid_credentials_do_not_create_session() {
+    let upstream = MockUpstream::start().await;
+    upstream.respond_once("/security/user/authenticate", StatusCode::UNAUTHORIZED, r#"{"title": "Unauthorized"}"#);
+    let app = create_router(sessions_enabled(&upstream));
+
+    let (status, body) = post_json(&app, "/session", json!({ "username": "wazuh", "password": "nope" })).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["session"].is_null());
+}
+
+#[tokio::test]
+async fn test_warmup_renews_rejected_service_token() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state_configured(|config| {
+        config.warmup = WarmupConfig { paths: vec!["groups".to_string()], interval: None };
+    });
+    // Log the service session in, then let Wazuh expire its token.
+    state.service_session().await.unwrap();
+    upstream.revoke_token(MOCK_TOKEN);
+
+    warmup::spawn(state).unwrap().await.unwrap();
+
+    assert_eq!(upstream.hits("/security/user/authenticate"), 2);
+    assert_eq!(upstream.hits("/groups"), 2);
+}
+
+#[tokio::test]
+async fn test_warmup_serves_live_sessions() {
+    let upstream = MockUpstream::start().await;
+    let state = upstream.app_state_configured(|config| {
+        config.sessions.enabled = true;
+        config.warmup = WarmupConfig { paths: vec!["rules".to_string()], interval: None };
+    });
+    let app = create_router(state.clone());
+    let (session, _) = login(&app).await;
+
+    warmup::spawn(state).unwrap().await.unwrap();
+    assert_eq!(upstream.hits("/rules"), 2, "Once for the service account, once for the session");
+
+    let (status, _) = post_with(&app, "/rules", header::AUTHORIZATION, &format!("Bearer {}", session), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/rules"), 2, "Served from the entry warmed with the session's token");
+}
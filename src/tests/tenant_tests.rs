@@ -0,0 +1,150 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use crate::create_router;
+use crate::shared::access::API_KEY_HEADER;
+use crate::shared::AppState;
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::call;
+
+/// Key store with two tenants on one manager; every role may read everything.
+fn tenant_state(upstream: &MockUpstream) -> (AppState, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.json");
+    let file = json!({
+        "roles": { "analyst": { "modules": { "*": "read" } } },
+        "tenants": {
+            "acme": { "groups": ["acme-web", "acme-db"] },
+            "globex": { "groups": ["globex"] }
+        }
+    });
+    std::fs::write(&path, file.to_string()).unwrap();
+
+    upstream.agent_in_groups("001", &["acme-web"]);
+    upstream.agent_in_groups("002", &["globex"]);
+    upstream.agent_in_groups("003", &["default", "acme-db"]);
+
+    let state = upstream.app_state_configured(|config| config.access.key_file = Some(path));
+    (state, dir)
+}
+
+fn key_for(state: &AppState, tenant: Option<&str>) -> String {
+    let id = tenant.unwrap_or("operator");
+    state.access.as_ref().unwrap().issue(id, vec!["analyst".to_string()], tenant.map(String::from)).unwrap()
+}
+
+async fn post_as(app: &Router, key: &str, path: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(path)
+        .header("content-type", "application/json")
+        .header(API_KEY_HEADER, key)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    call(app, request).await
+}
+
+#[tokio::test]
+async fn test_agent_routes_reject_other_tenants_agents() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = tenant_state(&upstream);
+    let acme = key_for(&state, Some("acme"));
+    let app = create_router(state);
+
+    let (status, _) = post_as(&app, &acme, "/syscollector/001/os", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_as(&app, &acme, "/sca/003", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK, "One owned group is enough");
+
+    for path in ["/syscollector/002/os", "/syscheck/002", "/rootcheck/002", "/ciscat/002/results", "/agents/002/daemons/stats", "/sca/999"] {
+        let (status, error) = post_as(&app, &acme, path, json!({ "token": "t" })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+        assert_eq!(error["kind"], "forbidden");
+        assert_eq!(upstream.hits(path), 0, "{} must not reach Wazuh", path);
+    }
+}
+
+#[tokio::test]
+async fn test_agent_listings_are_filtered_to_tenant_groups() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = tenant_state(&upstream);
+    let acme = key_for(&state, Some("acme"));
+    let app = create_router(state);
+
+    let (status, _) = post_as(&app, &acme, "/agents", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_as(&app, &acme, "/agents/outdated", json!({ "token": "t", "query": { "q": "os.platform=ubuntu" } })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let requests = upstream.requests();
+    assert!(requests.contains(&"/agents?q=group%3Dacme-db%2Cgroup%3Dacme-web".to_string()), "{:?}", requests);
+    assert!(requests.contains(&"/agents/outdated?q=%28os.platform%3Dubuntu%29%3B%28group%3Dacme-db%2Cgroup%3Dacme-web%29".to_string()), "{:?}", requests);
+
+    let (status, _) = post_as(&app, &acme, "/agents/summary/status", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_as(&app, &acme, "/agents/no_group", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_group_routes_are_limited_to_tenant_groups() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = tenant_state(&upstream);
+    let acme = key_for(&state, Some("acme"));
+    let app = create_router(state);
+
+    let (status, _) = post_as(&app, &acme, "/groups/acme-web/agents", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_as(&app, &acme, "/groups/globex/agents", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_as(&app, &acme, "/wql/globex", json!({ "report_type": "daily" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(upstream.hits("/groups/globex/agents"), 0);
+}
+
+#[tokio::test]
+async fn test_keys_without_tenant_are_not_restricted() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = tenant_state(&upstream);
+    let operator = key_for(&state, None);
+    let app = create_router(state);
+
+    let (status, _) = post_as(&app, &operator, "/syscollector/002/os", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_as(&app, &operator, "/agents/summary/status", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(upstream.requests().iter().all(|p| !p.contains("select=group")), "No membership lookups");
+}
+
+#[tokio::test]
+async fn test_unknown_tenant_cannot_be_issued() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = tenant_state(&upstream);
+
+    let result = state.access.as_ref().unwrap().issue("k", vec!["analyst".to_string()], Some("initech".to_string()));
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_q_cannot_escape_the_tenant_filter() {
+    let upstream = MockUpstream::start().await;
+    let (state, _dir) = tenant_state(&upstream);
+    let acme = key_for(&state, Some("acme"));
+    let app = create_router(state);
+
+    for q in ["id=002),(id!=x", "id=002),(group=globex"] {
+        let (status, error) = post_as(&app, &acme, "/agents", json!({ "token": "t", "query": { "q": q } })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", q);
+        assert_eq!(error["kind"], "bad_parameter");
+    }
+    assert_eq!(upstream.hits("/agents"), 0);
+
+    // Whatever Wazuh matches, only the tenant's agents come back
+    let (status, listing) = post_as(&app, &acme, "/agents", json!({ "token": "t", "query": { "agents_list": "001,002,003" } })).await;
+    assert_eq!(status, StatusCode::OK);
+    let ids: Vec<&str> = listing["data"]["affected_items"].as_array().unwrap().iter().filter_map(|a| a["id"].as_str()).collect();
+    assert_eq!(ids, vec!["001", "003"]);
+    assert_eq!(listing["data"]["total_affected_items"], 2);
+}
@@ -0,0 +1,93 @@
+use crate::client::{TlsConfig, WazuhClient};
+use crate::tests::core::tls_upstream::{fixture, TlsUpstream};
+
+fn server_pin() -> String {
+    std::fs::read_to_string(fixture("server.pin")).unwrap().trim().to_string()
+}
+
+async fn fetch(upstream: &TlsUpstream, tls: TlsConfig) -> Result<serde_json::Value, String> {
+    let client = WazuhClient::new().with_upstream("lab", &upstream.url(), &tls)?;
+    client.get_json(&format!("{}/agents", upstream.url()), Some("t"))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tokio::test]
+async fn test_custom_ca_bundle_is_trusted() {
+    let upstream = TlsUpstream::start(false).await;
+    let tls = TlsConfig { ca_file: Some(fixture("ca.pem")), ..Default::default() };
+
+    let data = fetch(&upstream, tls).await.unwrap();
+
+    assert_eq!(data["data"]["tls"], true);
+}
+
+#[tokio::test]
+async fn test_self_signed_server_is_rejected_by_default() {
+    let upstream = TlsUpstream::start(false).await;
+
+    assert!(fetch(&upstream, TlsConfig::default()).await.is_err(), "System roots must not trust the test CA");
+}
+
+#[tokio::test]
+async fn test_unrelated_ca_is_rejected() {
+    let upstream = TlsUpstream::start(false).await;
+    let tls = TlsConfig { ca_file: Some(fixture("other-ca.pem")), ..Default::default() };
+
+    assert!(fetch(&upstream, tls).await.is_err());
+}
+
+#[tokio::test]
+async fn test_insecure_mode_skips_verification() {
+    let upstream = TlsUpstream::start(false).await;
+    let tls = TlsConfig { insecure: true, ..Default::default() };
+
+    assert!(fetch(&upstream, tls).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_matching_spki_pin_is_accepted() {
+    let upstream = TlsUpstream::start(false).await;
+    let tls = TlsConfig {
+        ca_file: Some(fixture("ca.pem")),
+        spki_pins: vec![format!("sha256/{}", server_pin())],
+        ..Default::default()
+    };
+
+    assert!(fetch(&upstream, tls).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_mismatched_spki_pin_is_rejected() {
+    let upstream = TlsUpstream::start(false).await;
+    let wrong_pin = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_string();
+    let tls = TlsConfig { ca_file: Some(fixture("ca.pem")), spki_pins: vec![wrong_pin.clone()], ..Default::default() };
+    assert!(fetch(&upstream, tls).await.is_err());
+
+    // Pins are enforced even when chain verification is off
+    let tls = TlsConfig { insecure: true, spki_pins: vec![wrong_pin], ..Default::default() };
+    assert!(fetch(&upstream, tls).await.is_err());
+}
+
+#[tokio::test]
+async fn test_client_certificate_is_presented() {
+    let upstream = TlsUpstream::start(true).await;
+    let without_cert = TlsConfig { ca_file: Some(fixture("ca.pem")), ..Default::default() };
+    let with_cert = TlsConfig {
+        client_cert_file: Some(fixture("client.pem")),
+        client_key_file: Some(fixture("client-key.pem")),
+        ..without_cert.clone()
+    };
+
+    assert!(fetch(&upstream, without_cert).await.is_err());
+    assert!(fetch(&upstream, with_cert).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_missing_ca_file_fails_at_startup() {
+    let tls = TlsConfig { ca_file: Some(fixture("missing.pem")), ..Default::default() };
+
+    let error = WazuhClient::new().with_upstream("lab", "https://localhost:55000", &tls).err().unwrap();
+
+    assert!(error.contains("missing.pem"));
+}
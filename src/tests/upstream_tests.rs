@@ -0,0 +1,89 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::create_router;
+use crate::shared::upstreams::{Upstream, UpstreamRegistry};
+use crate::tests::core::MockUpstream;
+use crate::tests::core::mock_upstream::post_json;
+
+fn registry(upstreams: &[(&str, &MockUpstream)]) -> UpstreamRegistry {
+    let upstreams = upstreams
+        .iter()
+        .map(|(name, mock)| Upstream { name: name.to_string(), url: mock.url(), tls: Default::default() })
+        .collect();
+    UpstreamRegistry::new(upstreams, None).unwrap()
+}
+
+#[tokio::test]
+async fn test_default_upstream_is_used_without_endpoint() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, _) = post_json(&app, "/rules", json!({ "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.hits("/rules"), 1);
+}
+
+#[tokio::test]
+async fn test_upstream_is_selected_by_name() {
+    let prod = MockUpstream::start().await;
+    let lab = MockUpstream::start().await;
+    let app = create_router(prod.app_state_with(registry(&[("prod", &prod), ("lab", &lab)])));
+
+    let (status, _) = post_json(&app, "/rules", json!({ "upstream": "lab", "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(prod.requests().is_empty());
+    assert_eq!(lab.hits("/rules"), 1);
+}
+
+#[tokio::test]
+async fn test_unregistered_endpoint_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let outsider = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, error) = post_json(&app, "/rules", json!({ "endpoint": outsider.url(), "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["kind"], "unknown_upstream");
+    assert!(outsider.requests().is_empty());
+    assert!(upstream.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_unknown_upstream_name_is_rejected() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, error) = post_json(&app, "/rules", json!({ "upstream": "staging", "token": "t" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["kind"], "unknown_upstream");
+}
+
+#[tokio::test]
+async fn test_auth_rejects_unregistered_endpoint() {
+    let upstream = MockUpstream::start().await;
+    let outsider = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+    let body = json!({ "endpoint": outsider.url(), "username": "wazuh", "password": "wazuh" });
+
+    let (status, response) = post_json(&app, "/auth", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(response["token"].is_null());
+    assert!(outsider.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_auth_uses_default_upstream() {
+    let upstream = MockUpstream::start().await;
+    let app = create_router(upstream.app_state());
+
+    let (status, response) = post_json(&app, "/auth", json!({ "username": "wazuh", "password": "wazuh" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(response["token"].is_string());
+}
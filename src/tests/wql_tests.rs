@@ -0,0 +1,381 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use futures::future::join_all;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::create_router;
+use crate::features::wql::{handle_wql_query, ReportType, Version, WqlTransport};
+use crate::shared::wql::{WqlCompression, WqlConfig, WqlFraming, WqlProtocol, WqlSettings};
+use crate::tests::core::mock_upstream::call;
+use crate::tests::core::mock_wql_gateway::{MockWqlGateway, Speaks, SERVER_KEY};
+use crate::tests::core::MockUpstream;
+
+const QUERY: &str = r#"{"query":{"match":{"agent.name":"web-01"}}}"#;
+
+fn v2_gateway() -> Speaks {
+    Speaks::V2 { key_id: "s-2024-11".to_string() }
+}
+
+/// Settings that can speak v2: a client key id and an id for the server key.
+fn v2_settings(gateway: &MockWqlGateway) -> WqlSettings {
+    WqlSettings {
+        client_key_id: Some("c-2024-11".to_string()),
+        server_key_id: Some("s-2024-11".to_string()),
+        ..gateway.settings()
+    }
+}
+
+#[tokio::test]
+async fn test_v2_is_negotiated_with_a_v2_gateway() {
+    let gateway = MockWqlGateway::start(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(v2_settings(&gateway)));
+
+    let response = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+
+    assert!(response.status);
+    assert_eq!(response.version, Some(2));
+    assert_eq!(gateway.versions(), vec![Some(2)]);
+    assert_eq!(transport.version(), Version::V2);
+}
+
+#[tokio::test]
+async fn test_auto_falls_back_to_v1_gateway() {
+    let gateway = MockWqlGateway::start(Speaks::V1).await;
+    let transport = WqlTransport::new(gateway.config(v2_settings(&gateway)));
+
+    let response = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    assert!(response.status, "{}", response.data);
+    assert_eq!(gateway.versions(), vec![Some(2), None], "The v2 request is turned down, then sent again in v1");
+
+    // Later requests go straight to v1
+    transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    assert_eq!(gateway.versions(), vec![Some(2), None, None]);
+    assert_eq!(transport.version(), Version::V1);
+}
+
+#[tokio::test]
+async fn test_required_v2_rejects_v1_gateway() {
+    let gateway = MockWqlGateway::start(Speaks::V1).await;
+    let settings = WqlSettings { protocol: Some(WqlProtocol::V2), ..v2_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("requires v2"), "{}", err);
+    assert_eq!(gateway.versions(), vec![Some(2)]);
+}
+
+#[tokio::test]
+async fn test_without_key_ids_only_v1_is_spoken() {
+    let gateway = MockWqlGateway::start(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(gateway.settings()));
+
+    let response = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+
+    assert!(response.status);
+    assert_eq!(response.version, None);
+    assert_eq!(gateway.versions(), vec![None]);
+}
+
+#[tokio::test]
+async fn test_server_key_rotation() {
+    let gateway = MockWqlGateway::start(v2_gateway()).await;
+
+    // The gateway already signs with the new key while the old one is still accepted
+    let rotating = WqlSettings {
+        server_key: None,
+        server_key_id: None,
+        server_keys: Some(BTreeMap::from([
+            ("s-2024-10".to_string(), "previous-secret".to_string()),
+            ("s-2024-11".to_string(), SERVER_KEY.to_string()),
+        ])),
+        ..v2_settings(&gateway)
+    };
+    let transport = WqlTransport::new(gateway.config(rotating));
+    assert!(transport.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+
+    // Nexus no longer knows the key the gateway signs with
+    let retired = WqlSettings {
+        server_key: None,
+        server_key_id: None,
+        server_keys: Some(BTreeMap::from([("s-2024-12".to_string(), SERVER_KEY.to_string())])),
+        ..v2_settings(&gateway)
+    };
+    let transport = WqlTransport::new(gateway.config(retired));
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+    assert!(err.contains("unknown key 's-2024-11'"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_server_name_must_match_certificate() {
+    let gateway = MockWqlGateway::start(Speaks::V1).await;
+    let settings = WqlSettings { server_name: Some("wql.example".to_string()), ..gateway.settings() };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("TLS handshake with wql.example failed"), "{}", err);
+    assert!(gateway.versions().is_empty());
+}
+
+#[tokio::test]
+async fn test_replayed_v1_response_is_rejected() {
+    let gateway = MockWqlGateway::replaying(Speaks::V1).await;
+    let transport = WqlTransport::new(gateway.config(gateway.settings()));
+
+    assert!(transport.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("Replayed response"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_replayed_v2_response_does_not_answer_a_new_request() {
+    let gateway = MockWqlGateway::replaying(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(v2_settings(&gateway)));
+
+    assert!(transport.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("nonce mismatch"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_replayed_v1_frame_does_not_answer_a_v2_request() {
+    let gateway = MockWqlGateway::replaying(Speaks::V1).await;
+    // A v1 client captures a valid v1 answer...
+    let v1 = WqlTransport::new(gateway.config(gateway.settings()));
+    assert!(v1.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+
+    // ...which is then played back to a v2 request
+    let transport = WqlTransport::new(gateway.config(v2_settings(&gateway)));
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("v1 response to a v2 request"), "{}", err);
+    assert_eq!(transport.version(), Version::V2, "No fall back to v1");
+}
+
+#[tokio::test]
+async fn test_negotiated_v2_is_not_given_up() {
+    let gateway = MockWqlGateway::downgrading(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(v2_settings(&gateway)));
+    assert!(transport.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("not falling back to v1"), "{}", err);
+    assert_eq!(gateway.versions(), vec![Some(2), Some(2)], "Never sent again in v1");
+    assert_eq!(transport.version(), Version::V2);
+}
+
+fn framed_settings(gateway: &MockWqlGateway) -> WqlSettings {
+    WqlSettings { framing: Some(WqlFraming::Framed), ..v2_settings(gateway) }
+}
+
+#[tokio::test]
+async fn test_framed_connections_do_not_fall_back_to_v1() {
+    let gateway = MockWqlGateway::framed(Speaks::V1).await;
+    let transport = WqlTransport::new(gateway.config(framed_settings(&gateway)));
+
+    // Identical v1 answers on one session would be told apart by nothing but the nonce they lack
+    for _ in 0..2 {
+        let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+        assert!(err.contains("NEXUS_WQL_FRAMING=framed"), "{}", err);
+        assert!(!err.contains("Replayed"), "{}", err);
+    }
+    assert_eq!(gateway.versions(), vec![Some(2), Some(2)], "Never sent again in v1");
+
+    let v1 = WqlSettings { protocol: Some(WqlProtocol::V1), ..framed_settings(&gateway) };
+    assert!(WqlConfig::from_settings(v1).unwrap_err().contains("needs protocol v2"));
+}
+
+#[tokio::test]
+async fn test_legacy_framing_opens_a_connection_per_request() {
+    let gateway = MockWqlGateway::start(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(v2_settings(&gateway)));
+
+    for _ in 0..2 {
+        assert!(transport.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+    }
+
+    assert_eq!(gateway.connections(), 2);
+    assert_eq!(gateway.sessions(), vec![None, None]);
+}
+
+#[tokio::test]
+async fn test_framed_requests_reuse_the_connection_and_session() {
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(framed_settings(&gateway)));
+
+    let first = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    for _ in 0..2 {
+        let response = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+        assert!(response.status, "{}", response.data);
+        assert_eq!(response.session_id, first.session_id);
+    }
+
+    assert_eq!(gateway.connections(), 1);
+    let session = Some(first.session_id);
+    assert_eq!(gateway.sessions(), vec![None, session.clone(), session]);
+}
+
+#[tokio::test]
+async fn test_framed_requests_can_be_compressed() {
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let settings = WqlSettings { compression: Some(WqlCompression::Gzip), ..framed_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    for _ in 0..2 {
+        let response = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+        assert!(response.data.contains("web-01"), "{}", response.data);
+    }
+
+    assert_eq!(gateway.gzip_requests(), 2);
+}
+
+#[tokio::test]
+async fn test_framed_response_over_the_limit_is_rejected() {
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let settings = WqlSettings { max_response_bytes: Some(64), ..framed_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("exceeds the 64 byte limit"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_pool_bounds_concurrent_requests() {
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let settings = WqlSettings { pool_size: Some(2), ..framed_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    let responses = join_all((0..6).map(|_| transport.send_request_with_retry(QUERY.to_string()))).await;
+    assert!(responses.iter().all(|r| r.as_ref().is_ok_and(|r| r.status)), "{:?}", responses);
+
+    let stats = transport.pool_stats();
+    assert!(gateway.connections() <= 2, "{} connections", gateway.connections());
+    assert_eq!(stats.opened as usize, gateway.connections());
+    assert_eq!(stats.opened + stats.reused, 6);
+    assert_eq!((stats.open, stats.idle, stats.in_use, stats.waiting), (gateway.connections(), gateway.connections(), 0, 0));
+}
+
+#[tokio::test]
+async fn test_legacy_connections_are_not_kept() {
+    let gateway = MockWqlGateway::start(v2_gateway()).await;
+    let settings = WqlSettings { pool_size: Some(1), ..v2_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    let responses = join_all((0..3).map(|_| transport.send_request_with_retry(QUERY.to_string()))).await;
+    assert!(responses.iter().all(|r| r.is_ok()), "{:?}", responses);
+
+    let stats = transport.pool_stats();
+    assert_eq!((stats.opened, stats.reused, stats.open), (3, 0, 0));
+}
+
+#[tokio::test]
+async fn test_idle_connections_are_evicted() {
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let settings = WqlSettings { pool_idle_timeout_secs: Some(1), ..framed_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    assert_eq!(transport.pool_stats().idle, 1);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let stats = transport.pool_stats();
+    assert_eq!((stats.open, stats.idle, stats.closed_idle), (0, 0, 1));
+
+    transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    assert_eq!(gateway.connections(), 2);
+    assert_eq!(gateway.sessions()[1], None, "A new connection authenticates again");
+}
+
+#[tokio::test]
+async fn test_connections_are_replaced_after_their_lifetime() {
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let settings = WqlSettings { pool_max_lifetime_secs: Some(1), ..framed_settings(&gateway) };
+    let transport = WqlTransport::new(gateway.config(settings));
+
+    transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+
+    let stats = transport.pool_stats();
+    assert_eq!((stats.opened, stats.reused, stats.closed_expired), (2, 0, 1));
+    assert_eq!(gateway.connections(), 2);
+}
+
+#[tokio::test]
+async fn test_closed_connections_fail_the_health_check() {
+    let gateway = MockWqlGateway::framed_hanging_up(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(framed_settings(&gateway)));
+
+    transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+    // Let the gateway's close reach us
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = transport.send_request_with_retry(QUERY.to_string()).await.unwrap();
+
+    assert!(response.status);
+    let stats = transport.pool_stats();
+    assert_eq!((stats.opened, stats.closed_unhealthy), (2, 1));
+    assert_eq!(gateway.sessions(), vec![None, None], "No request was sent on the closed connection");
+}
+
+#[tokio::test]
+async fn test_rejected_answer_on_a_reused_connection_counts_as_a_retry() {
+    let gateway = MockWqlGateway::framed_replaying(v2_gateway()).await;
+    let transport = WqlTransport::new(gateway.config(framed_settings(&gateway)));
+    assert!(transport.send_request_with_retry(QUERY.to_string()).await.unwrap().status);
+
+    let err = transport.send_request_with_retry(QUERY.to_string()).await.unwrap_err();
+
+    assert!(err.contains("Max retries exceeded") && err.contains("nonce mismatch"), "{}", err);
+    assert_eq!(gateway.connections(), 1, "Only a broken connection is replaced without counting");
+}
+
+#[tokio::test]
+async fn test_pool_stats_endpoint() {
+    let upstream = MockUpstream::start().await;
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let wql = gateway.config(framed_settings(&gateway));
+
+    let app = create_router(upstream.app_state());
+    let (status, _) = call(&app, Request::get("/wql/pool/stats").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let state = upstream.app_state_configured(|config| config.wql = Some(wql));
+    state.wql.as_ref().unwrap().send_request_with_retry(QUERY.to_string()).await.unwrap();
+    let app = create_router(state);
+    let (status, stats) = call(&app, Request::get("/wql/pool/stats").body(Body::empty()).unwrap()).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stats["max_size"], 8);
+    assert_eq!(stats["opened"], 1);
+    assert_eq!(stats["idle"], 1);
+}
+
+#[tokio::test]
+async fn test_report_queries_agents_concurrently() {
+    let upstream = MockUpstream::start().await;
+    let agents = json!({
+        "data": {
+            "affected_items": [{ "id": "001", "name": "web-01" }, { "id": "002", "name": "web-02" }, { "id": "003", "name": "web-03" }],
+            "total_affected_items": 3
+        },
+        "error": 0
+    });
+    upstream.respond_with("/groups/web/agents", StatusCode::OK, &agents.to_string());
+    let gateway = MockWqlGateway::framed(v2_gateway()).await;
+    let wql = gateway.config(framed_settings(&gateway));
+    let state = upstream.app_state_configured(|config| config.wql = Some(wql));
+
+    // No report service runs here, so only the queries sent are checked
+    let _ = handle_wql_query(&state, "web".to_string(), ReportType::Daily).await;
+
+    assert_eq!(gateway.versions().len(), 3);
+    assert_eq!(gateway.connections(), 3, "Every agent got its own pooled connection at once");
+    assert_eq!(state.wql.as_ref().unwrap().pool_stats().idle, 3);
+}